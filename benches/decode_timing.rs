@@ -0,0 +1,109 @@
+//! Wall-clock comparisons that are too noisy to assert on in tests.
+//!
+//! Run with `cargo bench --bench decode_timing`; each comparison prints
+//! its per-shot times.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use rmatching::Matching;
+
+/// Repetition-code chain with `n` detectors and a boundary at both ends.
+fn rep_code_chain(n: usize) -> Matching {
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 1.0, &[0], 0.1);
+    for i in 0..n - 1 {
+        m.add_edge(i, i + 1, 1.0, &[], 0.1);
+    }
+    m.add_boundary_edge(n - 1, 1.0, &[], 0.1);
+    m
+}
+
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/// `decode_packed_batch` against `decode_batch_into` on 10k detectors with
+/// 1% firing, where the packed path scans each shot a word at a time.
+fn packed_batch_against_byte_batch() {
+    let num_detectors = 10_000;
+    let shot_bytes = num_detectors / 8;
+    let num_shots = 1_000;
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut syndromes = vec![vec![0u8; num_detectors]; num_shots];
+    let mut packed = vec![0u8; shot_bytes * num_shots];
+    for (shot, syndrome) in syndromes.iter_mut().enumerate() {
+        for _ in 0..num_detectors / 100 {
+            let d = (xorshift(&mut state) % num_detectors as u64) as usize;
+            syndrome[d] = 1;
+            packed[shot * shot_bytes + d / 8] |= 1 << (d % 8);
+        }
+    }
+
+    let mut m = rep_code_chain(num_detectors);
+    let mut out = Vec::new();
+    m.decode_batch_into(&syndromes, &mut out);
+    let rounds = 10;
+    let started = Instant::now();
+    for _ in 0..rounds {
+        m.decode_batch_into(black_box(&syndromes), &mut out);
+        black_box(&out);
+    }
+    let bytes = started.elapsed().as_secs_f64() / (rounds * num_shots) as f64;
+    let started = Instant::now();
+    for _ in 0..rounds {
+        black_box(m.decode_packed_batch(black_box(&packed), num_shots, num_detectors).unwrap());
+    }
+    let words = started.elapsed().as_secs_f64() / (rounds * num_shots) as f64;
+    println!("per-shot decode: bytes {:.2} us, packed {:.2} us", bytes * 1e6, words * 1e6);
+}
+
+/// Per-shot cost of sparse shots on a 1k- and a 10k-detector chain. It
+/// should follow the syndrome weight, not the graph size, since
+/// `GraphFlooder::reset` only revisits the nodes a shot touched.
+fn sparse_decode_against_graph_size() {
+    let time_per_shot = |n: usize| {
+        let mut m = rep_code_chain(n);
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let shots: Vec<Vec<usize>> = (0..100_000)
+            .map(|_| {
+                // Four single-edge errors, as detection events.
+                let mut events = Vec::new();
+                for _ in 0..4 {
+                    let i = (xorshift(&mut state) % (n as u64 - 1)) as usize;
+                    for d in [i, i + 1] {
+                        match events.iter().position(|&e| e == d) {
+                            Some(k) => {
+                                events.swap_remove(k);
+                            }
+                            None => events.push(d),
+                        }
+                    }
+                }
+                events
+            })
+            .collect();
+        let mut out = Vec::new();
+        m.decode_detection_events_into(&shots[0], &mut out);
+        let started = Instant::now();
+        for events in &shots {
+            m.decode_detection_events_into(black_box(events), &mut out);
+        }
+        started.elapsed().as_secs_f64() / shots.len() as f64
+    };
+    let small = time_per_shot(1_000);
+    let large = time_per_shot(10_000);
+    println!(
+        "per-shot sparse decode: 1k nodes {:.2} us, 10k nodes {:.2} us",
+        small * 1e6,
+        large * 1e6
+    );
+}
+
+fn main() {
+    packed_batch_against_byte_batch();
+    sparse_decode_against_graph_size();
+}
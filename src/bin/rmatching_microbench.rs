@@ -20,6 +20,9 @@ mod bench {
         mean_decode_us: f64,
         median_decode_us: f64,
         p95_decode_us: f64,
+        /// Mean time per round of the same syndromes through
+        /// `decode_packed_batch`.
+        mean_packed_decode_us: f64,
     }
 
     fn summarize_latencies(samples: &[f64]) -> (f64, f64, f64) {
@@ -37,6 +40,22 @@ mod bench {
         (mean, median, p95)
     }
 
+    /// Pack `syndromes` in Stim's bit-packed layout, padding short ones
+    /// with zeros. Returns the buffer and the number of detectors per shot.
+    fn pack_syndromes(syndromes: &[Vec<u8>]) -> (Vec<u8>, usize) {
+        let num_detectors = syndromes.iter().map(Vec::len).max().unwrap_or(0);
+        let shot_bytes = num_detectors.div_ceil(8);
+        let mut packed = vec![0u8; shot_bytes * syndromes.len()];
+        for (shot, syndrome) in syndromes.iter().enumerate() {
+            for (d, &bit) in syndrome.iter().enumerate() {
+                if bit != 0 {
+                    packed[shot * shot_bytes + d / 8] |= 1 << (d % 8);
+                }
+            }
+        }
+        (packed, num_detectors)
+    }
+
     pub fn run_request(req: BenchmarkRequest) -> BenchmarkResponse {
         let build_started = Instant::now();
         let mut matching = Matching::from_dem(&req.dem).expect("DEM must build");
@@ -60,6 +79,23 @@ mod bench {
         let (mean_decode_us, median_decode_us, p95_decode_us) =
             summarize_latencies(&decode_latencies_us);
 
+        let (packed, num_detectors) = pack_syndromes(&req.syndromes);
+        let num_shots = req.syndromes.len();
+        for _ in 0..req.warmup_rounds {
+            matching
+                .decode_packed_batch(&packed, num_shots, num_detectors)
+                .expect("packed syndromes must decode");
+        }
+        let mut packed_latencies_us = Vec::with_capacity(req.measure_rounds);
+        for _ in 0..req.measure_rounds {
+            let started = Instant::now();
+            matching
+                .decode_packed_batch(&packed, num_shots, num_detectors)
+                .expect("packed syndromes must decode");
+            packed_latencies_us.push(started.elapsed().as_secs_f64() * 1e6);
+        }
+        let (mean_packed_decode_us, _, _) = summarize_latencies(&packed_latencies_us);
+
         BenchmarkResponse {
             predictions,
             build_us,
@@ -67,6 +103,7 @@ mod bench {
             mean_decode_us,
             median_decode_us,
             p95_decode_us,
+            mean_packed_decode_us,
         }
     }
 
@@ -103,6 +140,15 @@ mod bench {
             assert_eq!(resp.decode_latencies_us.len(), 3);
             assert!(resp.build_us >= 0.0);
             assert!(resp.mean_decode_us >= 0.0);
+            assert!(resp.mean_packed_decode_us >= 0.0);
+        }
+
+        #[test]
+        fn pack_syndromes_uses_stim_bit_order() {
+            let (packed, num_detectors) =
+                pack_syndromes(&[vec![1, 0, 0, 0, 0, 0, 0, 0, 1], vec![0, 1]]);
+            assert_eq!(num_detectors, 9);
+            assert_eq!(packed, vec![0x01, 0x01, 0x02, 0x00]);
         }
 
         #[test]
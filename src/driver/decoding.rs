@@ -1,33 +1,146 @@
-use crate::driver::dem_parse::parse_dem;
-use crate::driver::user_graph::UserGraph;
-use crate::matcher::mwpm::{MatchingResult, Mwpm};
+use std::cell::RefCell;
+
+use crate::driver::dem_parse::{parse_dem, parse_dem_with_merge_strategy, validate_dem, DemStats};
+use crate::driver::logical_weight::BoundaryPair;
+use crate::driver::user_graph::{BoundaryEncoding, MergeStrategy, UserGraph};
+use crate::error::MatchingError;
+use crate::interop::CompressedEdge;
+use crate::matcher::mwpm::{MatchingResult, Mwpm, MwpmStats};
 use crate::types::*;
 
+/// Order in which `decode` writes observable predictions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ObservableOrder {
+    /// Observable `i` at position `i`.
+    #[default]
+    LsbFirst,
+    /// Observable `num_observables - 1 - i` at position `i`.
+    MsbFirst,
+    /// Observable `order[i]` at position `i`; the output has `order.len()`
+    /// entries. Observables that do not exist read as 0.
+    Custom(Vec<usize>),
+}
+
+/// How [`Matching::decode_detection_events`] treats a detector listed
+/// more than once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RepeatedEvents {
+    /// The detector fires once however often it is listed.
+    #[default]
+    Dedup,
+    /// Each listing flips the detector, so it fires only when listed an
+    /// odd number of times.
+    Parity,
+}
+
+/// Per-thread solver used by [`Matching::decode_shared`], tagged with the
+/// fingerprint of the graph it was built from.
+struct SharedSolver {
+    fingerprint: u64,
+    mwpm: Mwpm,
+    detection_events: Vec<usize>,
+    effective_events: Vec<usize>,
+}
+
+thread_local! {
+    static SHARED_SOLVER: RefCell<Option<SharedSolver>> = const { RefCell::new(None) };
+}
+
+/// Solver scratch for decoding through `&Matching`, created by
+/// [`Matching::context`]. Each context owns its own `Mwpm` and buffers, so
+/// any number of them can decode against one graph at once, including
+/// from inside another decode.
+pub struct DecodeContext<'a> {
+    matching: &'a Matching,
+    mwpm: Mwpm,
+    detection_events: Vec<usize>,
+    effective_events: Vec<usize>,
+}
+
 /// Public-facing decoder wrapping a `UserGraph` and its cached `Mwpm`.
 pub struct Matching {
     user_graph: UserGraph,
     detection_events_buf: Vec<usize>,
     effective_events_buf: Vec<usize>,
+    observable_order: ObservableOrder,
+    repeated_events: RepeatedEvents,
 }
 
 impl Matching {
-    /// Build a `Matching` from a Stim DEM text string.
-    pub fn from_dem(dem_text: &str) -> Result<Self, String> {
-        let user_graph = parse_dem(dem_text)?;
-        Ok(Matching {
+    fn with_user_graph(user_graph: UserGraph) -> Self {
+        Matching {
             user_graph,
             detection_events_buf: Vec::new(),
             effective_events_buf: Vec::new(),
-        })
+            observable_order: ObservableOrder::default(),
+            repeated_events: RepeatedEvents::default(),
+        }
+    }
+
+    /// Build a `Matching` from a Stim DEM text string.
+    pub fn from_dem(dem_text: &str) -> Result<Self, MatchingError> {
+        let user_graph = parse_dem(dem_text)?;
+        Ok(Self::with_user_graph(user_graph))
+    }
+
+    /// Like [`Matching::from_dem`], but combine errors on the same
+    /// detectors and observables according to `strategy`.
+    pub fn from_dem_with_merge_strategy(
+        dem_text: &str,
+        strategy: MergeStrategy,
+    ) -> Result<Self, MatchingError> {
+        let user_graph = parse_dem_with_merge_strategy(dem_text, strategy)?;
+        Ok(Self::with_user_graph(user_graph))
+    }
+
+    /// Check that `dem_text` parses and report its size and warnings,
+    /// without building a decoder. See [`validate_dem`].
+    pub fn validate_dem(dem_text: &str) -> Result<DemStats, MatchingError> {
+        validate_dem(dem_text)
+    }
+
+    /// Build the matching graph of a `width` x `height` toric code.
+    ///
+    /// Detector `y * width + x` sits at grid position `(x, y)` and is joined
+    /// to its right and lower neighbours with wraparound, so there are no
+    /// boundary edges. Horizontal edges crossing the `x = width - 1 -> 0` seam
+    /// flip `L0` and vertical edges crossing `y = height - 1 -> 0` flip `L1`;
+    /// each observable therefore counts crossings of one non-contractible cut.
+    /// Every edge gets the same `error_probability`.
+    ///
+    /// Errors on a torus always flip an even number of detectors, so
+    /// physical syndromes pair up internally. An odd number of detection
+    /// events has no perfect matching: [`Matching::try_decode`] reports it
+    /// as [`MatchingError::InvalidSyndrome`] before matching starts, and
+    /// the infallible decode methods panic with that error.
+    pub fn from_grid_periodic(
+        width: usize,
+        height: usize,
+        error_probability: f64,
+    ) -> Result<Self, MatchingError> {
+        if width < 3 || height < 3 {
+            return Err(MatchingError::InvalidGraph(format!(
+                "periodic grid must be at least 3x3, got {width}x{height}"
+            )));
+        }
+        let mut user_graph = UserGraph::new();
+        for y in 0..height {
+            for x in 0..width {
+                let node = y * width + x;
+                let right = y * width + (x + 1) % width;
+                let down = ((y + 1) % height) * width + x;
+                let right_obs = if x + 1 == width { vec![0] } else { vec![] };
+                let down_obs = if y + 1 == height { vec![1] } else { vec![] };
+                user_graph.handle_dem_instruction(error_probability, &[node, right], right_obs);
+                user_graph.handle_dem_instruction(error_probability, &[node, down], down_obs);
+            }
+        }
+        Ok(Self::with_user_graph(user_graph))
     }
 
     /// Create an empty `Matching` (edges added manually).
     pub fn new() -> Self {
-        Matching {
-            user_graph: UserGraph::new(),
-            detection_events_buf: Vec::new(),
-            effective_events_buf: Vec::new(),
-        }
+        Self::with_user_graph(UserGraph::new())
     }
 
     pub fn add_edge(
@@ -53,6 +166,233 @@ impl Matching {
             .add_boundary_edge(node, observables.to_vec(), weight, error_probability);
     }
 
+    /// Add an edge whose `secondary_weight` breaks ties between matchings of
+    /// equal primary weight (lexicographic MWPM). See
+    /// [`SECONDARY_WEIGHT_BITS`](crate::driver::user_graph::SECONDARY_WEIGHT_BITS)
+    /// for the bit budget.
+    pub fn add_edge_with_secondary_weight(
+        &mut self,
+        n1: usize,
+        n2: usize,
+        weight: f64,
+        secondary_weight: f64,
+        observables: &[usize],
+        error_probability: f64,
+    ) {
+        self.user_graph.add_edge_with_secondary_weight(
+            n1,
+            n2,
+            observables.to_vec(),
+            weight,
+            secondary_weight,
+            error_probability,
+        );
+    }
+
+    /// Boundary-edge counterpart of [`Matching::add_edge_with_secondary_weight`].
+    pub fn add_boundary_edge_with_secondary_weight(
+        &mut self,
+        node: usize,
+        weight: f64,
+        secondary_weight: f64,
+        observables: &[usize],
+        error_probability: f64,
+    ) {
+        self.user_graph.add_boundary_edge_with_secondary_weight(
+            node,
+            observables.to_vec(),
+            weight,
+            secondary_weight,
+            error_probability,
+        );
+    }
+
+    /// Sort edges into a canonical order, making fault ids (edge indices)
+    /// independent of construction order. Returns the permutation: entry `i`
+    /// is the old fault id of the edge that now has fault id `i`.
+    pub fn canonicalize_edges(&mut self) -> Vec<usize> {
+        self.user_graph.canonicalize_edges()
+    }
+
+    /// Indices of edges whose nonzero weight rounds to zero when discretized.
+    /// Such edges get the minimum weight, indistinguishable from each other,
+    /// which usually means the ratio between the largest and smallest
+    /// weights is too large.
+    pub fn weight_resolution_warnings(&self) -> Vec<usize> {
+        self.user_graph
+            .weight_resolution_warnings(self.user_graph.num_distinct_weights())
+    }
+
+    /// Edges between two boundary nodes that flip an observable. See
+    /// [`UserGraph::boundary_to_boundary_observable_edges`].
+    pub fn boundary_to_boundary_observable_edges(&self) -> Vec<usize> {
+        self.user_graph.boundary_to_boundary_observable_edges()
+    }
+
+    /// Mark the boundary in one way only. See
+    /// [`UserGraph::normalize_boundary_encoding`].
+    pub fn normalize_boundary_encoding(
+        &mut self,
+        prefer: BoundaryEncoding,
+    ) -> Result<(), MatchingError> {
+        self.user_graph.normalize_boundary_encoding(prefer)
+    }
+
+    /// Scale the weights of edges that span `axis` of the detector
+    /// coordinates. See [`UserGraph::scale_weights_by_coordinate`].
+    pub fn scale_weights_by_coordinate(
+        &mut self,
+        axis: usize,
+        factor: f64,
+    ) -> Result<usize, MatchingError> {
+        self.user_graph.scale_weights_by_coordinate(axis, factor)
+    }
+
+    /// Check a set of fault ids against the syndrome it should explain. See
+    /// [`UserGraph::verify_correction`].
+    pub fn verify_correction(&self, syndrome: &[u8], correction_faults: &[usize]) -> bool {
+        self.user_graph.verify_correction(syndrome, correction_faults)
+    }
+
+    /// Keep the cached solver through a series of changes. See
+    /// [`UserGraph::begin_batch`].
+    ///
+    /// Inside a batch, decodes through `&mut self`, fault ids included, see
+    /// the graph from before the batch, except that
+    /// [`Matching::decode_refined`] skips its refinement while changes are
+    /// pending. [`Matching::decode_shared`] and [`Matching::context`] build
+    /// their own solver, from the changed graph.
+    pub fn begin_batch(&mut self) {
+        self.user_graph.begin_batch();
+    }
+
+    /// End a batch of changes. See [`UserGraph::end_batch`].
+    pub fn end_batch(&mut self) {
+        self.user_graph.end_batch();
+    }
+
+    /// Rebuild the solver now instead of on the next decode. See
+    /// [`UserGraph::commit`].
+    pub fn commit(&mut self) -> bool {
+        self.user_graph.commit()
+    }
+
+    /// Build the solver now, reporting why it cannot be built instead of
+    /// panicking in the next decode. See [`UserGraph::try_commit`].
+    pub fn try_commit(&mut self) -> Result<bool, MatchingError> {
+        self.user_graph.try_commit()
+    }
+
+    /// Detectors that can match to the boundary. See
+    /// [`UserGraph::boundary_detectors`].
+    pub fn boundary_detectors(&self) -> Vec<usize> {
+        self.user_graph.boundary_detectors()
+    }
+
+    /// Observables the graph can flip. See
+    /// [`UserGraph::flipped_observables`].
+    pub fn flipped_observables(&self) -> Vec<usize> {
+        self.user_graph.flipped_observables()
+    }
+
+    /// Number of observables, one past the largest observable index.
+    pub fn num_observables(&self) -> usize {
+        self.user_graph.num_observables
+    }
+
+    /// Define observable `obs` as the parity of `detectors`, replacing any
+    /// edge-labelled `obs`. After decoding, its prediction is the parity of
+    /// the correction's effect on those detectors.
+    pub fn set_detector_observable(&mut self, obs: usize, detectors: &[usize]) {
+        self.user_graph.set_detector_observable(obs, detectors);
+    }
+
+    /// Choose the order of observables in `decode` / `decode_batch` output.
+    pub fn set_observable_order(&mut self, order: ObservableOrder) {
+        self.observable_order = order;
+    }
+
+    /// Choose how repeated indices in a sparse event list are treated.
+    pub fn set_repeated_events(&mut self, repeated: RepeatedEvents) {
+        self.repeated_events = repeated;
+    }
+
+    /// Break exact ties between equal-weight matchings pseudo-randomly.
+    ///
+    /// The same seed always yields the same matching; different seeds sample
+    /// different matchings among the tied ones. `None` restores the default
+    /// deterministic order.
+    pub fn set_tie_break_seed(&mut self, seed: Option<u64>) {
+        self.user_graph.set_tie_break_seed(seed);
+    }
+
+    /// Stop the decoder from using the edges between `n1` and `n2`, or the
+    /// boundary when `n2` is `None`. See [`UserGraph::forbid_edge`].
+    pub fn forbid_edge(&mut self, n1: usize, n2: Option<usize>) {
+        self.user_graph.forbid_edge(n1, n2);
+    }
+
+    /// Undo every [`Matching::forbid_edge`].
+    pub fn clear_forbidden_edges(&mut self) {
+        self.user_graph.clear_forbidden_edges();
+    }
+
+    /// Scale every boundary-edge weight by `multiplier` when decoding.
+    /// See [`UserGraph::set_boundary_weight_multiplier`].
+    pub fn set_boundary_weight_multiplier(&mut self, multiplier: f64) {
+        self.user_graph.set_boundary_weight_multiplier(multiplier);
+    }
+
+    /// Use `n` weight levels when discretizing edge weights.
+    /// See [`UserGraph::set_num_distinct_weights`].
+    pub fn set_num_distinct_weights(&mut self, n: Weight) {
+        self.user_graph.set_num_distinct_weights(n);
+    }
+
+    /// Approximate bytes used by the decoder: the graph, the cached solvers
+    /// and the scratch buffers. See [`UserGraph::memory_footprint`].
+    pub fn memory_footprint(&self) -> usize {
+        self.user_graph.memory_footprint()
+            + (self.detection_events_buf.capacity() + self.effective_events_buf.capacity())
+                * size_of::<usize>()
+    }
+
+    /// Refuse to build a solver while some edge has an error probability
+    /// outside `[0, 1]`: [`Matching::try_commit`] and
+    /// [`Matching::try_decode`] report it as
+    /// [`MatchingError::InvalidProbability`], and the infallible decode
+    /// methods panic. See [`UserGraph::set_require_valid_probabilities`].
+    pub fn set_require_valid_probabilities(&mut self, require: bool) {
+        self.user_graph.set_require_valid_probabilities(require);
+    }
+
+    /// Reject a syndrome in which a detector with no edges fires, instead of
+    /// skipping that detector: [`Matching::try_decode`] returns the error
+    /// and the infallible decode methods panic with it.
+    /// See [`UserGraph::set_strict_isolated_detectors`].
+    pub fn set_strict_isolated_detectors(&mut self, strict: bool) {
+        self.user_graph.set_strict_isolated_detectors(strict);
+    }
+
+    /// Keep one continuous queue timeline across decodes instead of
+    /// restarting at zero. See [`UserGraph::set_reset_time_per_decode`].
+    pub fn set_reset_time_per_decode(&mut self, reset: bool) {
+        self.user_graph.set_reset_time_per_decode(reset);
+    }
+
+    /// Weight of the lightest undetectable logical error per observable.
+    /// See [`UserGraph::min_logical_weight`] for how `boundary_pairs` is used.
+    pub fn min_logical_weight(&self, boundary_pairs: &[BoundaryPair]) -> Vec<f64> {
+        self.user_graph.min_logical_weight(boundary_pairs)
+    }
+
+    /// Weight of the lightest correction for `syndrome` in each of the
+    /// `2^num_observables` logical classes. See
+    /// [`UserGraph::logical_class_weights`].
+    pub fn logical_class_weights(&self, syndrome: &[u8]) -> Result<Vec<f64>, MatchingError> {
+        self.user_graph.logical_class_weights(syndrome)
+    }
+
     pub fn set_boundary(&mut self, boundary: &[usize]) {
         self.user_graph
             .set_boundary(boundary.iter().copied().collect());
@@ -62,23 +402,178 @@ impl Matching {
     ///
     /// `syndrome` has one byte per detector; non-zero means that detector fired.
     /// Returns one byte per observable (0 or 1).
+    ///
+    /// Panics where [`Matching::try_decode`] returns an error. This and the
+    /// other infallible decode methods can only fail on the syndrome once
+    /// [`Matching::try_commit`] has accepted the graph.
     pub fn decode(&mut self, syndrome: &[u8]) -> Vec<u8> {
         let mut out = Vec::new();
         self.decode_into(syndrome, &mut out);
         out
     }
 
+    /// Decode a syndrome, reporting instead of panicking when the graph or
+    /// the syndrome cannot be decoded:
+    ///
+    /// - the solver cannot be built, as for [`UserGraph::try_to_mwpm`];
+    /// - a detector with no edges fired under
+    ///   [`Matching::set_strict_isolated_detectors`];
+    /// - a component with no boundary, such as the whole of
+    ///   [`Matching::from_grid_periodic`], holds an odd number of detection
+    ///   events, which have no perfect matching.
+    ///
+    /// The syndrome is checked before any matching starts, so the decoder
+    /// is left ready for the next shot.
+    pub fn try_decode(&mut self, syndrome: &[u8]) -> Result<Vec<u8>, MatchingError> {
+        let mut out = Vec::new();
+        syndrome_to_detection_events_into(syndrome, &mut self.detection_events_buf);
+        self.try_decode_buffered_events_into(&mut out)?;
+        Ok(out)
+    }
+
     /// Decode a syndrome into a caller-provided output buffer.
     pub fn decode_into(&mut self, syndrome: &[u8], out: &mut Vec<u8>) {
+        syndrome_to_detection_events_into(syndrome, &mut self.detection_events_buf);
+        self.decode_buffered_events_into(out);
+    }
+
+    /// Decode a syndrome and also return the weight of the correction in the
+    /// decoder's integer units, including any negative-weight edges. Dividing
+    /// it by the matching graph's `normalising_constant` gives the weight in
+    /// the units edges were added with, up to discretization error; the
+    /// integer form compares exactly.
+    pub fn decode_with_int_weight(&mut self, syndrome: &[u8]) -> (Vec<u8>, TotalWeight) {
+        syndrome_to_detection_events_into(syndrome, &mut self.detection_events_buf);
+        let mwpm = self.user_graph.get_mwpm();
+        let neg_obs_mask =
+            compute_neg_obs_mask(&mwpm.flooder.graph.negative_weight_observables_set);
+        apply_negative_weight_events_into(
+            &self.detection_events_buf,
+            &mwpm.flooder.graph.negative_weight_detection_events_set,
+            &mwpm.flooder.graph.is_user_graph_boundary_node,
+            &mut self.effective_events_buf,
+        );
+        let res = decode_events_to_result(mwpm, &self.effective_events_buf, neg_obs_mask)
+            .unwrap_or_else(|e| panic!("{e}"));
+        let mut out = Vec::new();
+        obs_mask_to_predictions_into(
+            res.obs_mask,
+            mwpm.flooder.graph.num_observables,
+            &self.observable_order,
+            &mut out,
+        );
+        (out, res.weight)
+    }
+
+    /// Decode a syndrome and also return the weight of the correction in
+    /// the units edges were added with, including any negative-weight
+    /// edges. It is [`Matching::decode_with_int_weight`] divided by the
+    /// discretization's normalising constant, so it carries the same
+    /// rounding error.
+    pub fn decode_with_weight(&mut self, syndrome: &[u8]) -> (Vec<u8>, f64) {
+        let (prediction, weight) = self.decode_with_int_weight(syndrome);
+        let normalising_constant = self.user_graph.get_mwpm().flooder.graph.normalising_constant;
+        (prediction, weight as f64 / normalising_constant)
+    }
+
+    /// Decode from the indices of the detectors that fired, skipping the
+    /// scan over a dense syndrome. Order does not matter, a repeated index
+    /// fires once or cancels out as chosen by
+    /// [`Matching::set_repeated_events`], and indices past the last detector
+    /// are ignored, as in `decode`.
+    pub fn decode_detection_events(&mut self, detection_events: &[usize]) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.decode_detection_events_into(detection_events, &mut out);
+        out
+    }
+
+    /// Sparse counterpart of [`Matching::decode_into`].
+    pub fn decode_detection_events_into(&mut self, detection_events: &[usize], out: &mut Vec<u8>) {
+        let buf = &mut self.detection_events_buf;
+        buf.clear();
+        buf.extend_from_slice(detection_events);
+        buf.sort_unstable();
+        match self.repeated_events {
+            RepeatedEvents::Dedup => buf.dedup(),
+            RepeatedEvents::Parity => cancel_sorted_pairs(buf),
+        }
+        self.decode_buffered_events_into(out);
+    }
+
+    /// Decode from the indices of the detectors that fired, creating their
+    /// detection events in exactly the given order instead of index order.
+    /// A repeated index fires once or cancels out as chosen by
+    /// [`Matching::set_repeated_events`]; a detector that fires takes the
+    /// position of its first listing. Indices past the last detector are
+    /// ignored. Events added by negative-weight edges follow the given ones,
+    /// in index order.
+    ///
+    /// The minimum-weight matching does not depend on the order, so when it
+    /// is unique every order gives the same prediction; between tied
+    /// matchings the order may pick a different one. This is meant for
+    /// testing that property.
+    pub fn decode_ordered(&mut self, events: &[usize]) -> Vec<u8> {
+        let mwpm = self.user_graph.get_mwpm();
+        let graph = &mwpm.flooder.graph;
+        let neg_det_set = &graph.negative_weight_detection_events_set;
+        let is_boundary = &graph.is_user_graph_boundary_node;
+        let keep = |d: usize| d >= is_boundary.len() || !is_boundary[d];
+
+        let mut listings = std::collections::HashMap::new();
+        for &d in events {
+            *listings.entry(d).or_insert(0usize) += 1;
+        }
+        let repeated_events = self.repeated_events;
+        let fires = |d: usize| match (repeated_events, listings.get(&d)) {
+            (_, None) => false,
+            (RepeatedEvents::Dedup, Some(_)) => true,
+            (RepeatedEvents::Parity, Some(&n)) => n % 2 == 1,
+        };
+        let mut seen = std::collections::HashSet::new();
+        let effective = &mut self.effective_events_buf;
+        effective.clear();
+        effective.extend(events.iter().copied().filter(|&d| {
+            seen.insert(d) && fires(d) && !neg_det_set.contains(&d) && keep(d)
+        }));
+        let mut flipped: Vec<usize> = neg_det_set
+            .iter()
+            .copied()
+            .filter(|&d| !fires(d) && keep(d))
+            .collect();
+        flipped.sort_unstable();
+        effective.extend(flipped);
+
+        let num_observables = graph.num_observables;
+        let neg_obs_mask = compute_neg_obs_mask(&graph.negative_weight_observables_set);
+        let mut out = Vec::new();
+        decode_events_to_prediction_into(
+            mwpm,
+            effective,
+            num_observables,
+            neg_obs_mask,
+            &self.observable_order,
+            &mut out,
+        )
+        .unwrap_or_else(|e| panic!("{e}"));
+        out
+    }
+
+    /// Decode the detection events currently held in `detection_events_buf`.
+    fn decode_buffered_events_into(&mut self, out: &mut Vec<u8>) {
+        self.try_decode_buffered_events_into(out)
+            .unwrap_or_else(|e| panic!("{e}"));
+    }
+
+    fn try_decode_buffered_events_into(&mut self, out: &mut Vec<u8>) -> Result<(), MatchingError> {
         let user_graph = &mut self.user_graph;
         let detection_events_buf = &mut self.detection_events_buf;
         let effective_events_buf = &mut self.effective_events_buf;
-        let mwpm = user_graph.get_mwpm();
+        let observable_order = &self.observable_order;
+        let mwpm = user_graph.try_get_mwpm()?;
         let num_observables = mwpm.flooder.graph.num_observables;
         let neg_obs_mask =
             compute_neg_obs_mask(&mwpm.flooder.graph.negative_weight_observables_set);
 
-        syndrome_to_detection_events_into(syndrome, detection_events_buf);
         apply_negative_weight_events_into(
             detection_events_buf,
             &mwpm.flooder.graph.negative_weight_detection_events_set,
@@ -91,8 +586,96 @@ impl Matching {
             effective_events_buf,
             num_observables,
             neg_obs_mask,
+            observable_order,
             out,
-        );
+        )
+    }
+
+    /// Decode through `&self`, so one `Matching` can be shared across threads.
+    ///
+    /// Each thread builds its own solver on first use and keeps it in a
+    /// thread-local cache keyed on [`UserGraph::fingerprint`]; the solver is
+    /// rebuilt when the graph has changed since, or when the thread last
+    /// decoded a different graph. Results match `decode`, and it panics
+    /// where `decode` does.
+    pub fn decode_shared(&self, syndrome: &[u8]) -> Vec<u8> {
+        let fingerprint = self.user_graph.fingerprint();
+        SHARED_SOLVER.with_borrow_mut(|cache| {
+            if cache.as_ref().is_none_or(|c| c.fingerprint != fingerprint) {
+                *cache = Some(SharedSolver {
+                    fingerprint,
+                    mwpm: self.user_graph.to_mwpm(),
+                    detection_events: Vec::new(),
+                    effective_events: Vec::new(),
+                });
+            }
+            let solver = cache.as_mut().unwrap();
+            let mut out = Vec::new();
+            decode_syndrome_into(
+                &mut solver.mwpm,
+                syndrome,
+                &mut solver.detection_events,
+                &mut solver.effective_events,
+                &self.observable_order,
+                &mut out,
+            )
+            .unwrap_or_else(|e| panic!("{e}"));
+            out
+        })
+    }
+
+    /// Create a [`DecodeContext`] with its own solver for this graph, to
+    /// decode through `&self`. Unlike [`Matching::decode_shared`] the solver
+    /// is not tied to a thread, so several contexts can be used side by
+    /// side, or one can be kept for the graph's lifetime. Building it costs
+    /// as much as the first `decode`.
+    ///
+    /// Panics if the solver cannot be built; see [`Matching::try_context`].
+    pub fn context(&self) -> DecodeContext<'_> {
+        self.try_context().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like [`Matching::context`], but report why the solver cannot be
+    /// built, as [`UserGraph::try_to_mwpm`] does.
+    pub fn try_context(&self) -> Result<DecodeContext<'_>, MatchingError> {
+        Ok(DecodeContext {
+            matching: self,
+            mwpm: self.user_graph.try_to_mwpm()?,
+            detection_events: Vec::new(),
+            effective_events: Vec::new(),
+        })
+    }
+
+    /// Decode a syndrome and also return the number of flooder events the
+    /// matcher processed, a cheap proxy for how long the shot took, e.g. to
+    /// route hard shots elsewhere. An empty syndrome takes zero events.
+    pub fn decode_with_event_count(&mut self, syndrome: &[u8]) -> (Vec<u8>, usize) {
+        let prediction = self.decode(syndrome);
+        (prediction, self.user_graph.get_mwpm().stats.num_events)
+    }
+
+    /// Decode a syndrome and also return the number of edges in the
+    /// correction, i.e. the length of [`Matching::decode_to_fault_ids`],
+    /// negative-weight edges included unless a path cancels them. With unit
+    /// weights this is the size of the inferred error. It costs as much as
+    /// `decode_to_fault_ids`: every match spanning more than one edge takes
+    /// a shortest-path search.
+    pub fn decode_with_match_count(&mut self, syndrome: &[u8]) -> (Vec<u8>, usize) {
+        let matches = self.decode_to_weighted_matches(syndrome);
+        let num_edges = self.correction_fault_ids(&matches).len();
+        let graph = &self.user_graph.get_mwpm().flooder.graph;
+        let obs_mask = matches
+            .iter()
+            .fold(compute_neg_obs_mask(&graph.negative_weight_observables_set), |acc, m| acc ^ m.2);
+        let mut out = Vec::new();
+        obs_mask_to_predictions_into(obs_mask, graph.num_observables, &self.observable_order, &mut out);
+        (out, num_edges)
+    }
+
+    /// Blossom and alternating-tree statistics of the most recent decode
+    /// through `&mut self` (not [`Matching::decode_shared`]).
+    pub fn last_decode_stats(&mut self) -> MwpmStats {
+        self.user_graph.get_mwpm().stats
     }
 
     /// Decode multiple syndromes. Each result matches `decode` on the same input.
@@ -102,11 +685,106 @@ impl Matching {
         out
     }
 
+    /// Decode multiple syndromes with one solver, returning each prediction
+    /// with its correction weight as in [`Matching::decode_with_weight`],
+    /// e.g. to discard high-weight shots.
+    pub fn decode_batch_with_weights(&mut self, syndromes: &[Vec<u8>]) -> Vec<(Vec<u8>, f64)> {
+        let detection_events_buf = &mut self.detection_events_buf;
+        let effective_events_buf = &mut self.effective_events_buf;
+        let observable_order = &self.observable_order;
+        let mwpm = self.user_graph.get_mwpm();
+        let num_observables = mwpm.flooder.graph.num_observables;
+        let normalising_constant = mwpm.flooder.graph.normalising_constant;
+        let neg_obs_mask =
+            compute_neg_obs_mask(&mwpm.flooder.graph.negative_weight_observables_set);
+
+        syndromes
+            .iter()
+            .map(|syndrome| {
+                syndrome_to_detection_events_into(syndrome, detection_events_buf);
+                apply_negative_weight_events_into(
+                    detection_events_buf,
+                    &mwpm.flooder.graph.negative_weight_detection_events_set,
+                    &mwpm.flooder.graph.is_user_graph_boundary_node,
+                    effective_events_buf,
+                );
+                let res = decode_events_to_result(mwpm, effective_events_buf, neg_obs_mask)
+                    .unwrap_or_else(|e| panic!("{e}"));
+                let mut prediction = Vec::new();
+                obs_mask_to_predictions_into(
+                    res.obs_mask,
+                    num_observables,
+                    observable_order,
+                    &mut prediction,
+                );
+                (prediction, res.weight as f64 / normalising_constant)
+            })
+            .collect()
+    }
+
+    /// Sample one shot of the error model. See [`UserGraph::add_noise`].
+    pub fn add_noise(
+        &self,
+        uniform: &mut impl FnMut() -> f64,
+    ) -> Result<(Vec<u8>, Vec<u8>), MatchingError> {
+        self.user_graph.add_noise(uniform)
+    }
+
+    /// Estimate the logical error rate by Monte Carlo: sample `shots`
+    /// shots with [`Matching::add_noise`], decode each and count those
+    /// whose prediction differs from the observables actually flipped.
+    /// Returns `(rate, lower, upper)`: the fraction of shots that failed,
+    /// and the 95% Wilson score interval for the per-shot failure
+    /// probability. The interval is not centred on `rate`, which keeps it
+    /// meaningful with few or no failures: with none, `lower` is 0 but
+    /// `upper` is not. With no shots the rate is 1/2 and the interval
+    /// `[0, 1]`.
+    ///
+    /// Fails if some edge has no error probability in `[0, 1]`.
+    pub fn estimate_logical_error_rate_ci(
+        &mut self,
+        shots: usize,
+        uniform: &mut impl FnMut() -> f64,
+    ) -> Result<(f64, f64, f64), MatchingError> {
+        const Z: f64 = 1.959_963_984_540_054;
+        if shots == 0 {
+            self.user_graph.check_error_probabilities()?;
+            return Ok((0.5, 0.0, 1.0));
+        }
+        let mut prediction = Vec::new();
+        let mut expected = Vec::new();
+        let mut failures = 0usize;
+        for _ in 0..shots {
+            let (syndrome, observables) = self.add_noise(uniform)?;
+            self.decode_into(&syndrome, &mut prediction);
+            expected.clear();
+            match &self.observable_order {
+                ObservableOrder::LsbFirst => expected.extend(&observables),
+                ObservableOrder::MsbFirst => expected.extend(observables.iter().rev()),
+                ObservableOrder::Custom(order) => expected
+                    .extend(order.iter().map(|&obs| observables.get(obs).copied().unwrap_or(0))),
+            }
+            if prediction != expected {
+                failures += 1;
+            }
+        }
+        let n = shots as f64;
+        let p = failures as f64 / n;
+        let z2 = Z * Z;
+        let denominator = 1.0 + z2 / n;
+        let centre = (p + z2 / (2.0 * n)) / denominator;
+        let half_width = Z * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / denominator;
+        let lower = (centre - half_width).max(0.0);
+        let upper = (centre + half_width).min(1.0);
+        Ok((p, lower, upper))
+    }
+
     /// Decode multiple syndromes into caller-provided output buffers.
     pub fn decode_batch_into(&mut self, syndromes: &[Vec<u8>], out: &mut Vec<Vec<u8>>) {
         let user_graph = &mut self.user_graph;
         let detection_events_buf = &mut self.detection_events_buf;
         let effective_events_buf = &mut self.effective_events_buf;
+        let observable_order = &self.observable_order;
         let mwpm = user_graph.get_mwpm();
         let num_observables = mwpm.flooder.graph.num_observables;
         let neg_obs_mask =
@@ -129,16 +807,140 @@ impl Matching {
                 effective_events_buf,
                 num_observables,
                 neg_obs_mask,
+                observable_order,
                 prediction_out,
-            );
+            )
+            .unwrap_or_else(|e| panic!("{e}"));
         }
 
         out.truncate(syndromes.len());
     }
 
+    /// Decode `num_shots` syndromes stored back to back in Stim's bit-packed
+    /// layout: each shot takes `ceil(num_detectors / 8)` bytes, detector `d`
+    /// at bit `d % 8` of byte `d / 8`. Returns one observable mask per shot,
+    /// observable `i` at bit `i`; [`ObservableOrder`] does not apply.
+    ///
+    /// Fails if `data` has the wrong length or the graph has more than 64
+    /// observables.
+    pub fn decode_packed_batch(
+        &mut self,
+        data: &[u8],
+        num_shots: usize,
+        num_detectors: usize,
+    ) -> Result<Vec<u64>, MatchingError> {
+        let shot_bytes = num_detectors.div_ceil(8);
+        if data.len() != shot_bytes * num_shots {
+            return Err(MatchingError::InvalidSyndrome(format!(
+                "expected {num_shots} shots of {shot_bytes} bytes ({} bytes), got {} bytes",
+                shot_bytes * num_shots,
+                data.len()
+            )));
+        }
+        let user_graph = &mut self.user_graph;
+        let detection_events_buf = &mut self.detection_events_buf;
+        let effective_events_buf = &mut self.effective_events_buf;
+        let mwpm = user_graph.get_mwpm();
+        if mwpm.flooder.graph.num_observables > 64 {
+            return Err(MatchingError::ObservableOverflow {
+                operation: "packed decoding",
+                max: 64,
+                num_observables: mwpm.flooder.graph.num_observables,
+            });
+        }
+        let neg_obs_mask =
+            compute_neg_obs_mask(&mwpm.flooder.graph.negative_weight_observables_set);
+
+        let mut out = Vec::with_capacity(num_shots);
+        for shot in data.chunks_exact(shot_bytes.max(1)).take(num_shots) {
+            packed_to_detection_events_into(shot, num_detectors, detection_events_buf);
+            apply_negative_weight_events_into(
+                detection_events_buf,
+                &mwpm.flooder.graph.negative_weight_detection_events_set,
+                &mwpm.flooder.graph.is_user_graph_boundary_node,
+                effective_events_buf,
+            );
+            out.push(decode_events_to_result(mwpm, effective_events_buf, neg_obs_mask)?.obs_mask);
+        }
+        // With no detectors every shot is empty and `data` has no chunks.
+        out.resize(num_shots, neg_obs_mask);
+        Ok(out)
+    }
+
     /// Decode a syndrome and return matched pairs as `(node1, node2)`.
     /// Boundary matches use `-1` for the boundary node.
     pub fn decode_to_edges(&mut self, syndrome: &[u8]) -> Vec<(i64, i64)> {
+        self.decode_to_edges_with_observables(syndrome)
+            .into_iter()
+            .map(|(a, b, _)| (a, b))
+            .collect()
+    }
+
+    /// Decode a syndrome into a dense view of the matching: entry `i` is
+    /// `None` if detector `i` has no detection event, `Some(-1)` if it is
+    /// matched to the boundary and `Some(j)` if it is matched to detector
+    /// `j`. Matches inside blossoms are resolved to the detectors at their
+    /// ends, as in [`Matching::decode_to_edges`]. There is one entry per
+    /// node of the graph; with negative-weight edges the events are those
+    /// left after applying them.
+    pub fn decode_to_matching_graph(&mut self, syndrome: &[u8]) -> Vec<Option<i64>> {
+        let mut partners = vec![None; self.user_graph.get_num_nodes()];
+        for (a, b) in self.decode_to_edges(syndrome) {
+            partners[a as usize] = Some(b);
+            if b >= 0 {
+                partners[b as usize] = Some(a);
+            }
+        }
+        partners
+    }
+
+    /// Decode a syndrome, then run a refinement pass that checks the
+    /// matching against the original float weights and improves it where
+    /// rounding to integer weights misranked two matchings. The pass
+    /// re-pairs two matches, or splits one into two boundary matches,
+    /// while that lowers the float total, and the prediction follows the
+    /// float-shortest path of each final match. It is a local search, not a
+    /// second exact solve, and costs one Dijkstra search per matched
+    /// detector on top of the decode. Inside a batch with pending changes
+    /// the float weights no longer match the cached solver, so this returns
+    /// the unrefined prediction of `decode`.
+    pub fn decode_refined(&mut self, syndrome: &[u8]) -> Vec<u8> {
+        if self.user_graph.has_pending_changes() {
+            return self.decode(syndrome);
+        }
+        let mut matches: Vec<(usize, Option<usize>)> = self
+            .decode_to_edges(syndrome)
+            .into_iter()
+            .map(|(a, b)| (a as usize, (b >= 0).then_some(b as usize)))
+            .collect();
+        let obs_mask = self.user_graph.refine_matching(&mut matches);
+        let graph = &self.user_graph.get_mwpm().flooder.graph;
+        let neg_obs_mask = compute_neg_obs_mask(&graph.negative_weight_observables_set);
+        let mut out = Vec::new();
+        obs_mask_to_predictions_into(
+            obs_mask ^ neg_obs_mask,
+            graph.num_observables,
+            &self.observable_order,
+            &mut out,
+        );
+        out
+    }
+
+    /// Like [`Matching::decode_to_edges`], but each match also carries the
+    /// observables it flips, so a boundary match says which boundary edge
+    /// was used without a separate `decode`. As in
+    /// [`Matching::decode_streaming`], the masks exclude the correction for
+    /// negative-weight edges.
+    pub fn decode_to_edges_with_observables(&mut self, syndrome: &[u8]) -> Vec<(i64, i64, ObsMask)> {
+        self.decode_to_weighted_matches(syndrome)
+            .into_iter()
+            .map(|(a, b, obs_mask, _)| (a, b, obs_mask))
+            .collect()
+    }
+
+    /// Matches of [`Matching::decode_to_edges_with_observables`], each with
+    /// the length of its path in discretized units.
+    fn decode_to_weighted_matches(&mut self, syndrome: &[u8]) -> Vec<(i64, i64, ObsMask, TotalWeight)> {
         let mwpm = self.user_graph.get_mwpm();
 
         let detection_events = syndrome_to_detection_events(syndrome);
@@ -149,7 +951,8 @@ impl Matching {
             &mwpm.flooder.graph.is_user_graph_boundary_node,
         );
 
-        process_timeline_until_completion(mwpm, &effective_events);
+        process_timeline_until_completion(mwpm, &effective_events)
+            .unwrap_or_else(|e| panic!("{e}"));
 
         let edges = extract_match_edges(mwpm, &effective_events);
 
@@ -157,6 +960,197 @@ impl Matching {
 
         edges
     }
+
+    /// Like [`Matching::decode_to_edges`], but in a canonical order: pairs
+    /// sorted by `(min, max)` endpoint, then boundary matches sorted by
+    /// node. Useful for comparing output across runs and decoder versions.
+    pub fn decode_to_edges_sorted(&mut self, syndrome: &[u8]) -> Vec<(i64, i64)> {
+        let mut edges = self.decode_to_edges(syndrome);
+        edges.sort_unstable_by_key(|&(a, b)| (b < 0, a, b));
+        edges
+    }
+
+    /// Decode a syndrome and return the fault ids (indices into the edges in
+    /// insertion order, or canonical order after `canonicalize_edges`) of
+    /// the predicted correction, sorted. Each match is expanded along a
+    /// shortest path, and edges with negative weight are included unless a
+    /// path cancels them.
+    pub fn decode_to_fault_ids(&mut self, syndrome: &[u8]) -> Vec<usize> {
+        let matches = self.decode_to_weighted_matches(syndrome);
+        self.correction_fault_ids(&matches)
+    }
+
+    /// Sorted fault ids of the correction made of `matches`, as returned by
+    /// [`Matching::decode_to_weighted_matches`], and the negative-weight
+    /// edges.
+    fn correction_fault_ids(&mut self, matches: &[(i64, i64, ObsMask, TotalWeight)]) -> Vec<usize> {
+        // Paths are looked up by matching-graph node index.
+        debug_assert_eq!(
+            self.user_graph.get_search_flooder().graph.nodes.len(),
+            self.user_graph.get_mwpm().flooder.graph.nodes.len(),
+            "search graph and matching graph disagree on the number of nodes"
+        );
+        let mut fault_ids = self.user_graph.negative_fault_ids().to_vec();
+        for &(a, b, _, distance) in matches {
+            let dst = if b < 0 { None } else { Some(b as usize) };
+            self.user_graph
+                .match_path_fault_ids(a as usize, dst, distance, &mut fault_ids);
+        }
+        fault_ids.sort_unstable();
+        // A fault used an even number of times cancels out.
+        let mut correction = Vec::with_capacity(fault_ids.len());
+        for id in fault_ids {
+            if correction.last() == Some(&id) {
+                correction.pop();
+            } else {
+                correction.push(id);
+            }
+        }
+        correction
+    }
+
+    /// Like [`Matching::decode_to_fault_ids`], but report each fault id
+    /// together with every edge parallel to it, since parallel edges are
+    /// indistinguishable to the decoder. Each group starts with the fault id
+    /// `decode_to_fault_ids` reports, followed by the others in index order.
+    pub fn decode_to_fault_id_groups(&mut self, syndrome: &[u8]) -> Vec<Vec<usize>> {
+        self.decode_to_fault_ids(syndrome)
+            .into_iter()
+            .map(|id| self.user_graph.fault_id_group(id))
+            .collect()
+    }
+
+    /// Decode a syndrome and return each match of
+    /// [`Matching::decode_to_edges`] together with the fault ids along a
+    /// shortest path between its two detectors (or to the boundary), in
+    /// path order.
+    pub fn decode_to_matched_with_paths(&mut self, syndrome: &[u8]) -> Vec<(i64, i64, Vec<usize>)> {
+        self.decode_to_weighted_matches(syndrome)
+            .into_iter()
+            .map(|(a, b, _, distance)| {
+                let mut path = Vec::new();
+                let dst = if b < 0 { None } else { Some(b as usize) };
+                self.user_graph
+                    .match_path_fault_ids(a as usize, dst, distance, &mut path);
+                (a, b, path)
+            })
+            .collect()
+    }
+
+    /// Count, for every edge, how many of `syndromes` have it in their
+    /// predicted correction (see [`Matching::decode_to_fault_ids`]).
+    pub fn edge_match_frequency(&mut self, syndromes: &[Vec<u8>]) -> Vec<usize> {
+        let mut counts = vec![0; self.user_graph.get_num_edges()];
+        for syndrome in syndromes {
+            for id in self.decode_to_fault_ids(syndrome) {
+                counts[id] += 1;
+            }
+        }
+        counts
+    }
+
+    /// Decode a syndrome, handing each match to `on_match` as it is resolved
+    /// instead of collecting them, which keeps peak memory low for very large
+    /// syndromes.
+    ///
+    /// `on_match` receives `(node1, node2, obs_mask, weight)`, with `-1` for
+    /// the boundary as in [`Matching::decode_to_edges`]. Weights are in the
+    /// internal discretized units and `obs_mask` does not include the
+    /// correction for negative-weight edges, so the XOR of all masks equals
+    /// the prediction of [`Matching::decode`] only for graphs without them.
+    pub fn decode_streaming<F>(&mut self, syndrome: &[u8], mut on_match: F)
+    where
+        F: FnMut(i64, i64, ObsMask, TotalWeight),
+    {
+        self.stream_decode(syndrome, &mut |edge, weight| {
+            let from = edge.loc_from.map(|n| n.0 as i64).unwrap_or(-1);
+            let to = edge.loc_to.map(|n| n.0 as i64).unwrap_or(-1);
+            on_match(from, to, edge.obs_mask, weight);
+        });
+    }
+
+    /// Decode a syndrome and return the resolved matches as
+    /// `CompressedEdge`s, e.g. to build the residual problem for a second
+    /// decoding stage. A boundary match has `loc_to == None`. As with
+    /// [`Matching::decode_streaming`], `obs_mask` excludes the correction
+    /// for negative-weight edges.
+    pub fn decode_to_compressed_edges(&mut self, syndrome: &[u8]) -> Vec<CompressedEdge> {
+        let mut edges = Vec::new();
+        self.stream_decode(syndrome, &mut |edge, _| edges.push(edge));
+        edges
+    }
+
+    fn stream_decode<F>(&mut self, syndrome: &[u8], emit: &mut F)
+    where
+        F: FnMut(CompressedEdge, TotalWeight),
+    {
+        let user_graph = &mut self.user_graph;
+        let detection_events_buf = &mut self.detection_events_buf;
+        let effective_events_buf = &mut self.effective_events_buf;
+        let mwpm = user_graph.get_mwpm();
+
+        syndrome_to_detection_events_into(syndrome, detection_events_buf);
+        apply_negative_weight_events_into(
+            detection_events_buf,
+            &mwpm.flooder.graph.negative_weight_detection_events_set,
+            &mwpm.flooder.graph.is_user_graph_boundary_node,
+            effective_events_buf,
+        );
+
+        process_timeline_until_completion(mwpm, effective_events_buf)
+            .unwrap_or_else(|e| panic!("{e}"));
+        stream_matches(mwpm, effective_events_buf, emit);
+        mwpm.reset();
+    }
+}
+
+impl Default for Matching {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DecodeContext<'_> {
+    /// Decode a syndrome as [`Matching::decode`] does, using this
+    /// context's solver.
+    pub fn decode(&mut self, syndrome: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.decode_into(syndrome, &mut out);
+        out
+    }
+
+    /// Decode a syndrome, reporting the syndrome errors of
+    /// [`Matching::try_decode`] instead of panicking.
+    pub fn try_decode(&mut self, syndrome: &[u8]) -> Result<Vec<u8>, MatchingError> {
+        let mut out = Vec::new();
+        decode_syndrome_into(
+            &mut self.mwpm,
+            syndrome,
+            &mut self.detection_events,
+            &mut self.effective_events,
+            &self.matching.observable_order,
+            &mut out,
+        )?;
+        Ok(out)
+    }
+
+    /// Decode a syndrome into a caller-provided output buffer.
+    pub fn decode_into(&mut self, syndrome: &[u8], out: &mut Vec<u8>) {
+        decode_syndrome_into(
+            &mut self.mwpm,
+            syndrome,
+            &mut self.detection_events,
+            &mut self.effective_events,
+            &self.matching.observable_order,
+            out,
+        )
+        .unwrap_or_else(|e| panic!("{e}"));
+    }
+
+    /// The `Matching` this context decodes for.
+    pub fn matching(&self) -> &Matching {
+        self.matching
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -182,9 +1176,41 @@ fn decode_events_to_prediction(
         effective_events,
         num_observables,
         neg_obs_mask,
-        &mut predictions,
-    );
-    predictions
+        &ObservableOrder::LsbFirst,
+        &mut predictions,
+    )
+    .unwrap();
+    predictions
+}
+
+/// Decode `syndrome` with `mwpm`, using `detection_events` and
+/// `effective_events` as scratch.
+fn decode_syndrome_into(
+    mwpm: &mut Mwpm,
+    syndrome: &[u8],
+    detection_events: &mut Vec<usize>,
+    effective_events: &mut Vec<usize>,
+    observable_order: &ObservableOrder,
+    out: &mut Vec<u8>,
+) -> Result<(), MatchingError> {
+    let graph = &mwpm.flooder.graph;
+    let num_observables = graph.num_observables;
+    let neg_obs_mask = compute_neg_obs_mask(&graph.negative_weight_observables_set);
+    syndrome_to_detection_events_into(syndrome, detection_events);
+    apply_negative_weight_events_into(
+        detection_events,
+        &graph.negative_weight_detection_events_set,
+        &graph.is_user_graph_boundary_node,
+        effective_events,
+    );
+    decode_events_to_prediction_into(
+        mwpm,
+        effective_events,
+        num_observables,
+        neg_obs_mask,
+        observable_order,
+        out,
+    )
 }
 
 fn decode_events_to_prediction_into(
@@ -192,14 +1218,30 @@ fn decode_events_to_prediction_into(
     effective_events: &[usize],
     num_observables: usize,
     neg_obs_mask: ObsMask,
+    observable_order: &ObservableOrder,
     out: &mut Vec<u8>,
-) {
-    process_timeline_until_completion(mwpm, effective_events);
+) -> Result<(), MatchingError> {
+    let res = decode_events_to_result(mwpm, effective_events, neg_obs_mask)?;
+    obs_mask_to_predictions_into(res.obs_mask, num_observables, observable_order, out);
+    Ok(())
+}
+
+/// Match `effective_events` and return the correction's observable mask and
+/// discretized weight, both including the negative-weight edges.
+fn decode_events_to_result(
+    mwpm: &mut Mwpm,
+    effective_events: &[usize],
+    neg_obs_mask: ObsMask,
+) -> Result<MatchingResult, MatchingError> {
+    process_timeline_until_completion(mwpm, effective_events)?;
 
     let mut res = shatter_and_extract(mwpm, effective_events);
-    res.obs_mask ^= neg_obs_mask;
-    obs_mask_to_predictions_into(res.obs_mask, num_observables, out);
     mwpm.reset();
+    res += MatchingResult {
+        obs_mask: neg_obs_mask,
+        weight: mwpm.flooder.graph.negative_weight_sum,
+    };
+    Ok(res)
 }
 
 fn syndrome_to_detection_events_into(syndrome: &[u8], out: &mut Vec<usize>) {
@@ -213,6 +1255,35 @@ fn syndrome_to_detection_events_into(syndrome: &[u8], out: &mut Vec<usize>) {
     );
 }
 
+/// Bit-packed counterpart of [`syndrome_to_detection_events_into`]: scans
+/// the shot a `u64` word at a time, so runs of zero bytes cost one compare
+/// per eight bytes. Bits at or past `num_detectors` are ignored.
+fn packed_to_detection_events_into(shot: &[u8], num_detectors: usize, out: &mut Vec<usize>) {
+    out.clear();
+    let mut push_bits = |base: usize, mut bits: u64| {
+        while bits != 0 {
+            let d = base + bits.trailing_zeros() as usize;
+            if d >= num_detectors {
+                return;
+            }
+            out.push(d);
+            bits &= bits - 1;
+        }
+    };
+    let words = shot.chunks_exact(8);
+    let tail = words.remainder();
+    for (i, word) in words.enumerate() {
+        let bits = u64::from_le_bytes(word.try_into().unwrap());
+        if bits != 0 {
+            push_bits(i * 64, bits);
+        }
+    }
+    let base = (shot.len() - tail.len()) * 8;
+    for (i, &byte) in tail.iter().enumerate() {
+        push_bits(base + i * 8, byte as u64);
+    }
+}
+
 fn compute_neg_obs_mask(neg_obs_set: &std::collections::HashSet<usize>) -> ObsMask {
     let mut mask: ObsMask = 0;
     for &obs in neg_obs_set {
@@ -221,6 +1292,21 @@ fn compute_neg_obs_mask(neg_obs_set: &std::collections::HashSet<usize>) -> ObsMa
     mask
 }
 
+/// Drop values occurring an even number of times in sorted `events` and keep
+/// one copy of those occurring an odd number of times.
+fn cancel_sorted_pairs(events: &mut Vec<usize>) {
+    let mut len = 0;
+    for i in 0..events.len() {
+        if len > 0 && events[len - 1] == events[i] {
+            len -= 1;
+        } else {
+            events[len] = events[i];
+            len += 1;
+        }
+    }
+    events.truncate(len);
+}
+
 /// Compute the symmetric difference of detection events and negative-weight
 /// detection events, filtering out user-graph boundary nodes.
 fn apply_negative_weight_events(
@@ -266,6 +1352,199 @@ fn apply_negative_weight_events_into(
             .filter(|&d| d >= is_boundary.len() || !is_boundary[d]),
     );
     out.sort_unstable();
+    debug_assert_symmetric_difference(detection_events, neg_det_set, is_boundary, out);
+}
+
+/// Check that `out` holds, once each, exactly the non-boundary detectors in
+/// an odd number of `detection_events` and `neg_det_set` together. Anything
+/// else changes the parity of some component and can leave the effective
+/// syndrome without a perfect matching.
+fn debug_assert_symmetric_difference(
+    detection_events: &[usize],
+    neg_det_set: &std::collections::HashSet<usize>,
+    is_boundary: &[bool],
+    out: &[usize],
+) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let mut expected: Vec<usize> = detection_events
+        .iter()
+        .chain(neg_det_set)
+        .copied()
+        .filter(|&d| d >= is_boundary.len() || !is_boundary[d])
+        .collect();
+    expected.sort_unstable();
+    cancel_sorted_pairs(&mut expected);
+    let mut actual = out.to_vec();
+    actual.sort_unstable();
+    debug_assert_eq!(
+        actual, expected,
+        "effective detection events are not the symmetric difference of the \
+         syndrome and the negative-weight detection events"
+    );
+}
+
+/// Check that `detection_events` can be matched at all, before any of
+/// them is created: under `strict_isolated_detectors` none may sit at a
+/// detector with no edges, and no component without a boundary may hold
+/// an odd number of them.
+fn check_detection_events(mwpm: &mut Mwpm, detection_events: &[usize]) -> Result<(), MatchingError> {
+    let graph = &mut mwpm.flooder.graph;
+    if mwpm.strict_isolated_detectors
+        && let Some(det) = detection_events
+            .iter()
+            .copied()
+            .find(|&d| graph.nodes.get(d).is_some_and(|n| n.neighbors.is_empty()))
+    {
+        return Err(MatchingError::InvalidSyndrome(format!(
+            "detector {det} fired but has no incident edges"
+        )));
+    }
+    if let Some(det) = graph.odd_boundaryless_component(detection_events) {
+        return Err(MatchingError::InvalidSyndrome(format!(
+            "detector {det} is in a component without a boundary holding an odd number of \
+             detection events, which cannot all be matched"
+        )));
+    }
+    Ok(())
+}
+
+fn process_timeline_until_completion(
+    mwpm: &mut Mwpm,
+    detection_events: &[usize],
+) -> Result<(), MatchingError> {
+    check_detection_events(mwpm, detection_events)?;
+    if mwpm.reset_time_per_decode {
+        mwpm.flooder.queue.cur_time = 0;
+    }
+    mwpm.stats = MwpmStats::default();
+
+    let num_nodes = mwpm.flooder.graph.nodes.len();
+
+    for &det in detection_events {
+        if det >= num_nodes {
+            // Skip out-of-range detection events
+            continue;
+        }
+        if mwpm.flooder.graph.nodes[det].neighbors.is_empty() {
+            // No edge can explain it; its region would grow forever unmatched.
+            mwpm.stats.isolated_detection_events += 1;
+            continue;
+        }
+        mwpm.create_detection_event(NodeIdx(det as u32));
+    }
+
+    while mwpm.step().is_some() {
+        mwpm.stats.num_events += 1;
+    }
+    debug_assert_eq!(mwpm.verify_matching_symmetry(), Ok(()));
+    Ok(())
+}
+
+fn shatter_and_extract(mwpm: &mut Mwpm, detection_events: &[usize]) -> MatchingResult {
+    let mut res = MatchingResult::new();
+    stream_matches(mwpm, detection_events, &mut |edge, weight| {
+        res += MatchingResult {
+            obs_mask: edge.obs_mask,
+            weight,
+        };
+    });
+    res
+}
+
+/// Shatter every matched region reachable from `detection_events`, passing
+/// each match to `emit` as it is resolved.
+fn stream_matches<F>(mwpm: &mut Mwpm, detection_events: &[usize], emit: &mut F)
+where
+    F: FnMut(CompressedEdge, TotalWeight),
+{
+    let mut nodes_to_clean = std::mem::take(&mut mwpm.flooder.node_cleanup_buffer);
+    for &i in detection_events {
+        if i < mwpm.flooder.graph.nodes.len()
+            && mwpm.flooder.graph.nodes[i].region_that_arrived.is_some()
+        {
+            let top = mwpm.flooder.graph.nodes[i].region_that_arrived_top.unwrap();
+            // Collect shell-area nodes to reset *after* shattering, since
+            // pair_and_shatter_subblossoms needs region_that_arrived_top to
+            // locate sub-blossoms.
+            nodes_to_clean.clear();
+            collect_shell_nodes_recursive(mwpm.flooder.region_arena.items(), top, &mut nodes_to_clean);
+            let match_region = mwpm.flooder.region_arena[top.0]
+                .match_
+                .as_ref()
+                .and_then(|m| m.region);
+            if let Some(mr) = match_region {
+                collect_shell_nodes_recursive(
+                    mwpm.flooder.region_arena.items(),
+                    mr,
+                    &mut nodes_to_clean,
+                );
+            }
+            // Shattering reads region_that_arrived_top, so run it first.
+            mwpm.shatter_blossom_and_stream_matches(top, emit);
+            // Now reset the nodes to prevent double-processing.
+            for node_idx in nodes_to_clean.drain(..) {
+                mwpm.flooder.graph.nodes[node_idx.0 as usize].reset();
+            }
+        }
+    }
+    mwpm.flooder.node_cleanup_buffer = nodes_to_clean;
+}
+
+fn collect_shell_nodes_recursive(
+    regions: &[crate::flooder::fill_region::GraphFillRegion],
+    region: RegionIdx,
+    out: &mut Vec<NodeIdx>,
+) {
+    out.extend(regions[region.0 as usize].shell_area.iter().copied());
+    for child in &regions[region.0 as usize].blossom_children {
+        collect_shell_nodes_recursive(regions, child.region, out);
+    }
+}
+
+/// Matched pairs as `(node1, node2, obs_mask, weight)`, with `-1` for the
+/// boundary, normalized so the smaller node comes first and sorted.
+fn extract_match_edges(
+    mwpm: &mut Mwpm,
+    detection_events: &[usize],
+) -> Vec<(i64, i64, ObsMask, TotalWeight)> {
+    let mut edges = Vec::new();
+    stream_matches(mwpm, detection_events, &mut |ce, weight| {
+        if ce.loc_from.is_none() && ce.loc_to.is_none() && ce.obs_mask == 0 {
+            return;
+        }
+        let from = ce.loc_from.map(|n| n.0 as i64).unwrap_or(-1);
+        let to = ce.loc_to.map(|n| n.0 as i64).unwrap_or(-1);
+        // Normalize: smaller first (except boundary -1)
+        let (a, b) = if to == -1 || (from != -1 && from <= to) {
+            (from, to)
+        } else {
+            (to, from)
+        };
+        edges.push((a, b, ce.obs_mask, weight));
+    });
+    // Deduplicate
+    edges.sort();
+    edges.dedup_by_key(|&mut (a, b, obs_mask, _)| (a, b, obs_mask));
+    edges
+}
+
+fn obs_mask_to_predictions_into(
+    obs_mask: ObsMask,
+    num_observables: usize,
+    observable_order: &ObservableOrder,
+    out: &mut Vec<u8>,
+) {
+    let bit = |obs: usize| if obs < 64 { ((obs_mask >> obs) & 1) as u8 } else { 0 };
+    out.clear();
+    match observable_order {
+        ObservableOrder::LsbFirst => out.extend((0..num_observables).map(bit)),
+        ObservableOrder::MsbFirst => out.extend((0..num_observables).rev().map(bit)),
+        ObservableOrder::Custom(order) => {
+            out.extend(order.iter().map(|&obs| if obs < num_observables { bit(obs) } else { 0 }))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -274,6 +1553,14 @@ mod tests {
     use std::collections::HashSet;
     use crate::test_alloc::{allocation_count, reset_allocation_count};
 
+    /// Deterministic xorshift64 step, for reproducible random tests.
+    fn xorshift(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
     #[test]
     fn syndrome_to_detection_events_into_reuses_buffer() {
         let mut out = vec![99, 100];
@@ -284,6 +1571,32 @@ mod tests {
         assert_eq!(out, vec![0]);
     }
 
+    #[test]
+    fn packed_scan_matches_byte_scan() {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = move || xorshift(&mut state);
+        let mut out = Vec::new();
+        for num_detectors in [0, 1, 7, 8, 9, 63, 64, 65, 127, 200] {
+            let shot_bytes = usize::div_ceil(num_detectors, 8);
+            for _ in 0..50 {
+                // Random density, with padding bits past the last detector
+                // set as well.
+                let density = next() % 8;
+                let shot: Vec<u8> = (0..shot_bytes)
+                    .map(|_| (0..8).fold(0u8, |b, i| b | (((next() % 8) < density) as u8) << i))
+                    .collect();
+                let bytes: Vec<u8> = (0..num_detectors).map(|d| (shot[d / 8] >> (d % 8)) & 1).collect();
+                let mut expected = Vec::new();
+                syndrome_to_detection_events_into(&bytes, &mut expected);
+
+                packed_to_detection_events_into(&shot, num_detectors, &mut out);
+                assert_eq!(out, expected, "{num_detectors} detectors, shot {shot:?}");
+            }
+        }
+        packed_to_detection_events_into(&[0xff; 9], 72, &mut out);
+        assert_eq!(out, (0..72).collect::<Vec<_>>());
+    }
+
     #[test]
     fn apply_negative_weight_events_into_filters_and_sorts() {
         let detection_events = vec![0, 2, 4];
@@ -353,7 +1666,7 @@ mod tests {
             &mut effective_events,
         );
 
-        process_timeline_until_completion(mwpm, &effective_events);
+        process_timeline_until_completion(mwpm, &effective_events).unwrap();
         reset_allocation_count();
         let _ = shatter_and_extract(mwpm, &effective_events);
         mwpm.reset();
@@ -381,6 +1694,30 @@ mod tests {
         assert_eq!(allocation_count(), 0);
     }
 
+    #[test]
+    fn decode_into_building_a_deep_tree_does_not_allocate() {
+        // D1-D2 and D3-D4 match first; D0 then grows into D1 and D2 into
+        // D3, giving a tree of depth 2 that D4 takes to the boundary.
+        let mut matching = Matching::new();
+        matching.add_edge(0, 1, 12.0, &[], 0.1);
+        matching.add_edge(1, 2, 10.0, &[], 0.1);
+        matching.add_edge(2, 3, 12.0, &[], 0.1);
+        matching.add_edge(3, 4, 10.0, &[], 0.1);
+        matching.add_boundary_edge(0, 100.0, &[], 0.1);
+        matching.add_boundary_edge(4, 6.0, &[0], 0.1);
+
+        let syndrome = vec![1u8; 5];
+        let mut out = Vec::new();
+        matching.decode_into(&syndrome, &mut out);
+        assert_eq!(out, vec![1]);
+        assert_eq!(matching.last_decode_stats().max_tree_depth, 2);
+
+        reset_allocation_count();
+        matching.decode_into(&syndrome, &mut out);
+
+        assert_eq!(allocation_count(), 0);
+    }
+
     #[test]
     fn decode_batch_into_matches_public_decode_batch_and_reuses_buffers() {
         let mut matching = Matching::new();
@@ -401,133 +1738,89 @@ mod tests {
 
         assert_eq!(allocation_count(), 0);
     }
-}
-
-fn process_timeline_until_completion(mwpm: &mut Mwpm, detection_events: &[usize]) {
-    // Reset queue time
-    mwpm.flooder.queue.cur_time = 0;
-
-    let num_nodes = mwpm.flooder.graph.nodes.len();
-
-    for &det in detection_events {
-        if det >= num_nodes {
-            // Skip out-of-range detection events
-            continue;
-        }
-        mwpm.create_detection_event(NodeIdx(det as u32));
-    }
-
-    loop {
-        let event = mwpm.flooder.run_until_next_mwpm_notification();
-        if event.is_no_event() {
-            break;
-        }
-        mwpm.process_event(event);
-    }
-}
-
-fn shatter_and_extract(mwpm: &mut Mwpm, detection_events: &[usize]) -> MatchingResult {
-    let mut res = MatchingResult::new();
-    let mut nodes_to_clean = std::mem::take(&mut mwpm.flooder.node_cleanup_buffer);
-    for &i in detection_events {
-        if i < mwpm.flooder.graph.nodes.len()
-            && mwpm.flooder.graph.nodes[i].region_that_arrived.is_some()
-        {
-            let top = mwpm.flooder.graph.nodes[i].region_that_arrived_top.unwrap();
-            // Collect shell-area nodes to reset *after* shattering, since
-            // pair_and_shatter_subblossoms needs region_that_arrived_top to
-            // locate sub-blossoms.
-            nodes_to_clean.clear();
-            collect_shell_nodes_recursive(mwpm.flooder.region_arena.items(), top, &mut nodes_to_clean);
-            let match_region = mwpm.flooder.region_arena[top.0]
-                .match_
-                .as_ref()
-                .and_then(|m| m.region);
-            if let Some(mr) = match_region {
-                collect_shell_nodes_recursive(
-                    mwpm.flooder.region_arena.items(),
-                    mr,
-                    &mut nodes_to_clean,
-                );
-            }
-            // Shattering reads region_that_arrived_top, so run it first.
-            res += mwpm.shatter_blossom_and_extract_matches(top);
-            // Now reset the nodes to prevent double-processing.
-            for node_idx in nodes_to_clean.drain(..) {
-                mwpm.flooder.graph.nodes[node_idx.0 as usize].reset();
-            }
-        }
-    }
-    mwpm.flooder.node_cleanup_buffer = nodes_to_clean;
-    res
-}
 
-fn collect_shell_nodes_recursive(
-    regions: &[crate::flooder::fill_region::GraphFillRegion],
-    region: RegionIdx,
-    out: &mut Vec<NodeIdx>,
-) {
-    out.extend(regions[region.0 as usize].shell_area.iter().copied());
-    for child in &regions[region.0 as usize].blossom_children {
-        collect_shell_nodes_recursive(regions, child.region, out);
+    #[test]
+    fn direct_match_paths_skip_the_search() {
+        // D0 -2- D1 -3- D2 -3- D3, with boundary edges of weight 10 on D0
+        // and D3 and a heavy shortcut D1-D3 of weight 20.
+        let mut matching = Matching::new();
+        matching.add_edge(0, 1, 2.0, &[0], 0.1);
+        matching.add_edge(1, 2, 3.0, &[], 0.1);
+        matching.add_edge(2, 3, 3.0, &[1], 0.1);
+        matching.add_edge(1, 3, 20.0, &[], 0.1);
+        matching.add_boundary_edge(0, 10.0, &[], 0.1);
+        matching.add_boundary_edge(3, 10.0, &[], 0.1);
+        let searches = |m: &mut Matching| m.user_graph.get_search_flooder().num_searches;
+
+        // Single hop, including to the boundary.
+        assert_eq!(matching.decode_to_fault_ids(&[1, 1, 0, 0]), vec![0]);
+        assert_eq!(matching.decode_to_fault_ids(&[1, 0, 0, 0]), vec![4]);
+        assert_eq!(searches(&mut matching), 0);
+
+        // D1-D3 is matched along D1-D2-D3, not the direct edge.
+        assert_eq!(matching.decode_to_fault_ids(&[0, 1, 0, 1]), vec![1, 2]);
+        assert_eq!(searches(&mut matching), 1);
+        assert_eq!(
+            matching.decode_to_matched_with_paths(&[0, 1, 0, 1]),
+            vec![(1, 3, vec![1, 2])]
+        );
+        assert_eq!(searches(&mut matching), 2);
     }
-}
 
-fn extract_match_edges(mwpm: &mut Mwpm, detection_events: &[usize]) -> Vec<(i64, i64)> {
-    let mut match_edges = Vec::new();
-    let mut nodes_to_clean = std::mem::take(&mut mwpm.flooder.node_cleanup_buffer);
-    for &i in detection_events {
-        if i < mwpm.flooder.graph.nodes.len()
-            && mwpm.flooder.graph.nodes[i].region_that_arrived.is_some()
-        {
-            let top = mwpm.flooder.graph.nodes[i].region_that_arrived_top.unwrap();
-            // Collect shell-area nodes to reset after shattering
-            nodes_to_clean.clear();
-            collect_shell_nodes_recursive(mwpm.flooder.region_arena.items(), top, &mut nodes_to_clean);
-            let match_region = mwpm.flooder.region_arena[top.0]
-                .match_
-                .as_ref()
-                .and_then(|m| m.region);
-            if let Some(mr) = match_region {
-                collect_shell_nodes_recursive(
-                    mwpm.flooder.region_arena.items(),
-                    mr,
-                    &mut nodes_to_clean,
-                );
+    #[test]
+    fn continuous_time_decodes_match_per_shot_reset() {
+        let build = || {
+            let mut m = Matching::new();
+            let mut state = 0x2545_f491_4f6c_dd1du64;
+            let mut weight = || 1.0 + (xorshift(&mut state) % 9) as f64;
+            for y in 0..5 {
+                for x in 0..5 {
+                    let i = y * 5 + x;
+                    if x + 1 < 5 {
+                        m.add_edge(i, i + 1, weight(), &[0], 0.1);
+                    }
+                    if y + 1 < 5 {
+                        m.add_edge(i, i + 5, weight(), &[], 0.1);
+                    }
+                    if x == 0 || x == 4 {
+                        m.add_boundary_edge(i, weight(), &[1], 0.1);
+                    }
+                }
             }
-            // Shatter to collect compressed edges
-            mwpm.shatter_blossom_and_extract_match_edges(top, &mut match_edges);
-            // Reset nodes to prevent double-processing
-            for node_idx in nodes_to_clean.drain(..) {
-                mwpm.flooder.graph.nodes[node_idx.0 as usize].reset();
+            m
+        };
+        let mut reset = build();
+        let mut continuous = build();
+        continuous.set_reset_time_per_decode(false);
+        let cur_time = |m: &mut Matching| m.user_graph.get_mwpm().flooder.queue.cur_time;
+
+        let mut state = 7u64;
+        let mut last_time = 0;
+        for shot in 0..200 {
+            if shot == 100 {
+                // Continue just below 2^32, so later shots wrap the queue's
+                // cyclic u32 times.
+                continuous.user_graph.get_mwpm().flooder.queue.cur_time = (1 << 32) - 20;
+                last_time = cur_time(&mut continuous);
             }
+            let syndrome: Vec<u8> = (0..25)
+                .map(|_| xorshift(&mut state).is_multiple_of(5) as u8)
+                .collect();
+            assert_eq!(
+                continuous.decode_with_int_weight(&syndrome),
+                reset.decode_with_int_weight(&syndrome),
+                "shot {shot}"
+            );
+            assert_eq!(
+                continuous.decode_to_edges(&syndrome),
+                reset.decode_to_edges(&syndrome),
+                "shot {shot}"
+            );
+            let time = cur_time(&mut continuous);
+            assert!(time >= last_time);
+            last_time = time;
+            assert_eq!(cur_time(&mut reset), 0);
         }
-    }
-    mwpm.flooder.node_cleanup_buffer = nodes_to_clean;
-
-    // Convert CompressedEdge pairs to (i64, i64) detection event pairs
-    let mut edges = Vec::new();
-    for ce in &match_edges {
-        let from = ce.loc_from.map(|n| n.0 as i64).unwrap_or(-1);
-        let to = ce.loc_to.map(|n| n.0 as i64).unwrap_or(-1);
-        // Normalize: smaller first (except boundary -1)
-        let (a, b) = if to == -1 || (from != -1 && from <= to) {
-            (from, to)
-        } else {
-            (to, from)
-        };
-        edges.push((a, b));
-    }
-    // Deduplicate
-    edges.sort();
-    edges.dedup();
-    edges
-}
-
-fn obs_mask_to_predictions_into(obs_mask: ObsMask, num_observables: usize, out: &mut Vec<u8>) {
-    out.clear();
-    out.resize(num_observables, 0);
-    for (i, value) in out.iter_mut().take(64).enumerate() {
-        *value = ((obs_mask >> i) & 1) as u8;
+        assert!(last_time > 1 << 32);
     }
 }
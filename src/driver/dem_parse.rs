@@ -1,22 +1,151 @@
-use crate::driver::user_graph::UserGraph;
+use crate::driver::user_graph::{MergeStrategy, UserGraph};
+use crate::error::MatchingError;
+
+/// Summary of a DEM checked by [`validate_dem`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DemStats {
+    pub num_detectors: usize,
+    pub num_edges: usize,
+    pub num_observables: usize,
+    /// Problems that do not stop decoding but probably indicate a mistake.
+    pub warnings: Vec<String>,
+}
+
+/// What the parser skipped while building the graph.
+#[derive(Default)]
+struct ParseNotes {
+    /// Keywords of skipped instructions, once each.
+    unsupported: Vec<String>,
+    /// Error segments with more than two detectors, which cannot be edges.
+    hyperedges: Vec<String>,
+}
 
 /// Parse a Stim Detector Error Model (DEM) text into a `UserGraph`.
 ///
 /// Handles: `error(p) D<i> ...`, `detector D<i>`, `repeat N { ... }`,
 /// comments (`#`), blank lines, `^` separator, and unknown instructions.
-pub fn parse_dem(text: &str) -> Result<UserGraph, String> {
+pub fn parse_dem(text: &str) -> Result<UserGraph, MatchingError> {
+    Ok(parse_dem_with_notes(text)?.0)
+}
+
+/// Like [`parse_dem`], but combine errors on the same detectors and
+/// observables according to `strategy`. As with [`parse_dem`], errors with
+/// `p <= 0` are dropped and errors with `p >= 1` always occur; neither is
+/// merged.
+pub fn parse_dem_with_merge_strategy(
+    text: &str,
+    strategy: MergeStrategy,
+) -> Result<UserGraph, MatchingError> {
     let mut graph = UserGraph::new();
+    graph.set_merge_strategy(strategy);
+    Ok(parse_into(text, graph)?.0)
+}
+
+/// Like [`parse_dem`], but fail on any instruction other than `error`,
+/// `detector`, `repeat`, `shift_detectors`, `logical_observable` and `tick`
+/// instead of skipping it. The error lists every unsupported keyword.
+pub fn parse_dem_strict(text: &str) -> Result<UserGraph, MatchingError> {
+    let (graph, notes) = parse_dem_with_notes(text)?;
+    if !notes.unsupported.is_empty() {
+        return Err(MatchingError::ParseError(format!(
+            "unsupported DEM instructions: {}",
+            notes.unsupported.join(", ")
+        )));
+    }
+    Ok(graph)
+}
+
+/// Parse a DEM and report its size and likely mistakes without building
+/// a solver. Warns about zero-weight edges (`p = 0.5`), detectors no edge
+/// touches, and undecomposed hyperedges, which are dropped when decoding.
+pub fn validate_dem(text: &str) -> Result<DemStats, MatchingError> {
+    let (graph, notes) = parse_dem_with_notes(text)?;
+    let mut warnings = Vec::new();
+
+    for (i, e) in graph.edges.iter().enumerate() {
+        if e.weight == 0.0 {
+            warnings.push(format!("edge {i} has zero weight (p = 0.5)"));
+        }
+    }
+    let mut touched = vec![false; graph.nodes.len()];
+    for e in &graph.edges {
+        for n in [e.node1, e.node2] {
+            if n < touched.len() {
+                touched[n] = true;
+            }
+        }
+    }
+    for (d, _) in touched.iter().enumerate().filter(|(_, t)| !**t) {
+        warnings.push(format!("detector D{d} is not in any error"));
+    }
+    for h in notes.hyperedges {
+        warnings.push(format!("hyperedge dropped, decompose it with '^': {h}"));
+    }
+
+    Ok(DemStats {
+        num_detectors: graph.get_num_detectors(),
+        num_edges: graph.get_num_edges(),
+        num_observables: graph.num_observables,
+        warnings,
+    })
+}
+
+fn parse_dem_with_notes(text: &str) -> Result<(UserGraph, ParseNotes), MatchingError> {
+    parse_into(text, UserGraph::new())
+}
+
+fn parse_into(
+    text: &str,
+    mut graph: UserGraph,
+) -> Result<(UserGraph, ParseNotes), MatchingError> {
     let lines: Vec<&str> = text.lines().collect();
     let mut detector_offset = 0usize;
-    parse_block(&lines, &mut graph, &mut detector_offset)?;
-    Ok(graph)
+    let mut coordinate_offset = Vec::new();
+    let mut notes = ParseNotes::default();
+    parse_block(
+        &lines,
+        &mut graph,
+        &mut detector_offset,
+        &mut coordinate_offset,
+        &mut notes,
+    )
+    .map_err(MatchingError::ParseError)?;
+    Ok((graph, notes))
+}
+
+/// Instruction keyword of a DEM line: its first token, without arguments.
+fn instruction_keyword(line: &str) -> &str {
+    let end = line
+        .find(|c: char| c.is_whitespace() || c == '(')
+        .unwrap_or(line.len());
+    &line[..end]
 }
 
-/// Parse a slice of lines into `graph`, applying `detector_offset` to all D indices.
+/// Parenthesized numeric arguments of an instruction, e.g. the coordinates
+/// in `detector(1, 2, 0) D3`. Empty when there are none.
+fn instruction_arguments(line: &str) -> Result<Vec<f64>, String> {
+    let keyword = instruction_keyword(line);
+    let rest = &line[keyword.len()..];
+    let Some(args) = rest.strip_prefix('(') else {
+        return Ok(Vec::new());
+    };
+    let close = args.find(')').ok_or_else(|| format!("missing ')' in '{line}'"))?;
+    args[..close]
+        .split(',')
+        .filter(|a| !a.trim().is_empty())
+        .map(|a| a.trim().parse().map_err(|e| format!("bad argument '{a}': {e}")))
+        .collect()
+}
+
+/// Parse a slice of lines into `graph`, applying `detector_offset` to all D
+/// indices and `coordinate_offset` to detector coordinates.
+/// Skipped instructions and hyperedges are recorded in `notes`.
 fn parse_block(
     lines: &[&str],
     graph: &mut UserGraph,
     detector_offset: &mut usize,
+    coordinate_offset: &mut Vec<f64>,
+    notes: &mut ParseNotes,
 ) -> Result<usize, String> {
     let mut max_detector: usize = 0;
     let mut i = 0;
@@ -29,21 +158,35 @@ fn parse_block(
         }
 
         if line.starts_with("error") {
-            let det = parse_error_line(line, graph, *detector_offset)?;
+            let det = parse_error_line(line, graph, *detector_offset, notes)?;
             max_detector = max_detector.max(det);
         } else if line.starts_with("detector") {
-            let det = parse_detector_line(line, graph, *detector_offset)?;
+            let det = parse_detector_line(line, graph, *detector_offset, coordinate_offset)?;
             max_detector = max_detector.max(det);
         } else if line.starts_with("shift_detectors") {
             *detector_offset += parse_shift_detectors_line(line)?;
+            let shift = instruction_arguments(line)?;
+            if coordinate_offset.len() < shift.len() {
+                coordinate_offset.resize(shift.len(), 0.0);
+            }
+            for (offset, delta) in coordinate_offset.iter_mut().zip(shift) {
+                *offset += delta;
+            }
         } else if line.starts_with("repeat") {
             let (det, consumed) =
-                parse_repeat(lines, i, graph, detector_offset)?;
+                parse_repeat(lines, i, graph, detector_offset, coordinate_offset, notes)?;
             max_detector = max_detector.max(det);
             i += consumed;
             continue;
+        } else {
+            // All other instructions (logical_observable, etc.) are skipped.
+            let keyword = instruction_keyword(line);
+            if !matches!(keyword, "logical_observable" | "tick")
+                && !notes.unsupported.iter().any(|k| k == keyword)
+            {
+                notes.unsupported.push(keyword.to_string());
+            }
         }
-        // All other instructions (logical_observable, etc.) are skipped.
         i += 1;
     }
     Ok(max_detector)
@@ -55,6 +198,7 @@ fn parse_error_line(
     line: &str,
     graph: &mut UserGraph,
     detector_offset: usize,
+    notes: &mut ParseNotes,
 ) -> Result<usize, String> {
     // Extract probability from error(p)
     let open = line.find('(').ok_or("error line missing '('")?;
@@ -81,26 +225,38 @@ fn parse_error_line(
             }
         }
 
+        // An error that always occurs needs no edge, however many detectors.
+        if detectors.len() > 2 && p < 1.0 {
+            notes.hyperedges.push(line.to_string());
+        }
         graph.handle_dem_instruction(p, &detectors, observables);
     }
     Ok(max_det)
 }
 
-/// Parse a `detector D<i> [coords...]` line. Ensures the node exists.
+/// Parse a `detector[(coords...)] D<i>` line. Ensures the node exists and
+/// records its coordinates, shifted by `coordinate_offset`.
 /// Returns the raw detector index (before offset).
 fn parse_detector_line(
     line: &str,
     graph: &mut UserGraph,
     detector_offset: usize,
+    coordinate_offset: &[f64],
 ) -> Result<usize, String> {
+    let mut coordinates = instruction_arguments(line)?;
+    for (c, offset) in coordinates.iter_mut().zip(coordinate_offset) {
+        *c += offset;
+    }
     for token in line.split_whitespace().skip(1) {
         if let Some(rest) = token.strip_prefix('D') {
             let idx: usize = rest.parse().map_err(|e| format!("bad detector index: {e}"))?;
-            // Ensure the node exists in the graph (coordinates are ignored)
             let shifted = idx + detector_offset;
             if shifted >= graph.nodes.len() {
                 graph.nodes.resize_with(shifted + 1, Default::default);
             }
+            if !coordinates.is_empty() {
+                graph.nodes[shifted].coordinates = coordinates;
+            }
             return Ok(idx);
         }
     }
@@ -109,11 +265,17 @@ fn parse_detector_line(
 
 /// Parse a `repeat N { ... }` block starting at `lines[start]`.
 /// Returns (max_detector_in_block, number_of_lines_consumed).
+///
+/// `detector_offset` and `coordinate_offset` are left advanced by every
+/// `shift_detectors` the body executed (`N` times its per-iteration shift),
+/// so lines after the block are read in the shifted frame.
 fn parse_repeat(
     lines: &[&str],
     start: usize,
     graph: &mut UserGraph,
     detector_offset: &mut usize,
+    coordinate_offset: &mut Vec<f64>,
+    notes: &mut ParseNotes,
 ) -> Result<(usize, usize), String> {
     let header = lines[start].trim();
     // Parse repeat count
@@ -149,7 +311,7 @@ fn parse_repeat(
 
     let mut overall_max = 0usize;
     for _ in 0..count {
-        let det = parse_block(&body_lines, graph, detector_offset)?;
+        let det = parse_block(&body_lines, graph, detector_offset, coordinate_offset, notes)?;
         overall_max = overall_max.max(det);
     }
 
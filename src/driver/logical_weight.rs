@@ -0,0 +1,437 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::driver::decoding::Matching;
+use crate::driver::user_graph::UserGraph;
+use crate::error::MatchingError;
+use crate::types::*;
+
+/// Most observables [`UserGraph::logical_class_weights`] accepts. With `k`
+/// observables it may decode up to `2^(2^k - 1)` auxiliary syndromes.
+pub const MAX_LOGICAL_CLASS_OBSERVABLES: usize = 3;
+
+/// Boundary node sets between which the logical operator of `observable`
+/// runs, for codes where a logical connects two specific boundaries rather
+/// than any boundary to any other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundaryPair {
+    pub observable: usize,
+    /// Boundary nodes (from `set_boundary`, or `usize::MAX` for edges added
+    /// with `add_boundary_edge`) where the operator may start.
+    pub from: Vec<usize>,
+    /// Boundary nodes where the operator may end.
+    pub to: Vec<usize>,
+}
+
+/// The edges flipping an observable, plus the boundary pair it is measured
+/// between if any. Observables with equal keys have equal logical weights.
+type LogicalWeightKey = (Vec<usize>, Option<(Vec<usize>, Vec<usize>)>);
+
+/// Results of [`UserGraph::min_logical_weight`] kept between calls, so
+/// observables flipped by the same edges, and repeated queries, are only
+/// searched once. Cleared whenever the graph changes.
+#[derive(Debug, Default)]
+pub(crate) struct LogicalWeightCache {
+    weights: HashMap<LogicalWeightKey, f64>,
+    /// Number of observables actually searched, for tests.
+    searches: usize,
+}
+
+impl LogicalWeightCache {
+    pub(crate) fn clear(&mut self) {
+        self.weights.clear();
+    }
+}
+
+/// Adjacency list over user nodes plus one virtual boundary node at index
+/// `nodes.len()`: `(neighbor, weight, obs_mask)` per edge end.
+type Adjacency = Vec<Vec<(usize, f64, ObsMask)>>;
+
+impl UserGraph {
+    /// Weight of the lightest undetectable error flipping each observable,
+    /// or `f64::INFINITY` if no such error exists.
+    ///
+    /// By default all boundary nodes are treated as one sink, and the
+    /// result is the lightest closed chain of edges (through the boundary or
+    /// not) crossing the observable an odd number of times. An observable
+    /// listed in `boundary_pairs` instead gets the lightest chain from any
+    /// node of `from` to any node of `to` that flips it, with boundary nodes
+    /// kept distinct. Weights are taken as absolute values.
+    ///
+    /// Results are cached until the graph changes, and observables flipped
+    /// by exactly the same edges share one search.
+    pub fn min_logical_weight(&self, boundary_pairs: &[BoundaryPair]) -> Vec<f64> {
+        let mut cache = self.logical_weight_cache.lock().unwrap();
+        let keys: Vec<LogicalWeightKey> = (0..self.num_observables)
+            .map(|obs| {
+                let flipping = self
+                    .edges
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, e)| {
+                        self.edge_observables(e).iter().filter(|&&o| o == obs).count() % 2 == 1
+                    })
+                    .map(|(i, _)| i)
+                    .collect();
+                let pair = boundary_pairs.iter().find(|p| p.observable == obs).map(|p| {
+                    let mut from = p.from.clone();
+                    let mut to = p.to.clone();
+                    from.sort_unstable();
+                    to.sort_unstable();
+                    (from, to)
+                });
+                (flipping, pair)
+            })
+            .collect();
+        if keys.iter().all(|key| cache.weights.contains_key(key)) {
+            return keys.iter().map(|key| cache.weights[key]).collect();
+        }
+
+        let virtual_boundary = self.nodes.len();
+        let merged = self.logical_adjacency(true);
+        let separate = if boundary_pairs.is_empty() {
+            Vec::new()
+        } else {
+            self.logical_adjacency(false)
+        };
+
+        (0..self.num_observables)
+            .map(|obs| {
+                if let Some(&weight) = cache.weights.get(&keys[obs]) {
+                    return weight;
+                }
+                cache.searches += 1;
+                let weight = self.search_min_logical_weight(
+                    obs,
+                    boundary_pairs,
+                    &merged,
+                    &separate,
+                    virtual_boundary,
+                );
+                cache.weights.insert(keys[obs].clone(), weight);
+                weight
+            })
+            .collect()
+    }
+
+    /// Uncached body of [`UserGraph::min_logical_weight`] for one observable.
+    fn search_min_logical_weight(
+        &self,
+        obs: usize,
+        boundary_pairs: &[BoundaryPair],
+        merged: &Adjacency,
+        separate: &Adjacency,
+        virtual_boundary: usize,
+    ) -> f64 {
+        let bit: ObsMask = 1 << obs;
+        if let Some(pair) = boundary_pairs.iter().find(|p| p.observable == obs) {
+            let map = |n: usize| if n == usize::MAX { virtual_boundary } else { n };
+            let sources: Vec<usize> = pair.from.iter().map(|&n| map(n)).collect();
+            let mut is_target = vec![false; virtual_boundary + 1];
+            for &n in &pair.to {
+                is_target[map(n)] = true;
+            }
+            return parity_distance(separate, bit, &sources, &is_target, f64::INFINITY);
+        }
+
+        // Every odd closed chain uses an edge flipping `obs`, so it
+        // passes through an endpoint of one.
+        let mut best = f64::INFINITY;
+        let mut is_target = vec![false; virtual_boundary + 1];
+        for v in 0..merged.len() {
+            if !merged[v].iter().any(|&(_, _, mask)| mask & bit != 0) {
+                continue;
+            }
+            is_target[v] = true;
+            best = best.min(parity_distance(merged, bit, &[v], &is_target, best));
+            is_target[v] = false;
+        }
+        best
+    }
+
+    /// Weight of the lightest correction for `syndrome` in each logical
+    /// class: entry `c` is the lightest set of edges that produces the
+    /// syndrome and flips exactly the observables in bitmask `c`, or
+    /// `f64::INFINITY` if there is none. The smallest entry is the weight of
+    /// the correction `decode` picks.
+    ///
+    /// Every cycle of edges avoiding the boundary must flip no observable,
+    /// as in planar codes; then an observable only depends on which
+    /// boundary edges a correction uses. Relabelling the edges so that only
+    /// boundary edges flip observables, and turning each class of boundary
+    /// edges other than the non-flipping one into a detector, the parity of
+    /// matches into each class is set by whether that detector fires. Each
+    /// logical class is then the lightest of a few ordinary decodes. Edge
+    /// weights must be non-negative, and there may be at most
+    /// [`MAX_LOGICAL_CLASS_OBSERVABLES`] observables.
+    pub fn logical_class_weights(&self, syndrome: &[u8]) -> Result<Vec<f64>, MatchingError> {
+        let k = self.num_observables;
+        if k > MAX_LOGICAL_CLASS_OBSERVABLES {
+            return Err(MatchingError::ObservableOverflow {
+                operation: "logical_class_weights",
+                max: MAX_LOGICAL_CLASS_OBSERVABLES,
+                num_observables: k,
+            });
+        }
+        if let Some(i) = self.edges.iter().position(|e| e.weight < 0.0) {
+            return Err(MatchingError::InvalidWeight {
+                edge: i,
+                weight: self.edges[i].weight,
+            });
+        }
+        let n = self.nodes.len();
+        let num_classes = 1usize << k;
+        let masks: Vec<ObsMask> = self
+            .edges
+            .iter()
+            .map(|e| {
+                self.edge_observables(e)
+                    .iter()
+                    .fold(0, |mask: ObsMask, &obs| mask ^ (1 << obs))
+            })
+            .collect();
+
+        // Potential `phi` with `phi[u] ^ phi[v]` equal to the mask of every
+        // internal edge (u, v), found by a traversal per component.
+        let mut internal: Vec<Vec<(usize, ObsMask, usize)>> = vec![Vec::new(); n];
+        for (i, e) in self.edges.iter().enumerate() {
+            if !self.is_boundary_node(e.node1) && !self.is_boundary_node(e.node2) {
+                internal[e.node1].push((e.node2, masks[i], i));
+                internal[e.node2].push((e.node1, masks[i], i));
+            }
+        }
+        let mut phi: Vec<Option<ObsMask>> = vec![None; n];
+        for root in 0..n {
+            if phi[root].is_some() {
+                continue;
+            }
+            phi[root] = Some(0);
+            let mut stack = vec![root];
+            while let Some(u) = stack.pop() {
+                let pu = phi[u].unwrap();
+                for &(v, mask, i) in &internal[u] {
+                    match phi[v] {
+                        None => {
+                            phi[v] = Some(pu ^ mask);
+                            stack.push(v);
+                        }
+                        Some(pv) if pv != pu ^ mask => {
+                            return Err(MatchingError::InvalidGraph(format!(
+                                "edge {i} closes a cycle that flips observables without \
+                                 reaching the boundary"
+                            )));
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+        let phi: Vec<ObsMask> = phi.into_iter().map(Option::unwrap).collect();
+
+        // Auxiliary graph: internal edges flip nothing, boundary edges of
+        // class `b != 0` end at detector `n + b - 1`, and class 0 keeps the
+        // boundary. `fault_edge[j]` is the original index of edge `j`. Its
+        // components are tracked to skip syndromes with no correction.
+        let num_aux_nodes = n + num_classes - 1;
+        let mut aux = Matching::new();
+        let mut fault_edge = Vec::new();
+        let mut parent: Vec<usize> = (0..num_aux_nodes).collect();
+        let mut reaches_boundary = vec![false; num_aux_nodes];
+        for (i, e) in self.edges.iter().enumerate() {
+            let (b1, b2) = (self.is_boundary_node(e.node1), self.is_boundary_node(e.node2));
+            let (u, v) = match (b1, b2) {
+                (true, true) => continue,
+                (false, false) => {
+                    aux.add_edge(e.node1, e.node2, e.weight, &[], e.error_probability);
+                    (e.node1, e.node2)
+                }
+                _ => {
+                    let u = if b1 { e.node2 } else { e.node1 };
+                    let class = (masks[i] ^ phi[u]) as usize;
+                    if class == 0 {
+                        aux.add_boundary_edge(u, e.weight, &[], e.error_probability);
+                        reaches_boundary[u] = true;
+                        (u, u)
+                    } else {
+                        aux.add_edge(u, n + class - 1, e.weight, &[], e.error_probability);
+                        (u, n + class - 1)
+                    }
+                }
+            };
+            let (ru, rv) = (find_root(&mut parent, u), find_root(&mut parent, v));
+            parent[ru] = rv;
+            fault_edge.push(i);
+        }
+        for x in 0..num_aux_nodes {
+            if reaches_boundary[x] {
+                let root = find_root(&mut parent, x);
+                reaches_boundary[root] = true;
+            }
+        }
+
+        let events: Vec<usize> = syndrome
+            .iter()
+            .enumerate()
+            .filter(|&(d, &v)| v != 0 && d < n && !self.is_boundary_node(d))
+            .map(|(d, _)| d)
+            .collect();
+        let offset = events.iter().fold(0, |acc, &d| acc ^ phi[d]);
+        let mut weights = vec![f64::INFINITY; num_classes];
+        let mut aux_syndrome = vec![0u8; num_aux_nodes];
+        let mut odd_component = vec![false; num_aux_nodes];
+        // Each subset of fired class detectors yields the class given by the
+        // XOR of their labels.
+        for subset in 0..1usize << (num_classes - 1) {
+            let fired = |b: usize| subset & (1 << (b - 1)) != 0;
+            aux_syndrome.fill(0);
+            for &d in &events {
+                aux_syndrome[d] = 1;
+            }
+            let mut class = offset as usize;
+            for b in (1..num_classes).filter(|&b| fired(b)) {
+                aux_syndrome[n + b - 1] = 1;
+                class ^= b;
+            }
+            // A component without the boundary needs an even number of events.
+            odd_component.fill(false);
+            for x in (0..num_aux_nodes).filter(|&x| aux_syndrome[x] != 0) {
+                let root = find_root(&mut parent, x);
+                odd_component[root] ^= true;
+            }
+            if (0..num_aux_nodes).any(|r| odd_component[r] && !reaches_boundary[r]) {
+                continue;
+            }
+            let faults = aux.decode_to_fault_ids(&aux_syndrome);
+            let weight: f64 = faults.iter().map(|&j| self.edges[fault_edge[j]].weight).sum();
+            debug_assert_eq!(
+                faults.iter().fold(0, |acc, &j| acc ^ masks[fault_edge[j]]) as usize,
+                class
+            );
+            weights[class] = weights[class].min(weight);
+        }
+        Ok(weights)
+    }
+
+    fn logical_adjacency(&self, merge_boundary: bool) -> Adjacency {
+        let virtual_boundary = self.nodes.len();
+        let map = |n: usize| {
+            if n == usize::MAX || (merge_boundary && self.is_boundary_node(n)) {
+                virtual_boundary
+            } else {
+                n
+            }
+        };
+        let mut adjacency: Adjacency = vec![Vec::new(); virtual_boundary + 1];
+        for e in &self.edges {
+            let (a, b) = (map(e.node1), map(e.node2));
+            let mask = self
+                .edge_observables(e)
+                .iter()
+                .fold(0, |mask: ObsMask, &obs| mask ^ (1 << obs));
+            let w = e.weight.abs();
+            adjacency[a].push((b, w, mask));
+            if a != b {
+                adjacency[b].push((a, w, mask));
+            }
+        }
+        adjacency
+    }
+}
+
+/// Dijkstra over `(node, parity of bit)` states from `(s, 0)` for each
+/// source to the nearest `(t, 1)` with `is_target[t]`. Gives up once the
+/// distance reaches `limit`.
+fn parity_distance(
+    adjacency: &Adjacency,
+    bit: ObsMask,
+    sources: &[usize],
+    is_target: &[bool],
+    limit: f64,
+) -> f64 {
+    let mut dist = vec![f64::INFINITY; adjacency.len() * 2];
+    let mut heap = BinaryHeap::new();
+    for &s in sources {
+        dist[s * 2] = 0.0;
+        // Non-negative floats order the same as their bit patterns.
+        heap.push(Reverse((0f64.to_bits(), s * 2)));
+    }
+    while let Some(Reverse((d_bits, state))) = heap.pop() {
+        let d = f64::from_bits(d_bits);
+        if d >= limit {
+            break;
+        }
+        if d > dist[state] {
+            continue;
+        }
+        let (node, parity) = (state / 2, state % 2);
+        if parity == 1 && is_target[node] {
+            return d;
+        }
+        for &(next, w, mask) in &adjacency[node] {
+            let next_state = next * 2 + (parity ^ ((mask & bit != 0) as usize));
+            let nd = d + w;
+            if nd < dist[next_state] {
+                dist[next_state] = nd;
+                heap.push(Reverse((nd.to_bits(), next_state)));
+            }
+        }
+    }
+    f64::INFINITY
+}
+
+/// Union-find root of `x`, halving paths on the way.
+fn find_root(parent: &mut [usize], mut x: usize) -> usize {
+    while parent[x] != x {
+        parent[x] = parent[parent[x]];
+        x = parent[x];
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// d x d surface-code patch with L0 on the left boundary edges and L1
+    /// on the same edges, so both observables share one search.
+    fn patch_with_shared_observables(d: usize) -> UserGraph {
+        let mut g = UserGraph::new();
+        let cols = d - 1;
+        let det = |r: usize, c: usize| r * cols + c;
+        for r in 0..d {
+            g.add_boundary_edge(det(r, 0), vec![0, 1], 1.0, 0.01);
+            for c in 0..cols - 1 {
+                g.add_edge(det(r, c), det(r, c + 1), vec![], 1.0, 0.01);
+            }
+            g.add_boundary_edge(det(r, cols - 1), vec![], 1.0, 0.01);
+            if r + 1 < d {
+                for c in 0..cols {
+                    g.add_edge(det(r, c), det(r + 1, c), vec![], 1.0, 0.01);
+                }
+            }
+        }
+        g
+    }
+
+    fn searches(g: &UserGraph) -> usize {
+        g.logical_weight_cache.lock().unwrap().searches
+    }
+
+    #[test]
+    fn min_logical_weight_shares_searches_between_identical_observables() {
+        let mut g = patch_with_shared_observables(7);
+        assert_eq!(g.min_logical_weight(&[]), vec![7.0, 7.0]);
+        assert_eq!(searches(&g), 1);
+
+        // Repeated queries are answered from the cache.
+        assert_eq!(g.min_logical_weight(&[]), vec![7.0, 7.0]);
+        assert_eq!(searches(&g), 1);
+
+        // Any change to the graph clears it. A lighter L0-only edge parallel
+        // to a shared one splits the observables, and together the two form
+        // a chain flipping only L1.
+        g.add_boundary_edge(0, vec![0], 0.5, 0.01);
+        assert_eq!(g.min_logical_weight(&[]), vec![6.5, 1.5]);
+        assert_eq!(searches(&g), 3);
+    }
+}
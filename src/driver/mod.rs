@@ -1,3 +1,9 @@
 pub mod decoding;
 pub mod dem_parse;
+pub mod logical_weight;
+pub mod pair;
+pub mod refine;
 pub mod user_graph;
+
+/// The crate-wide error type, kept reachable at its old path.
+pub use crate::error;
@@ -0,0 +1,67 @@
+use crate::driver::decoding::Matching;
+use crate::error::MatchingError;
+
+/// Decoders for the two sectors of a CSS code, built by [`Matching::pair`]:
+/// one matches the syndrome of X errors, the other that of Z errors.
+pub struct MatchingPair {
+    pub x: Matching,
+    pub z: Matching,
+}
+
+impl Matching {
+    /// Pair an X-sector and a Z-sector decoder. The two share one
+    /// observable numbering, so no observable may be flipped by both; for
+    /// instance the X sector might use observables `0..k` and the Z sector
+    /// `k..2k`.
+    pub fn pair(x_decoder: Matching, z_decoder: Matching) -> Result<MatchingPair, MatchingError> {
+        let z_observables = z_decoder.flipped_observables();
+        if let Some(obs) = x_decoder
+            .flipped_observables()
+            .into_iter()
+            .find(|obs| z_observables.binary_search(obs).is_ok())
+        {
+            return Err(MatchingError::InvalidGraph(format!(
+                "observable {obs} is flipped by both the X and the Z decoder"
+            )));
+        }
+        Ok(MatchingPair {
+            x: x_decoder,
+            z: z_decoder,
+        })
+    }
+}
+
+impl MatchingPair {
+    /// Decode each sector on its own graph, returning the X and Z
+    /// predictions.
+    pub fn decode(&mut self, x_syndrome: &[u8], z_syndrome: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        (self.x.decode(x_syndrome), self.z.decode(z_syndrome))
+    }
+
+    /// Decode both sectors and combine them into one prediction over the
+    /// shared observable numbering: the two predictions XORed position by
+    /// position, the shorter padded with zeros.
+    pub fn decode_combined(&mut self, x_syndrome: &[u8], z_syndrome: &[u8]) -> Vec<u8> {
+        let (x, z) = self.decode(x_syndrome, z_syndrome);
+        let (mut long, short) = if x.len() >= z.len() { (x, z) } else { (z, x) };
+        for (l, s) in long.iter_mut().zip(short) {
+            *l ^= s;
+        }
+        long
+    }
+
+    /// Decode a batch of shots, running the two sectors on separate
+    /// threads. Returns the X and Z predictions of every shot.
+    pub fn decode_batch(
+        &mut self,
+        x_syndromes: &[Vec<u8>],
+        z_syndromes: &[Vec<u8>],
+    ) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        let MatchingPair { x, z } = self;
+        std::thread::scope(|s| {
+            let z_handle = s.spawn(|| z.decode_batch(z_syndromes));
+            let x_predictions = x.decode_batch(x_syndromes);
+            (x_predictions, z_handle.join().unwrap())
+        })
+    }
+}
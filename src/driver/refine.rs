@@ -0,0 +1,145 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::driver::user_graph::UserGraph;
+use crate::types::*;
+
+/// Adjacency by edge weight in the original float units, with the boundary
+/// as an extra node that paths can end at but not pass through.
+type FloatAdjacency = Vec<Vec<(usize, f64, ObsMask)>>;
+
+impl UserGraph {
+    /// Refinement pass over a matching found with discretized weights.
+    ///
+    /// Rounding each weight to the decoder's integer units can make a
+    /// matching that is heavier in the original float weights look
+    /// lighter, or tie with a lighter one. This recomputes the matched
+    /// distances with the float weights (the absolute value of negative
+    /// ones, as the decoder uses them) and repeatedly re-pairs any two
+    /// matches, or splits one into two boundary matches, while that lowers
+    /// the float total. `None` is the boundary. Returns the observables
+    /// flipped along the float-shortest paths of the final matches.
+    ///
+    /// This is a local search: it confirms that no exchange between two
+    /// matches helps, which catches the misrankings rounding produces in
+    /// practice, but does not re-solve the matching from scratch. Its cost
+    /// is one Dijkstra search per matched detector.
+    pub(crate) fn refine_matching(&self, matches: &mut Vec<(usize, Option<usize>)>) -> ObsMask {
+        let n = self.nodes.len();
+        let boundary = n;
+        let mut adjacency: FloatAdjacency = vec![Vec::new(); n + 1];
+        for e in self.usable_edges() {
+            let w = self.scaled_weight(e).abs();
+            let mask = Self::obs_mask(&self.edge_observables(e));
+            match (self.is_boundary_node(e.node1), self.is_boundary_node(e.node2)) {
+                (false, false) => {
+                    adjacency[e.node1].push((e.node2, w, mask));
+                    adjacency[e.node2].push((e.node1, w, mask));
+                }
+                (false, true) => adjacency[e.node1].push((boundary, w, mask)),
+                (true, false) => adjacency[e.node2].push((boundary, w, mask)),
+                (true, true) => {}
+            }
+        }
+
+        let mut events: Vec<usize> = matches
+            .iter()
+            .flat_map(|&(a, b)| std::iter::once(a).chain(b))
+            .collect();
+        events.sort_unstable();
+        events.dedup();
+        let paths: Vec<(Vec<f64>, Vec<ObsMask>)> = events
+            .iter()
+            .map(|&s| float_shortest_paths(&adjacency, s, boundary))
+            .collect();
+        let path = |a: Option<usize>, b: Option<usize>| -> (f64, ObsMask) {
+            match (a, b) {
+                (None, None) => (0.0, 0),
+                (Some(a), b) | (b, Some(a)) => {
+                    let (dist, obs) = &paths[events.binary_search(&a).unwrap()];
+                    let t = b.unwrap_or(boundary);
+                    (dist[t], obs[t])
+                }
+            }
+        };
+        let cost = |a, b| path(a, b).0;
+
+        // An extra `(None, None)` pair stands in for splitting a match
+        // between two detectors into two boundary matches.
+        let mut pairs: Vec<(Option<usize>, Option<usize>)> =
+            matches.iter().map(|&(a, b)| (Some(a), b)).collect();
+        loop {
+            let mut improved = false;
+            for i in 0..pairs.len() {
+                for j in i + 1..=pairs.len() {
+                    let (a, b) = pairs[i];
+                    let (c, d) = pairs.get(j).copied().unwrap_or((None, None));
+                    let current = cost(a, b) + cost(c, d);
+                    let mut best = None;
+                    let mut best_cost = current;
+                    for (p, q) in [((a, c), (b, d)), ((a, d), (b, c))] {
+                        let w = cost(p.0, p.1) + cost(q.0, q.1);
+                        if w + 1e-9 * (1.0 + w.abs()) < best_cost {
+                            best = Some((p, q));
+                            best_cost = w;
+                        }
+                    }
+                    if let Some((p, q)) = best {
+                        pairs[i] = p;
+                        match pairs.get_mut(j) {
+                            Some(pair) => *pair = q,
+                            None => pairs.push(q),
+                        }
+                        improved = true;
+                    }
+                }
+            }
+            pairs.retain(|&(a, b)| a.is_some() || b.is_some());
+            if !improved {
+                break;
+            }
+        }
+
+        matches.clear();
+        let mut obs_mask = 0;
+        for (a, b) in pairs {
+            obs_mask ^= path(a, b).1;
+            match (a, b) {
+                (Some(a), b) | (b, Some(a)) => matches.push((a, b)),
+                (None, None) => unreachable!(),
+            }
+        }
+        obs_mask
+    }
+}
+
+/// Dijkstra from `source` over `adjacency`, returning the distance to every
+/// node and the observables flipped along the path found. `boundary` is
+/// reached but not expanded.
+fn float_shortest_paths(
+    adjacency: &FloatAdjacency,
+    source: usize,
+    boundary: usize,
+) -> (Vec<f64>, Vec<ObsMask>) {
+    let mut dist = vec![f64::INFINITY; adjacency.len()];
+    let mut obs = vec![0; adjacency.len()];
+    let mut heap = BinaryHeap::new();
+    dist[source] = 0.0;
+    // Non-negative floats order the same as their bit patterns.
+    heap.push(Reverse((0f64.to_bits(), source)));
+    while let Some(Reverse((d_bits, node))) = heap.pop() {
+        let d = f64::from_bits(d_bits);
+        if d > dist[node] || node == boundary {
+            continue;
+        }
+        for &(next, w, mask) in &adjacency[node] {
+            let nd = d + w;
+            if nd < dist[next] {
+                dist[next] = nd;
+                obs[next] = obs[node] ^ mask;
+                heap.push(Reverse((nd.to_bits(), next)));
+            }
+        }
+    }
+    (dist, obs)
+}
@@ -1,8 +1,14 @@
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
 
+use crate::driver::logical_weight::LogicalWeightCache;
+use crate::error::MatchingError;
 use crate::flooder::graph::MatchingGraph;
 use crate::flooder::graph_flooder::GraphFlooder;
 use crate::matcher::mwpm::Mwpm;
+use crate::search::search_flooder::SearchFlooder;
 use crate::search::search_graph::SearchGraph;
 use crate::types::*;
 
@@ -10,6 +16,23 @@ use crate::types::*;
 /// Matches PyMatching's `NUM_DISTINCT_WEIGHTS = 1 << (sizeof(weight_int)*8 - 8)`.
 pub const NUM_DISTINCT_WEIGHTS: Weight = 1 << (std::mem::size_of::<Weight>() * 8 - 8);
 
+/// Number of low-order bits of a discretized weight reserved for the
+/// secondary (tie-breaking) weight when any edge carries one.
+///
+/// With the default `NUM_DISTINCT_WEIGHTS = 2^24`, the primary weight keeps
+/// `2^16` distinct levels and the secondary weight gets `2^8`. The resulting
+/// order is exactly lexicographic in `(primary, secondary)` as long as the
+/// summed secondary weight along any competing matching stays below
+/// `2^SECONDARY_WEIGHT_BITS`; larger sums carry into the primary bits.
+pub const SECONDARY_WEIGHT_BITS: u32 = 8;
+
+/// Source of [`UserGraph::fingerprint`] values, unique across all graphs.
+static NEXT_FINGERPRINT: AtomicU64 = AtomicU64::new(0);
+
+fn next_fingerprint() -> u64 {
+    NEXT_FINGERPRINT.fetch_add(1, Ordering::Relaxed)
+}
+
 /// A user-facing edge between two detector nodes (or one node and boundary).
 #[derive(Debug, Clone)]
 pub struct UserEdge {
@@ -17,13 +40,41 @@ pub struct UserEdge {
     pub node2: usize,
     pub observable_indices: Vec<usize>,
     pub weight: f64,
+    /// Tie-breaking weight, only consulted between edges whose primary
+    /// weights discretize equally. Must be non-negative; zero by default.
+    pub secondary_weight: f64,
     pub error_probability: f64,
 }
 
+/// How [`UserGraph::handle_dem_instruction`] treats an error on the same
+/// detectors and observables as an edge it already added.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Add every error as its own parallel edge.
+    #[default]
+    Parallel,
+    /// Fold the error into the existing edge, whose probability becomes
+    /// that of an odd number of the merged errors occurring:
+    /// `p1 * (1 - p2) + p2 * (1 - p1)`.
+    Independent,
+}
+
+/// The two ways a graph can mark the boundary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundaryEncoding {
+    /// Boundary edges end at `usize::MAX`, as built by `add_boundary_edge`
+    /// and single-detector DEM errors.
+    Virtual,
+    /// Boundary edges end at nodes listed by `set_boundary`.
+    Nodes,
+}
+
 /// Placeholder for per-node metadata.
 #[derive(Debug, Clone, Default)]
 pub struct UserNode {
     pub is_boundary: bool,
+    /// Coordinates from the DEM's `detector(...)` instruction, if any.
+    pub coordinates: Vec<f64>,
 }
 
 /// High-level graph that accumulates edges from user / DEM input and
@@ -33,8 +84,37 @@ pub struct UserGraph {
     pub edges: Vec<UserEdge>,
     pub boundary_nodes: HashSet<usize>,
     pub num_observables: usize,
+    /// Faults that occur in every shot, as `(detectors, observables)`.
+    certain_faults: Vec<(Vec<usize>, Vec<usize>)>,
     mwpm: Option<Mwpm>,
+    search_flooder: Option<SearchFlooder>,
+    fault_ids: HashMap<(usize, usize), Vec<usize>>,
+    /// Usable negative-weight edges, which every correction starts from.
+    /// Built with `fault_ids`.
+    negative_fault_ids: Vec<usize>,
+    tie_break_seed: Option<u64>,
+    strict_isolated_detectors: bool,
+    reset_time_per_decode: bool,
+    detector_observables: Vec<(usize, HashSet<usize>)>,
     all_edges_have_error_probabilities: bool,
+    require_valid_probabilities: bool,
+    boundary_weight_multiplier: f64,
+    /// Weight levels used when discretizing; see
+    /// [`UserGraph::set_num_distinct_weights`].
+    num_distinct_weights: Weight,
+    merge_strategy: MergeStrategy,
+    /// Edge added by `handle_dem_instruction` for each (endpoints,
+    /// observables) key, consulted by [`MergeStrategy::Independent`].
+    dem_edge_index: HashMap<(usize, usize, Vec<usize>), usize>,
+    /// Endpoint pairs, keyed as in `fault_key`, whose edges the decoder
+    /// may not use.
+    forbidden_edges: HashSet<(usize, usize)>,
+    fingerprint: u64,
+    in_batch: bool,
+    /// The graph changed during the current batch, so the cached solver no
+    /// longer matches it.
+    solver_stale: bool,
+    pub(crate) logical_weight_cache: Mutex<LogicalWeightCache>,
 }
 
 impl UserGraph {
@@ -44,9 +124,191 @@ impl UserGraph {
             edges: Vec::new(),
             boundary_nodes: HashSet::new(),
             num_observables: 0,
+            certain_faults: Vec::new(),
             mwpm: None,
+            search_flooder: None,
+            fault_ids: HashMap::new(),
+            negative_fault_ids: Vec::new(),
+            tie_break_seed: None,
+            strict_isolated_detectors: false,
+            reset_time_per_decode: true,
+            detector_observables: Vec::new(),
             all_edges_have_error_probabilities: true,
+            require_valid_probabilities: false,
+            boundary_weight_multiplier: 1.0,
+            num_distinct_weights: NUM_DISTINCT_WEIGHTS,
+            merge_strategy: MergeStrategy::default(),
+            dem_edge_index: HashMap::new(),
+            forbidden_edges: HashSet::new(),
+            fingerprint: next_fingerprint(),
+            in_batch: false,
+            solver_stale: false,
+            logical_weight_cache: Mutex::new(LogicalWeightCache::default()),
+        }
+    }
+
+    /// Identifier of the graph's current contents. A fresh value is drawn
+    /// whenever the graph changes through its methods, so equal
+    /// fingerprints mean an identical graph. Direct edits to the public
+    /// fields are not tracked.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Drop the cached solver state after the graph changes. Inside a batch
+    /// the solver is only marked stale, to be dropped when the batch ends.
+    fn invalidate_caches(&mut self) {
+        self.fingerprint = next_fingerprint();
+        self.logical_weight_cache.get_mut().unwrap().clear();
+        if self.in_batch {
+            self.solver_stale = true;
+        } else {
+            self.drop_solver();
+        }
+    }
+
+    fn drop_solver(&mut self) {
+        self.mwpm = None;
+        self.search_flooder = None;
+        self.fault_ids.clear();
+        self.negative_fault_ids.clear();
+        self.solver_stale = false;
+    }
+
+    /// Start a batch of changes: until [`UserGraph::end_batch`] or
+    /// [`UserGraph::commit`], changes keep the cached solver instead of
+    /// dropping it, so decoding inside the batch still sees the graph as it
+    /// was when the batch began.
+    ///
+    /// The matcher and the path-search state are held back together: if
+    /// only one of them is cached, the other is built now, and inside the
+    /// batch a missing one is always built along with the other. Anything
+    /// that builds its own solver from the graph, such as
+    /// [`UserGraph::to_mwpm`], sees the changes at once.
+    pub fn begin_batch(&mut self) {
+        if self.mwpm.is_some() && self.search_flooder.is_none() {
+            self.build_search_flooder();
+        } else if self.mwpm.is_none() && self.search_flooder.is_some() {
+            self.mwpm = self.try_to_mwpm().ok();
+        }
+        self.in_batch = true;
+    }
+
+    /// Whether the graph changed during the current batch, so the cached
+    /// solver describes the graph from before it.
+    pub fn has_pending_changes(&self) -> bool {
+        self.solver_stale
+    }
+
+    /// End a batch, dropping the cached solver if the graph changed during
+    /// it. The new solver is built lazily, on the next decode.
+    pub fn end_batch(&mut self) {
+        self.in_batch = false;
+        if self.solver_stale {
+            self.drop_solver();
+        }
+    }
+
+    /// End any batch and build the solver and path-search state now, so the
+    /// cost is paid here rather than by the next decode. Returns whether
+    /// anything had to be built; `false` means nothing changed since the
+    /// last build.
+    ///
+    /// Panics if the solver cannot be built; see [`UserGraph::try_commit`].
+    pub fn commit(&mut self) -> bool {
+        self.try_commit().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like [`UserGraph::commit`], but report why the solver cannot be
+    /// built, as [`UserGraph::try_to_mwpm`] does. Once this succeeds,
+    /// decoding cannot fail on the graph itself until it changes again.
+    pub fn try_commit(&mut self) -> Result<bool, MatchingError> {
+        self.end_batch();
+        let rebuild = self.mwpm.is_none() || self.search_flooder.is_none();
+        self.try_get_mwpm()?;
+        self.get_search_flooder();
+        Ok(rebuild)
+    }
+
+    /// Refuse to build a solver while some edge has an error probability
+    /// outside `[0, 1]`, which usually means a weight was passed where a
+    /// probability was expected. Off by default.
+    pub fn set_require_valid_probabilities(&mut self, require: bool) {
+        self.require_valid_probabilities = require;
+        self.invalidate_caches();
+    }
+
+    /// Check that every edge has an error probability in `[0, 1]`, naming
+    /// the first that does not.
+    pub fn check_error_probabilities(&self) -> Result<(), MatchingError> {
+        if self.all_edges_have_error_probabilities {
+            return Ok(());
         }
+        match self
+            .edges
+            .iter()
+            .position(|e| !(0.0..=1.0).contains(&e.error_probability))
+        {
+            Some(i) => Err(MatchingError::InvalidProbability {
+                edge: i,
+                probability: self.edges[i].error_probability,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Approximate bytes held by the graph and its cached solvers, summed
+    /// from `Vec`, set and map capacities. Meant for capacity planning; the
+    /// allocator's own overhead is not included.
+    pub fn memory_footprint(&self) -> usize {
+        let edges: usize = self
+            .edges
+            .iter()
+            .map(|e| e.observable_indices.capacity() * size_of::<usize>())
+            .sum();
+        let detector_observables: usize = self
+            .detector_observables
+            .iter()
+            .map(|(_, d)| d.capacity() * size_of::<usize>())
+            .sum();
+        let fault_ids: usize = self
+            .fault_ids
+            .values()
+            .map(|group| group.capacity() * size_of::<usize>())
+            .sum();
+        let coordinates: usize = self
+            .nodes
+            .iter()
+            .map(|n| n.coordinates.capacity() * size_of::<f64>())
+            .sum();
+        self.nodes.capacity() * size_of::<UserNode>()
+            + coordinates
+            + self.edges.capacity() * size_of::<UserEdge>()
+            + edges
+            + self.boundary_nodes.capacity() * size_of::<usize>()
+            + self.certain_faults.capacity() * size_of::<(Vec<usize>, Vec<usize>)>()
+            + self
+                .certain_faults
+                .iter()
+                .map(|(d, o)| (d.capacity() + o.capacity()) * size_of::<usize>())
+                .sum::<usize>()
+            + self.detector_observables.capacity() * size_of::<(usize, HashSet<usize>)>()
+            + detector_observables
+            + self.fault_ids.capacity() * size_of::<((usize, usize), Vec<usize>)>()
+            + fault_ids
+            + self.negative_fault_ids.capacity() * size_of::<usize>()
+            + self.dem_edge_index.capacity() * size_of::<((usize, usize, Vec<usize>), usize)>()
+            + self
+                .dem_edge_index
+                .keys()
+                .map(|(_, _, obs)| obs.capacity() * size_of::<usize>())
+                .sum::<usize>()
+            + self.forbidden_edges.capacity() * size_of::<(usize, usize)>()
+            + self.mwpm.as_ref().map_or(0, Mwpm::memory_footprint)
+            + self
+                .search_flooder
+                .as_ref()
+                .map_or(0, SearchFlooder::memory_footprint)
     }
 
     /// Ensure `nodes` is large enough to hold index `id`.
@@ -73,6 +335,27 @@ impl UserGraph {
         observables: Vec<usize>,
         weight: f64,
         error_probability: f64,
+    ) {
+        self.add_edge_with_secondary_weight(
+            node1,
+            node2,
+            observables,
+            weight,
+            0.0,
+            error_probability,
+        );
+    }
+
+    /// Add an edge between two detector nodes carrying a secondary weight
+    /// used to break ties between equal primary weights.
+    pub fn add_edge_with_secondary_weight(
+        &mut self,
+        node1: usize,
+        node2: usize,
+        observables: Vec<usize>,
+        weight: f64,
+        secondary_weight: f64,
+        error_probability: f64,
     ) {
         self.ensure_node(node1.max(node2));
         self.update_num_observables(&observables);
@@ -84,9 +367,10 @@ impl UserGraph {
             node2,
             observable_indices: observables,
             weight,
+            secondary_weight,
             error_probability,
         });
-        self.mwpm = None;
+        self.invalidate_caches();
     }
 
     /// Add an edge from a detector node to the boundary.
@@ -97,6 +381,24 @@ impl UserGraph {
         observables: Vec<usize>,
         weight: f64,
         error_probability: f64,
+    ) {
+        self.add_boundary_edge_with_secondary_weight(
+            node,
+            observables,
+            weight,
+            0.0,
+            error_probability,
+        );
+    }
+
+    /// Add a boundary edge carrying a secondary tie-breaking weight.
+    pub fn add_boundary_edge_with_secondary_weight(
+        &mut self,
+        node: usize,
+        observables: Vec<usize>,
+        weight: f64,
+        secondary_weight: f64,
+        error_probability: f64,
     ) {
         self.ensure_node(node);
         self.update_num_observables(&observables);
@@ -108,9 +410,10 @@ impl UserGraph {
             node2: usize::MAX,
             observable_indices: observables,
             weight,
+            secondary_weight,
             error_probability,
         });
-        self.mwpm = None;
+        self.invalidate_caches();
     }
 
     /// Mark a set of nodes as boundary nodes.
@@ -129,7 +432,158 @@ impl UserGraph {
         for &n in &self.boundary_nodes {
             self.nodes[n].is_boundary = true;
         }
-        self.mwpm = None;
+        self.invalidate_caches();
+    }
+
+    /// Define observable `obs` as the parity of `detectors` instead of by
+    /// edge labels. An edge then flips `obs` exactly when one of its
+    /// endpoints is in `detectors`, so the prediction is the parity of the
+    /// correction's effect on those detectors; any `obs` labels on edges are
+    /// ignored. Boundary nodes from `set_boundary` may be included.
+    pub fn set_detector_observable(&mut self, obs: usize, detectors: &[usize]) {
+        let detectors: HashSet<usize> = detectors.iter().copied().collect();
+        if let Some(max) = detectors.iter().copied().max() {
+            self.ensure_node(max);
+        }
+        self.update_num_observables(&[obs]);
+        match self.detector_observables.iter_mut().find(|(o, _)| *o == obs) {
+            Some((_, existing)) => *existing = detectors,
+            None => self.detector_observables.push((obs, detectors)),
+        }
+        self.invalidate_caches();
+    }
+
+    /// Observables flipped by `e`, accounting for detector-defined observables.
+    pub(crate) fn edge_observables<'a>(&self, e: &'a UserEdge) -> Cow<'a, [usize]> {
+        if self.detector_observables.is_empty() {
+            return Cow::Borrowed(&e.observable_indices);
+        }
+        let mut observables: Vec<usize> = e
+            .observable_indices
+            .iter()
+            .copied()
+            .filter(|obs| !self.detector_observables.iter().any(|(o, _)| o == obs))
+            .collect();
+        for (obs, detectors) in &self.detector_observables {
+            if detectors.contains(&e.node1) != detectors.contains(&e.node2) {
+                observables.push(*obs);
+            }
+        }
+        Cow::Owned(observables)
+    }
+
+    /// Rewrite the graph to mark the boundary only with `prefer`, keeping
+    /// decoding results, edge indices and observables unchanged.
+    ///
+    /// For [`BoundaryEncoding::Virtual`] every edge endpoint at a
+    /// `set_boundary` node moves to `usize::MAX` and the boundary set is
+    /// cleared; the former boundary nodes stay as detectors without edges,
+    /// so detection events there are still ignored, but count as isolated
+    /// (or are rejected under `set_strict_isolated_detectors`). This fails when
+    /// a detector-defined observable contains some boundary nodes but not
+    /// all (counting an existing virtual boundary), since a single virtual
+    /// boundary cannot tell them apart.
+    ///
+    /// For [`BoundaryEncoding::Nodes`] every `usize::MAX` endpoint moves to
+    /// one boundary node: an existing one that belongs to the same
+    /// detector-defined observables as the virtual boundary, or else a new
+    /// node appended to the graph.
+    pub fn normalize_boundary_encoding(
+        &mut self,
+        prefer: BoundaryEncoding,
+    ) -> Result<(), MatchingError> {
+        match prefer {
+            BoundaryEncoding::Virtual => {
+                if self.boundary_nodes.is_empty() {
+                    return Ok(());
+                }
+                let has_virtual = self
+                    .edges
+                    .iter()
+                    .any(|e| e.node1 == usize::MAX || e.node2 == usize::MAX);
+                for (obs, detectors) in &self.detector_observables {
+                    let mut memberships = self.boundary_nodes.iter().map(|n| detectors.contains(n));
+                    let first = memberships.next().unwrap();
+                    if memberships.any(|m| m != first)
+                        || (has_virtual && first != detectors.contains(&usize::MAX))
+                    {
+                        return Err(MatchingError::InvalidGraph(format!(
+                            "observable {obs} is defined by some boundary nodes but not all, \
+                             so the boundary cannot be merged into one"
+                        )));
+                    }
+                }
+                for (_, detectors) in &mut self.detector_observables {
+                    if self.boundary_nodes.iter().any(|n| detectors.contains(n)) {
+                        detectors.insert(usize::MAX);
+                    }
+                }
+                let boundary_nodes = std::mem::take(&mut self.boundary_nodes);
+                for e in &mut self.edges {
+                    for n in [&mut e.node1, &mut e.node2] {
+                        if boundary_nodes.contains(n) {
+                            *n = usize::MAX;
+                        }
+                    }
+                }
+                for &n in &boundary_nodes {
+                    self.nodes[n].is_boundary = false;
+                }
+                for (_, detectors) in &mut self.detector_observables {
+                    detectors.retain(|n| !boundary_nodes.contains(n));
+                }
+            }
+            BoundaryEncoding::Nodes => {
+                if !self.edges.iter().any(|e| e.node1 == usize::MAX || e.node2 == usize::MAX) {
+                    return Ok(());
+                }
+                let existing = self
+                    .boundary_nodes
+                    .iter()
+                    .copied()
+                    .filter(|n| {
+                        self.detector_observables
+                            .iter()
+                            .all(|(_, d)| d.contains(n) == d.contains(&usize::MAX))
+                    })
+                    .min();
+                let boundary = existing.unwrap_or(self.nodes.len());
+                for e in &mut self.edges {
+                    for n in [&mut e.node1, &mut e.node2] {
+                        if *n == usize::MAX {
+                            *n = boundary;
+                        }
+                    }
+                }
+                for (_, detectors) in &mut self.detector_observables {
+                    if detectors.remove(&usize::MAX) {
+                        detectors.insert(boundary);
+                    }
+                }
+                self.ensure_node(boundary);
+                self.nodes[boundary].is_boundary = true;
+                self.boundary_nodes.insert(boundary);
+            }
+        }
+        self.invalidate_caches();
+        Ok(())
+    }
+
+    /// Observables flipped by a fault on `detectors` labelled with
+    /// `observables`, accounting for detector-defined observables as in
+    /// `edge_observables`.
+    fn fault_observables(&self, detectors: &[usize], observables: &[usize]) -> Vec<usize> {
+        let mut flipped: Vec<usize> = observables
+            .iter()
+            .copied()
+            .filter(|obs| !self.detector_observables.iter().any(|(o, _)| o == obs))
+            .collect();
+        for (obs, members) in &self.detector_observables {
+            if detectors.iter().filter(|d| members.contains(d)).count() % 2 == 1 {
+                flipped.push(*obs);
+            }
+        }
+        flipped
     }
 
     /// Whether a node index represents a boundary node.
@@ -138,11 +592,128 @@ impl UserGraph {
             || (node_id < self.nodes.len() && self.nodes[node_id].is_boundary)
     }
 
+    /// Multiply by `factor` the weight of every edge between two detectors
+    /// whose coordinates differ along `axis`, e.g. the time axis to reweight
+    /// time-like edges against space-like ones. Boundary edges are left
+    /// alone. Returns the number of edges scaled.
+    ///
+    /// Fails without changing anything if an edge between two detectors
+    /// has an endpoint with no coordinate along `axis`.
+    pub fn scale_weights_by_coordinate(
+        &mut self,
+        axis: usize,
+        factor: f64,
+    ) -> Result<usize, MatchingError> {
+        let mut scaled = Vec::new();
+        for (i, e) in self.edges.iter().enumerate() {
+            if self.is_boundary_node(e.node1) || self.is_boundary_node(e.node2) {
+                continue;
+            }
+            let coordinate = |n: usize| {
+                self.nodes[n].coordinates.get(axis).copied().ok_or_else(|| {
+                    MatchingError::InvalidGraph(format!(
+                        "edge {i}: detector {n} has no coordinate along axis {axis}"
+                    ))
+                })
+            };
+            if coordinate(e.node1)? != coordinate(e.node2)? {
+                scaled.push(i);
+            }
+        }
+        for &i in &scaled {
+            self.edges[i].weight *= factor;
+        }
+        self.invalidate_caches();
+        Ok(scaled.len())
+    }
+
+    /// Scale the weight of every edge with exactly one boundary endpoint by
+    /// `multiplier` when discretizing. Values above 1 make the decoder prefer
+    /// matching detection events to each other over the boundary. The
+    /// stored edge weights are left unchanged.
+    pub fn set_boundary_weight_multiplier(&mut self, multiplier: f64) {
+        self.boundary_weight_multiplier = multiplier;
+        self.invalidate_caches();
+    }
+
+    /// Number of weight levels used when discretizing edge weights for
+    /// decoding, [`NUM_DISTINCT_WEIGHTS`] by default. Fewer levels lose
+    /// resolution between weights but shrink the flooder's region radii,
+    /// so graphs whose regions would outgrow the event queue's horizon
+    /// (see [`UserGraph::check_weight_horizon`]) can still be decoded.
+    ///
+    /// Panics unless `2^(SECONDARY_WEIGHT_BITS + 1) <= n <= NUM_DISTINCT_WEIGHTS`.
+    pub fn set_num_distinct_weights(&mut self, n: Weight) {
+        let min = 2 << SECONDARY_WEIGHT_BITS;
+        assert!(
+            (min..=NUM_DISTINCT_WEIGHTS).contains(&n),
+            "num_distinct_weights must lie in [{min}, {NUM_DISTINCT_WEIGHTS}], got {n}"
+        );
+        self.num_distinct_weights = n;
+        self.invalidate_caches();
+    }
+
+    /// Number of weight levels used when discretizing; see
+    /// [`UserGraph::set_num_distinct_weights`].
+    pub fn num_distinct_weights(&self) -> Weight {
+        self.num_distinct_weights
+    }
+
+    /// Stop the decoder from using the edges between detectors `n1` and
+    /// `n2`, or between `n1` and the boundary when `n2` is `None`, as if
+    /// their weight were infinite. Meant for faults known not to have
+    /// occurred, e.g. from heralded information. Parallel edges are
+    /// forbidden together. Only decoding is affected: the edges keep their
+    /// fault ids, are still sampled by [`UserGraph::add_noise`] and still
+    /// count toward [`UserGraph::min_logical_weight`].
+    pub fn forbid_edge(&mut self, n1: usize, n2: Option<usize>) {
+        let key = match n2 {
+            Some(n2) if !self.is_boundary_node(n2) => (n1.min(n2), n1.max(n2)),
+            _ => (n1, usize::MAX),
+        };
+        if self.forbidden_edges.insert(key) {
+            self.invalidate_caches();
+        }
+    }
+
+    /// Allow the decoder to use every edge again.
+    pub fn clear_forbidden_edges(&mut self) {
+        if !self.forbidden_edges.is_empty() {
+            self.forbidden_edges.clear();
+            self.invalidate_caches();
+        }
+    }
+
+    /// Whether `e` was excluded by [`UserGraph::forbid_edge`].
+    pub(crate) fn is_forbidden(&self, e: &UserEdge) -> bool {
+        !self.forbidden_edges.is_empty()
+            && self.fault_key(e).is_some_and(|key| self.forbidden_edges.contains(&key))
+    }
+
+    /// Edges the decoder may use: all but the forbidden ones.
+    pub(crate) fn usable_edges(&self) -> impl Iterator<Item = &UserEdge> {
+        self.edges.iter().filter(|e| !self.is_forbidden(e))
+    }
+
+    /// Weight of `e` as the decoder sees it, after the boundary multiplier.
+    pub(crate) fn scaled_weight(&self, e: &UserEdge) -> f64 {
+        if self.is_boundary_node(e.node1) != self.is_boundary_node(e.node2) {
+            e.weight * self.boundary_weight_multiplier
+        } else {
+            e.weight
+        }
+    }
+
+    /// Discretize `e`'s scaled weight with `discretization`.
+    fn discretize(&self, discretization: &WeightDiscretization, e: &UserEdge) -> SignedWeight {
+        discretization.discretize(self.scaled_weight(e), e.secondary_weight)
+    }
+
     /// Maximum absolute weight across all edges.
     fn max_abs_weight(&self) -> f64 {
         self.edges
             .iter()
-            .map(|e| e.weight.abs())
+            .map(|e| self.scaled_weight(e).abs())
             .fold(0.0f64, f64::max)
     }
 
@@ -158,7 +729,7 @@ impl UserGraph {
         let all_integral = self
             .edges
             .iter()
-            .all(|e| e.weight.round() == e.weight);
+            .all(|e| self.scaled_weight(e).round() == self.scaled_weight(e));
         if all_integral {
             1.0
         } else {
@@ -167,8 +738,182 @@ impl UserGraph {
         }
     }
 
+    /// Choose how user weights map onto integer weights.
+    ///
+    /// Without secondary weights this is just the normalising constant. With
+    /// them, the primary weight is quantized to `num_distinct_weights >>
+    /// SECONDARY_WEIGHT_BITS` levels and shifted up to make room for the
+    /// secondary weight in the low bits.
+    fn weight_discretization(&self, num_distinct_weights: Weight) -> WeightDiscretization {
+        let max_secondary = self
+            .edges
+            .iter()
+            .map(|e| e.secondary_weight.abs())
+            .fold(0.0f64, f64::max);
+        if max_secondary == 0.0 {
+            return WeightDiscretization {
+                norm: self.get_edge_weight_normalising_constant(num_distinct_weights),
+                secondary_norm: 0.0,
+                secondary_shift: 0,
+            };
+        }
+
+        let secondary_levels = (1u32 << SECONDARY_WEIGHT_BITS) - 1;
+        let secondary_integral = self
+            .edges
+            .iter()
+            .all(|e| e.secondary_weight.round() == e.secondary_weight);
+        let secondary_norm = if secondary_integral && max_secondary <= secondary_levels as f64 {
+            1.0
+        } else {
+            secondary_levels as f64 / max_secondary
+        };
+        // Integral primaries are only kept as-is when they still fit in the
+        // reduced number of levels; otherwise rescale as for fractional ones.
+        let primary_levels = num_distinct_weights >> SECONDARY_WEIGHT_BITS;
+        let max_primary = (primary_levels - 1) as f64;
+        let mut norm = self.get_edge_weight_normalising_constant(primary_levels);
+        if self.max_abs_weight() * norm > max_primary {
+            norm = max_primary / self.max_abs_weight();
+        }
+        WeightDiscretization {
+            norm,
+            secondary_norm,
+            secondary_shift: SECONDARY_WEIGHT_BITS,
+        }
+    }
+
+    /// Indices of edges with a nonzero weight that discretizes to zero, i.e.
+    /// edges the decoder can only treat as having the minimum weight because
+    /// the weight range exceeds what `num_distinct_weights` levels resolve.
+    pub fn weight_resolution_warnings(&self, num_distinct_weights: Weight) -> Vec<usize> {
+        let discretization = self.weight_discretization(num_distinct_weights);
+        self.edges
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| {
+                e.weight != 0.0
+                    && discretization.rounds_to_zero(self.scaled_weight(e), e.secondary_weight)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Indices of edges joining two boundary nodes that flip an observable.
+    /// No detector ever sees such an error, so it is an undetectable logical
+    /// error: with positive weight the decoder never predicts it, and with
+    /// negative weight it is always part of the correction.
+    pub fn boundary_to_boundary_observable_edges(&self) -> Vec<usize> {
+        self.edges
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| self.is_boundary_node(e.node1) && self.is_boundary_node(e.node2))
+            .filter(|(_, e)| !self.edge_observables(e).is_empty())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Detectors with at least one edge to the boundary, either a boundary
+    /// edge or an edge to a node from `set_boundary`, in increasing order.
+    pub fn boundary_detectors(&self) -> Vec<usize> {
+        let mut detectors: Vec<usize> = self
+            .edges
+            .iter()
+            .filter_map(|e| match (self.is_boundary_node(e.node1), self.is_boundary_node(e.node2)) {
+                (false, true) => Some(e.node1),
+                (true, false) => Some(e.node2),
+                _ => None,
+            })
+            .collect();
+        detectors.sort_unstable();
+        detectors.dedup();
+        detectors
+    }
+
+    /// Observables that some edge or certain fault flips, in increasing
+    /// order.
+    pub fn flipped_observables(&self) -> Vec<usize> {
+        let mut observables: Vec<usize> = self
+            .edges
+            .iter()
+            .flat_map(|e| self.edge_observables(e).into_owned())
+            .chain(
+                self.certain_faults
+                    .iter()
+                    .flat_map(|(detectors, obs)| self.fault_observables(detectors, obs)),
+            )
+            .collect();
+        observables.sort_unstable();
+        observables.dedup();
+        observables
+    }
+
+    /// Sample one shot of the error model: each edge fails independently
+    /// with its error probability, and every certain fault always does.
+    /// Returns the detectors and observables the failures flip, one entry
+    /// per node and per observable. `uniform` must return numbers drawn
+    /// uniformly from `[0, 1)`.
+    ///
+    /// Fails if some edge has no error probability in `[0, 1]`.
+    pub fn add_noise(
+        &self,
+        uniform: &mut impl FnMut() -> f64,
+    ) -> Result<(Vec<u8>, Vec<u8>), MatchingError> {
+        self.check_error_probabilities()?;
+        let mut syndrome = vec![0u8; self.nodes.len()];
+        let mut observables = vec![0u8; self.num_observables];
+        let mut flip = |detectors: &[usize], flipped: &[usize]| {
+            for &d in detectors {
+                if !self.is_boundary_node(d) {
+                    syndrome[d] ^= 1;
+                }
+            }
+            for &obs in flipped {
+                observables[obs] ^= 1;
+            }
+        };
+        for e in &self.edges {
+            if uniform() < e.error_probability {
+                flip(&[e.node1, e.node2], &self.edge_observables(e));
+            }
+        }
+        for (detectors, observables) in &self.certain_faults {
+            flip(detectors, &self.fault_observables(detectors, observables));
+        }
+        Ok((syndrome, observables))
+    }
+
+    /// Check that a correction explains `syndrome`: flipping the detectors
+    /// of each fault in `correction_faults` (edge indices, as returned by
+    /// `Matching::decode_to_fault_ids`) together with those of every
+    /// certain fault must reproduce it exactly. Boundary nodes are ignored,
+    /// as are detectors past the end of `syndrome`, which must then not be
+    /// flipped. Returns `false` for an unknown fault id.
+    pub fn verify_correction(&self, syndrome: &[u8], correction_faults: &[usize]) -> bool {
+        let mut flipped = vec![0u8; self.nodes.len().max(syndrome.len())];
+        let mut flip = |n: usize| {
+            if !self.is_boundary_node(n) {
+                flipped[n] ^= 1;
+            }
+        };
+        for &id in correction_faults {
+            let Some(e) = self.edges.get(id) else {
+                return false;
+            };
+            flip(e.node1);
+            flip(e.node2);
+        }
+        for (detectors, _) in &self.certain_faults {
+            detectors.iter().for_each(|&d| flip(d));
+        }
+        flipped
+            .iter()
+            .enumerate()
+            .all(|(i, &f)| (f != 0) == syndrome.get(i).is_some_and(|&s| s != 0))
+    }
+
     /// Convert observable indices to a bitmask.
-    fn obs_mask(observables: &[usize]) -> ObsMask {
+    pub(crate) fn obs_mask(observables: &[usize]) -> ObsMask {
         let mut mask: ObsMask = 0;
         for &obs in observables {
             mask ^= 1u64 << obs;
@@ -183,7 +928,7 @@ impl UserGraph {
     ) -> MatchingGraph {
         let mut mg =
             MatchingGraph::new(self.nodes.len(), self.num_observables);
-        let norm = self.get_edge_weight_normalising_constant(num_distinct_weights);
+        let discretization = self.weight_discretization(num_distinct_weights);
 
         // Collect boundary edges per node, keeping only the smallest signed weight
         // (matches PyMatching's parallel boundary edge deduplication).
@@ -192,25 +937,28 @@ impl UserGraph {
         let mut boundary_edge_weights: Vec<SignedWeight> = vec![0; num_nodes];
         let mut boundary_edge_observables: Vec<Vec<usize>> = vec![Vec::new(); num_nodes];
 
-        for e in &self.edges {
-            let w = (e.weight * norm).round() as SignedWeight * 2;
+        for e in self.usable_edges() {
+            let w = self.discretize(&discretization, e);
+            let observables = self.edge_observables(e);
             let n1_boundary = self.is_boundary_node(e.node1);
             let n2_boundary = self.is_boundary_node(e.node2);
 
             if n2_boundary && !n1_boundary {
                 if !has_boundary_edge[e.node1] || boundary_edge_weights[e.node1] > w {
                     boundary_edge_weights[e.node1] = w;
-                    boundary_edge_observables[e.node1] = e.observable_indices.clone();
+                    boundary_edge_observables[e.node1] = observables.to_vec();
                     has_boundary_edge[e.node1] = true;
                 }
             } else if n1_boundary && !n2_boundary {
                 if !has_boundary_edge[e.node2] || boundary_edge_weights[e.node2] > w {
                     boundary_edge_weights[e.node2] = w;
-                    boundary_edge_observables[e.node2] = e.observable_indices.clone();
+                    boundary_edge_observables[e.node2] = observables.to_vec();
                     has_boundary_edge[e.node2] = true;
                 }
             } else if !n1_boundary {
-                mg.add_edge(e.node1, e.node2, w, &e.observable_indices);
+                mg.add_edge(e.node1, e.node2, w, &observables);
+            } else {
+                mg.add_boundary_to_boundary_edge(w, &observables);
             }
         }
 
@@ -221,7 +969,19 @@ impl UserGraph {
             }
         }
 
-        mg.normalising_constant = norm * 2.0;
+        for (detectors, observables) in &self.certain_faults {
+            // Observables come from the full detector list: a boundary node
+            // can belong to a detector-defined observable, as for edges.
+            let flipped = self.fault_observables(detectors, observables);
+            let detectors: Vec<usize> = detectors
+                .iter()
+                .copied()
+                .filter(|&d| !self.is_boundary_node(d))
+                .collect();
+            mg.add_certain_fault(&detectors, &flipped);
+        }
+
+        mg.normalising_constant = discretization.normalising_constant();
 
         if !self.boundary_nodes.is_empty() {
             mg.is_user_graph_boundary_node = vec![false; self.nodes.len()];
@@ -229,6 +989,7 @@ impl UserGraph {
                 mg.is_user_graph_boundary_node[i] = true;
             }
         }
+        mg.compute_boundaryless_components();
 
         mg
     }
@@ -240,7 +1001,7 @@ impl UserGraph {
     ) -> SearchGraph {
         let mut sg =
             SearchGraph::new(self.nodes.len(), self.num_observables);
-        let norm = self.get_edge_weight_normalising_constant(num_distinct_weights);
+        let discretization = self.weight_discretization(num_distinct_weights);
 
         // Collect boundary edges per node, keeping only the smallest signed weight
         let num_nodes = self.nodes.len();
@@ -248,9 +1009,9 @@ impl UserGraph {
         let mut boundary_edge_weights: Vec<SignedWeight> = vec![0; num_nodes];
         let mut boundary_edge_obs: Vec<ObsMask> = vec![0; num_nodes];
 
-        for e in &self.edges {
-            let w_signed = (e.weight * norm).round() as SignedWeight * 2;
-            let obs = Self::obs_mask(&e.observable_indices);
+        for e in self.usable_edges() {
+            let w_signed = self.discretize(&discretization, e);
+            let obs = Self::obs_mask(&self.edge_observables(e));
             let n1_boundary = self.is_boundary_node(e.node1);
             let n2_boundary = self.is_boundary_node(e.node2);
 
@@ -284,30 +1045,365 @@ impl UserGraph {
     }
 
     /// Build a full `Mwpm` solver from the current graph.
+    ///
+    /// Panics if the graph fails [`UserGraph::check_weight_horizon`], or
+    /// [`UserGraph::check_error_probabilities`] when
+    /// [`UserGraph::set_require_valid_probabilities`] is on; use
+    /// [`UserGraph::try_to_mwpm`] to get the error instead.
     pub fn to_mwpm(&self) -> Mwpm {
-        let mg = self.to_matching_graph(NUM_DISTINCT_WEIGHTS);
-        let flooder = GraphFlooder::new(mg);
-        Mwpm::new(flooder)
+        self.try_to_mwpm().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Build a full `Mwpm` solver, or explain why the flooder cannot
+    /// handle this graph.
+    pub fn try_to_mwpm(&self) -> Result<Mwpm, MatchingError> {
+        if self.require_valid_probabilities {
+            self.check_error_probabilities()?;
+        }
+        self.check_weight_horizon(self.num_distinct_weights)?;
+        let mg = self.to_matching_graph(self.num_distinct_weights);
+        let mut flooder = GraphFlooder::new(mg);
+        flooder.tie_break_seed = self.tie_break_seed;
+        let mut mwpm = Mwpm::new(flooder);
+        mwpm.strict_isolated_detectors = self.strict_isolated_detectors;
+        mwpm.reset_time_per_decode = self.reset_time_per_decode;
+        Ok(mwpm)
+    }
+
+    /// Check that flooding this graph stays within the event queue's
+    /// horizon: queued events must lie less than 2^31 time units ahead of
+    /// the current time, or the 32-bit wrapped times compare wrongly.
+    ///
+    /// The furthest-ahead event is a shrinking region reaching zero radius,
+    /// and a region stops growing once it reaches the boundary, so in a
+    /// component with a boundary no radius exceeds the largest distance
+    /// from a node to the boundary. Without a boundary a region can grow
+    /// across the component, bounded by twice the eccentricity of any of
+    /// its nodes. Regions growing towards each other meet sooner, not
+    /// later, so no further factor is needed.
+    pub fn check_weight_horizon(&self, num_distinct_weights: Weight) -> Result<(), MatchingError> {
+        const HORIZON: i64 = 1 << 31;
+        let max_radius = self.max_region_radius(num_distinct_weights);
+        if max_radius >= HORIZON {
+            return Err(MatchingError::WeightHorizonExceeded {
+                max_radius,
+                num_distinct_weights,
+            });
+        }
+        Ok(())
+    }
+
+    /// Upper bound on any region radius, in discretized units; see
+    /// [`UserGraph::check_weight_horizon`].
+    fn max_region_radius(&self, num_distinct_weights: Weight) -> i64 {
+        use std::cmp::Reverse;
+        use std::collections::BinaryHeap;
+
+        let discretization = self.weight_discretization(num_distinct_weights);
+        let boundary = self.nodes.len();
+        let map = |n: usize| if self.is_boundary_node(n) { boundary } else { n };
+        let mut adjacency: Vec<Vec<(usize, i64)>> = vec![Vec::new(); boundary + 1];
+        for e in self.usable_edges() {
+            let (a, b) = (map(e.node1), map(e.node2));
+            let w = self.discretize(&discretization, e).unsigned_abs() as i64;
+            adjacency[a].push((b, w));
+            adjacency[b].push((a, w));
+        }
+
+        let mut dist = vec![i64::MAX; boundary + 1];
+        let flood = |dist: &mut [i64], source: usize| -> i64 {
+            let mut heap = BinaryHeap::new();
+            dist[source] = 0;
+            heap.push(Reverse((0, source)));
+            let mut furthest = 0;
+            while let Some(Reverse((d, v))) = heap.pop() {
+                if d > dist[v] {
+                    continue;
+                }
+                furthest = d;
+                for &(u, w) in &adjacency[v] {
+                    if d + w < dist[u] {
+                        dist[u] = d + w;
+                        heap.push(Reverse((d + w, u)));
+                    }
+                }
+            }
+            furthest
+        };
+
+        let mut max_radius = flood(&mut dist, boundary);
+        for v in 0..boundary {
+            if dist[v] == i64::MAX {
+                max_radius = max_radius.max(flood(&mut dist, v).saturating_mul(2));
+            }
+        }
+        max_radius
+    }
+
+    /// Seed (or, with `None`, disable) randomized tie-breaking between
+    /// simultaneous flooder events. Applies to the cached solver as well.
+    pub fn set_tie_break_seed(&mut self, seed: Option<u64>) {
+        self.tie_break_seed = seed;
+        self.fingerprint = next_fingerprint();
+        if let Some(mwpm) = self.mwpm.as_mut() {
+            mwpm.flooder.tie_break_seed = seed;
+        }
+    }
+
+    /// Choose whether a detection event at a detector with no edges is an
+    /// error (`true`) or is skipped and counted in
+    /// [`crate::matcher::mwpm::MwpmStats::isolated_detection_events`]
+    /// (`false`, the default). The error is reported by
+    /// [`Matching::try_decode`](crate::Matching::try_decode) before any
+    /// matching starts; the infallible decode methods panic with it.
+    /// Applies to the cached solver as well.
+    pub fn set_strict_isolated_detectors(&mut self, strict: bool) {
+        self.strict_isolated_detectors = strict;
+        self.fingerprint = next_fingerprint();
+        if let Some(mwpm) = self.mwpm.as_mut() {
+            mwpm.strict_isolated_detectors = strict;
+        }
+    }
+
+    /// Choose whether each decode starts at queue time zero (`true`, the
+    /// default) or continues from where the previous decode stopped, for
+    /// online decoders that want one monotonic timeline. See
+    /// [`Mwpm::reset_time_per_decode`]. Applies to the cached solver as well.
+    pub fn set_reset_time_per_decode(&mut self, reset: bool) {
+        self.reset_time_per_decode = reset;
+        self.fingerprint = next_fingerprint();
+        if let Some(mwpm) = self.mwpm.as_mut() {
+            mwpm.reset_time_per_decode = reset;
+        }
     }
 
     /// Lazy-initialise and return a mutable reference to the cached `Mwpm`.
+    ///
+    /// Panics if the solver cannot be built; see [`UserGraph::try_get_mwpm`].
     pub fn get_mwpm(&mut self) -> &mut Mwpm {
+        self.try_get_mwpm().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like [`UserGraph::get_mwpm`], but report why the solver cannot be
+    /// built instead of panicking. Nothing is cached on failure.
+    pub fn try_get_mwpm(&mut self) -> Result<&mut Mwpm, MatchingError> {
         if self.mwpm.is_none() {
-            self.mwpm = Some(self.to_mwpm());
+            self.mwpm = Some(self.try_to_mwpm()?);
+            if self.in_batch {
+                // Keep the path-search state in step with the matcher.
+                self.build_search_flooder();
+            }
+        }
+        Ok(self.mwpm.as_mut().unwrap())
+    }
+
+    /// Lazy-initialise and return a mutable reference to the cached
+    /// `SearchFlooder` used for shortest-path reconstruction.
+    pub fn get_search_flooder(&mut self) -> &mut SearchFlooder {
+        if self.search_flooder.is_none() {
+            self.build_search_flooder();
+            if self.in_batch {
+                // Keep the matcher in step with the path-search state; a
+                // graph it cannot be built from fails to decode anyway.
+                self.mwpm = self.try_to_mwpm().ok();
+            }
         }
-        self.mwpm.as_mut().unwrap()
+        self.search_flooder.as_mut().unwrap()
+    }
+
+    /// Usable negative-weight edges, by fault id, from the same graph as the
+    /// cached path-search state.
+    pub(crate) fn negative_fault_ids(&mut self) -> &[usize] {
+        self.get_search_flooder();
+        &self.negative_fault_ids
+    }
+
+    fn build_search_flooder(&mut self) {
+        let num_distinct_weights = self.num_distinct_weights;
+        self.search_flooder = Some(SearchFlooder::new(self.to_search_graph(num_distinct_weights)));
+        self.fault_ids = self.fault_id_lookup(num_distinct_weights);
+        self.negative_fault_ids = self
+            .edges
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.weight < 0.0 && !self.is_forbidden(e))
+            .map(|(i, _)| i)
+            .collect();
+    }
+
+    /// Search-graph key of an edge: `(min, max)` endpoint, with `usize::MAX`
+    /// for the boundary. `None` for edges joining two boundary nodes, which
+    /// no path uses.
+    fn fault_key(&self, e: &UserEdge) -> Option<(usize, usize)> {
+        match (self.is_boundary_node(e.node1), self.is_boundary_node(e.node2)) {
+            (true, true) => None,
+            (false, true) => Some((e.node1, usize::MAX)),
+            (true, false) => Some((e.node2, usize::MAX)),
+            (false, false) => Some((e.node1.min(e.node2), e.node1.max(e.node2))),
+        }
+    }
+
+    /// Map each search-graph edge, keyed as in `fault_key`, to the indices in
+    /// `edges` of all the parallel edges it stands for. The first index is
+    /// the representative: the lightest edge, earliest first, which for
+    /// boundary edges is the one `to_search_graph` keeps. The rest follow in
+    /// index order.
+    fn fault_id_lookup(&self, num_distinct_weights: Weight) -> HashMap<(usize, usize), Vec<usize>> {
+        let discretization = self.weight_discretization(num_distinct_weights);
+        let mut lookup: HashMap<(usize, usize), (SignedWeight, Vec<usize>)> = HashMap::new();
+        for (i, e) in self.edges.iter().enumerate() {
+            let Some(key) = self.fault_key(e) else {
+                continue;
+            };
+            let w = if key.1 == usize::MAX {
+                self.discretize(&discretization, e)
+            } else {
+                self.discretize(&discretization, e).abs()
+            };
+            let (best, group) = lookup.entry(key).or_insert((w, Vec::new()));
+            if w < *best {
+                *best = w;
+                group.insert(0, i);
+            } else {
+                group.push(i);
+            }
+        }
+        lookup
+            .into_iter()
+            .map(|(key, (_, mut group))| {
+                group[1..].sort_unstable();
+                (key, group)
+            })
+            .collect()
+    }
+
+    /// Every fault id whose edge is parallel to `fault_id`'s (including
+    /// `fault_id` itself), representative first as in the ids reported by
+    /// [`UserGraph::shortest_path_fault_ids`].
+    pub fn fault_id_group(&mut self, fault_id: usize) -> Vec<usize> {
+        self.get_search_flooder();
+        match self.fault_key(&self.edges[fault_id]) {
+            Some(key) => self.fault_ids[&key].clone(),
+            None => vec![fault_id],
+        }
+    }
+
+    /// Append the fault ids (indices into `edges`) along a shortest path
+    /// from detector `src` to `dst`, or to the boundary when `dst` is `None`.
+    pub fn shortest_path_fault_ids(&mut self, src: usize, dst: Option<usize>, out: &mut Vec<usize>) {
+        self.get_search_flooder();
+        let fault_ids = &self.fault_ids;
+        let search_flooder = self.search_flooder.as_mut().unwrap();
+        search_flooder.iter_edges_on_shortest_path(src, dst, |from, to, _| {
+            let (a, b) = match (from, to) {
+                (Some(a), Some(b)) => (a.0 as usize, b.0 as usize),
+                (Some(n), None) | (None, Some(n)) => (n.0 as usize, usize::MAX),
+                (None, None) => return,
+            };
+            out.push(fault_ids[&(a.min(b), a.max(b))][0]);
+        })
+        // `to_search_graph` adds every edge through `SearchGraph::add_edge`,
+        // which lists it in both directions, so no reverse edge is missing.
+        .expect("search graphs built from a UserGraph list every edge both ways");
+    }
+
+    /// Like [`UserGraph::shortest_path_fault_ids`], for a match whose path
+    /// has length `distance` in discretized units. When a single edge of
+    /// that length joins `src` and `dst`, it is a shortest path and its
+    /// fault id is appended without running a search.
+    pub fn match_path_fault_ids(
+        &mut self,
+        src: usize,
+        dst: Option<usize>,
+        distance: TotalWeight,
+        out: &mut Vec<usize>,
+    ) {
+        let node = &self.get_search_flooder().graph.nodes[src];
+        let target = dst.map(|d| SearchNodeIdx(d as u32));
+        let direct = node
+            .neighbors
+            .iter()
+            .zip(&node.neighbor_weights)
+            .filter(|&(n, _)| *n == target)
+            .map(|(_, &w)| w)
+            .min();
+        if direct.is_some_and(|w| w as TotalWeight == distance) {
+            let key = match dst {
+                Some(d) => (src.min(d), src.max(d)),
+                None => (src, usize::MAX),
+            };
+            out.push(self.fault_ids[&key][0]);
+            return;
+        }
+        self.shortest_path_fault_ids(src, dst, out);
+    }
+
+    /// Choose how later DEM instructions on an existing edge are combined.
+    pub fn set_merge_strategy(&mut self, strategy: MergeStrategy) {
+        self.merge_strategy = strategy;
+    }
+
+    /// How DEM instructions on an existing edge are combined.
+    pub fn merge_strategy(&self) -> MergeStrategy {
+        self.merge_strategy
+    }
+
+    /// Record a fault that occurs in every shot: each decode flips
+    /// `detectors` before matching and `observables` in the prediction, as
+    /// a fixed part of the correction. It is not an edge, so it has no
+    /// fault id and the matching cannot undo it. Any number of detectors,
+    /// including none, is allowed.
+    pub fn add_certain_fault(&mut self, detectors: &[usize], observables: Vec<usize>) {
+        if let Some(&max) = detectors.iter().max() {
+            self.ensure_node(max);
+        }
+        self.update_num_observables(&observables);
+        self.certain_faults.push((detectors.to_vec(), observables));
+        self.invalidate_caches();
     }
 
     /// Handle a detector-error-model instruction.
     ///
     /// Converts probability `p` to weight `ln((1-p)/p)` and adds the
-    /// appropriate edge.
+    /// appropriate edge, or merges it into an earlier one under
+    /// [`MergeStrategy::Independent`]. An error with `p <= 0` never occurs
+    /// and is dropped, though its observables still count. One with
+    /// `p >= 1` always occurs and is added with
+    /// [`UserGraph::add_certain_fault`].
     pub fn handle_dem_instruction(
         &mut self,
         p: f64,
         detectors: &[usize],
         observables: Vec<usize>,
     ) {
+        if p <= 0.0 {
+            self.update_num_observables(&observables);
+            return;
+        }
+        if p >= 1.0 {
+            self.add_certain_fault(detectors, observables);
+            return;
+        }
+        if self.merge_strategy == MergeStrategy::Independent && matches!(detectors.len(), 1 | 2) {
+            let (a, b) = match *detectors {
+                [a] => (a, usize::MAX),
+                [a, b] => (a.min(b), a.max(b)),
+                _ => unreachable!(),
+            };
+            let mut key_observables = observables.clone();
+            key_observables.sort_unstable();
+            let key = (a, b, key_observables);
+            if let Some(&i) = self.dem_edge_index.get(&key) {
+                let e = &mut self.edges[i];
+                let q = e.error_probability;
+                let merged = q * (1.0 - p) + p * (1.0 - q);
+                e.error_probability = merged;
+                e.weight = ((1.0 - merged) / merged).ln();
+                self.invalidate_caches();
+                return;
+            }
+            self.dem_edge_index.insert(key, self.edges.len());
+        }
         let weight = ((1.0 - p) / p).ln();
         match detectors.len() {
             2 => self.add_edge(
@@ -322,6 +1418,42 @@ impl UserGraph {
         }
     }
 
+    /// Reorder `edges` by `(min endpoint, max endpoint, weight, observables,
+    /// secondary weight, error probability)` so that edge indices (fault ids)
+    /// no longer depend on insertion order. Boundary edges sort after all
+    /// internal edges of the same node. Only edges identical in every field
+    /// keep their relative order.
+    ///
+    /// Returns the permutation applied: entry `i` is the old index of the
+    /// edge now stored at index `i`.
+    pub fn canonicalize_edges(&mut self) -> Vec<usize> {
+        let mut permutation: Vec<usize> = (0..self.edges.len()).collect();
+        permutation.sort_by(|&a, &b| {
+            let (ea, eb) = (&self.edges[a], &self.edges[b]);
+            (ea.node1.min(ea.node2), ea.node1.max(ea.node2))
+                .cmp(&(eb.node1.min(eb.node2), eb.node1.max(eb.node2)))
+                .then(ea.weight.total_cmp(&eb.weight))
+                .then_with(|| ea.observable_indices.cmp(&eb.observable_indices))
+                .then(ea.secondary_weight.total_cmp(&eb.secondary_weight))
+                .then(ea.error_probability.total_cmp(&eb.error_probability))
+        });
+        let mut old_edges: Vec<Option<UserEdge>> =
+            std::mem::take(&mut self.edges).into_iter().map(Some).collect();
+        self.edges = permutation
+            .iter()
+            .map(|&old| old_edges[old].take().unwrap())
+            .collect();
+        let mut new_index = vec![0; permutation.len()];
+        for (new, &old) in permutation.iter().enumerate() {
+            new_index[old] = new;
+        }
+        for i in self.dem_edge_index.values_mut() {
+            *i = new_index[*i];
+        }
+        self.invalidate_caches();
+        permutation
+    }
+
     pub fn get_num_edges(&self) -> usize {
         self.edges.len()
     }
@@ -334,3 +1466,52 @@ impl UserGraph {
         self.nodes.len() - self.boundary_nodes.len()
     }
 }
+
+impl Default for UserGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Mapping from floating-point user weights to doubled integer weights.
+struct WeightDiscretization {
+    norm: f64,
+    secondary_norm: f64,
+    secondary_shift: u32,
+}
+
+impl WeightDiscretization {
+    /// Discretize an edge's weight, folding its secondary weight into the
+    /// low-order bits. The result is doubled, as the flooder requires.
+    ///
+    /// Weights that would round to zero are raised to the smallest nonzero
+    /// weight: zero-weight edges let regions collide repeatedly without the
+    /// flooder's clock advancing, and any zero-weight cycle makes the
+    /// matching degenerate.
+    fn discretize(&self, weight: f64, secondary_weight: f64) -> SignedWeight {
+        let primary = (weight * self.norm).round() as SignedWeight;
+        // The secondary weight always adds cost, so it is added after the
+        // sign: a negative edge with a larger secondary weight is less
+        // attractive, not more.
+        let value = if self.secondary_shift == 0 {
+            primary
+        } else {
+            let secondary =
+                (secondary_weight.max(0.0) * self.secondary_norm).round() as SignedWeight;
+            (primary << self.secondary_shift) + secondary
+        };
+        if value == 0 { 2 } else { value * 2 }
+    }
+
+    /// Whether `discretize` had to raise this weight from zero.
+    fn rounds_to_zero(&self, weight: f64, secondary_weight: f64) -> bool {
+        let primary = (weight * self.norm).round();
+        let secondary = (secondary_weight.max(0.0) * self.secondary_norm).round();
+        primary == 0.0 && (self.secondary_shift == 0 || secondary == 0.0)
+    }
+
+    /// Factor converting user weights into the (primary part of) integer weights.
+    fn normalising_constant(&self) -> f64 {
+        self.norm * (1u64 << self.secondary_shift) as f64 * 2.0
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt;
+
+use crate::types::Weight;
+
+/// Why a fallible `Matching` or `UserGraph` operation failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchingError {
+    /// The DEM text is malformed, or uses instructions a strict parse
+    /// rejects.
+    ParseError(String),
+    /// An edge's error probability lies outside `[0, 1]`.
+    InvalidProbability { edge: usize, probability: f64 },
+    /// An edge's weight is not allowed by the operation, e.g. a negative
+    /// weight for `logical_class_weights`.
+    InvalidWeight { edge: usize, weight: f64 },
+    /// The graph has more observables than the operation supports.
+    ObservableOverflow {
+        operation: &'static str,
+        max: usize,
+        num_observables: usize,
+    },
+    /// Flooding the graph could queue events past the 2^31 time horizon.
+    WeightHorizonExceeded {
+        max_radius: i64,
+        num_distinct_weights: Weight,
+    },
+    /// Syndrome input does not have the expected shape.
+    InvalidSyndrome(String),
+    /// The graph's structure does not allow the operation.
+    InvalidGraph(String),
+}
+
+impl fmt::Display for MatchingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchingError::ParseError(msg)
+            | MatchingError::InvalidSyndrome(msg)
+            | MatchingError::InvalidGraph(msg) => f.write_str(msg),
+            MatchingError::InvalidProbability { edge, probability } => write!(
+                f,
+                "edge {edge} has error probability {probability} outside [0, 1]; was a weight \
+                 passed as the probability?"
+            ),
+            MatchingError::InvalidWeight { edge, .. } => write!(f, "edge {edge} has negative weight"),
+            MatchingError::ObservableOverflow {
+                operation,
+                max,
+                num_observables,
+            } => write!(
+                f,
+                "{operation} supports at most {max} observables, graph has {num_observables}"
+            ),
+            MatchingError::WeightHorizonExceeded {
+                max_radius,
+                num_distinct_weights,
+            } => write!(
+                f,
+                "worst-case region radius {max_radius} exceeds the flooder's 2^31 time \
+                 horizon at num_distinct_weights = {num_distinct_weights}; lower it with \
+                 set_num_distinct_weights or use a smaller spread of edge weights"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MatchingError {}
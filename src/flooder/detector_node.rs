@@ -12,7 +12,7 @@ thread_local! {
     static LOCAL_RADIUS_CALLS: Cell<usize> = const { Cell::new(0) };
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct DetectorNode {
     // Permanent (graph structure)
     pub neighbors: Vec<NodeIdx>,
@@ -28,23 +28,6 @@ pub struct DetectorNode {
     pub node_event_tracker: QueuedEventTracker,
 }
 
-impl Default for DetectorNode {
-    fn default() -> Self {
-        DetectorNode {
-            neighbors: Vec::new(),
-            neighbor_weights: Vec::new(),
-            neighbor_observables: Vec::new(),
-            region_that_arrived: None,
-            region_that_arrived_top: None,
-            reached_from_source: None,
-            observables_crossed_from_source: 0,
-            radius_of_arrival: 0,
-            wrapped_radius_cached: 0,
-            node_event_tracker: QueuedEventTracker::default(),
-        }
-    }
-}
-
 impl DetectorNode {
     pub fn new() -> Self {
         Self::default()
@@ -12,10 +12,6 @@ pub struct GraphFillRegion {
     pub match_: Option<Match>,
     pub blossom_children: Vec<RegionEdge>,
     pub shell_area: Vec<NodeIdx>,
-    /// Node anchoring the parent-side edge (set when creating a blossom)
-    pub blossom_in_parent_loc: Option<NodeIdx>,
-    /// Node anchoring the child-side edge (set when creating a blossom)
-    pub blossom_in_child_loc: Option<NodeIdx>,
 }
 
 impl Default for GraphFillRegion {
@@ -29,8 +25,6 @@ impl Default for GraphFillRegion {
             match_: None,
             blossom_children: Vec::new(),
             shell_area: Vec::new(),
-            blossom_in_parent_loc: None,
-            blossom_in_child_loc: None,
         }
     }
 }
@@ -45,8 +39,6 @@ impl GraphFillRegion {
         self.match_ = None;
         self.blossom_children.clear();
         self.shell_area.clear();
-        self.blossom_in_parent_loc = None;
-        self.blossom_in_child_loc = None;
     }
 
     pub fn tree_equal(&self, other: &GraphFillRegion) -> bool {
@@ -6,6 +6,11 @@ use super::detector_node::DetectorNode;
 /// Sentinel NodeIdx for boundary neighbors.
 pub const BOUNDARY_NODE: NodeIdx = NodeIdx(u32::MAX);
 
+/// Entry of `MatchingGraph::boundaryless_component` for a node whose
+/// component reaches the boundary, or that has no edges.
+pub const NO_COMPONENT: u32 = u32::MAX;
+
+#[derive(Debug, Clone)]
 pub struct MatchingGraph {
     pub nodes: Vec<DetectorNode>,
     pub num_observables: usize,
@@ -15,6 +20,13 @@ pub struct MatchingGraph {
     pub negative_weight_sum: TotalWeight,
     pub is_user_graph_boundary_node: Vec<bool>,
     pub normalising_constant: f64,
+    /// Per node, the index of its connected component among those with no
+    /// boundary edge, or [`NO_COMPONENT`]. Empty until
+    /// [`MatchingGraph::compute_boundaryless_components`] runs.
+    pub boundaryless_component: Vec<u32>,
+    /// Scratch parity per boundaryless component, all `false` between calls
+    /// to [`MatchingGraph::odd_boundaryless_component`].
+    component_parity: Vec<bool>,
 }
 
 impl MatchingGraph {
@@ -28,9 +40,87 @@ impl MatchingGraph {
             negative_weight_sum: 0,
             is_user_graph_boundary_node: Vec::new(),
             normalising_constant: 1.0,
+            boundaryless_component: Vec::new(),
+            component_parity: Vec::new(),
         }
     }
 
+    /// Label the connected components that have no boundary edge. Every
+    /// detection event in such a component must be matched to another in
+    /// it, so an odd number of them cannot be matched at all; see
+    /// [`MatchingGraph::odd_boundaryless_component`]. Call once the edges
+    /// are in place.
+    pub fn compute_boundaryless_components(&mut self) {
+        let n = self.nodes.len();
+        let mut component = vec![NO_COMPONENT; n];
+        let mut has_boundary = Vec::new();
+        let mut stack = Vec::new();
+        for root in 0..n {
+            if component[root] != NO_COMPONENT || self.nodes[root].neighbors.is_empty() {
+                continue;
+            }
+            let label = has_boundary.len() as u32;
+            let mut boundary = false;
+            component[root] = label;
+            stack.push(root);
+            while let Some(u) = stack.pop() {
+                for &v in &self.nodes[u].neighbors {
+                    if v == BOUNDARY_NODE {
+                        boundary = true;
+                    } else if component[v.0 as usize] == NO_COMPONENT {
+                        component[v.0 as usize] = label;
+                        stack.push(v.0 as usize);
+                    }
+                }
+            }
+            has_boundary.push(boundary);
+        }
+
+        // Renumber so that only the boundaryless components keep a label.
+        let mut relabel = vec![NO_COMPONENT; has_boundary.len()];
+        let mut num_boundaryless = 0;
+        for (label, _) in has_boundary.iter().enumerate().filter(|(_, b)| !**b) {
+            relabel[label] = num_boundaryless;
+            num_boundaryless += 1;
+        }
+        for c in &mut component {
+            if *c != NO_COMPONENT {
+                *c = relabel[*c as usize];
+            }
+        }
+        self.boundaryless_component = component;
+        self.component_parity = vec![false; num_boundaryless as usize];
+    }
+
+    /// A detector of `detection_events` lying in a component without a
+    /// boundary that holds an odd number of them, if there is one.
+    /// `detection_events` must not repeat a detector.
+    pub fn odd_boundaryless_component(&mut self, detection_events: &[usize]) -> Option<usize> {
+        if self.component_parity.is_empty() {
+            return None;
+        }
+        let component = |d: usize| {
+            self.boundaryless_component
+                .get(d)
+                .copied()
+                .filter(|&c| c != NO_COMPONENT)
+        };
+        for &d in detection_events {
+            if let Some(c) = component(d) {
+                self.component_parity[c as usize] ^= true;
+            }
+        }
+        let mut odd = None;
+        for &d in detection_events {
+            if let Some(c) = component(d)
+                && std::mem::take(&mut self.component_parity[c as usize])
+            {
+                odd.get_or_insert(d);
+            }
+        }
+        odd
+    }
+
     pub fn add_edge(
         &mut self,
         u: usize,
@@ -77,6 +167,37 @@ impl MatchingGraph {
         self.nodes[v].neighbor_observables.push(obs_mask);
     }
 
+    /// Account for an edge between two boundary nodes. It is never part of
+    /// a matching, but a negative-weight one is always in the correction,
+    /// so its observables and weight are folded into the negative-weight
+    /// terms.
+    pub fn add_boundary_to_boundary_edge(&mut self, weight: SignedWeight, observables: &[usize]) {
+        if weight < 0 {
+            for &obs in observables {
+                if !self.negative_weight_observables_set.remove(&obs) {
+                    self.negative_weight_observables_set.insert(obs);
+                }
+            }
+            self.negative_weight_sum += weight as TotalWeight;
+        }
+    }
+
+    /// Account for a fault that always occurs: its detectors and
+    /// observables are flipped in every shot, like those of a
+    /// negative-weight edge, but no edge is added for the matching to undo.
+    pub fn add_certain_fault(&mut self, detectors: &[usize], observables: &[usize]) {
+        for &obs in observables {
+            if !self.negative_weight_observables_set.remove(&obs) {
+                self.negative_weight_observables_set.insert(obs);
+            }
+        }
+        for &d in detectors {
+            if !self.negative_weight_detection_events_set.remove(&d) {
+                self.negative_weight_detection_events_set.insert(d);
+            }
+        }
+    }
+
     pub fn add_boundary_edge(
         &mut self,
         u: usize,
@@ -108,4 +229,26 @@ impl MatchingGraph {
         self.nodes[u].neighbor_weights.push(abs_weight);
         self.nodes[u].neighbor_observables.push(obs_mask);
     }
+
+    /// Approximate bytes held by the graph, from its `Vec` and set
+    /// capacities.
+    pub fn memory_footprint(&self) -> usize {
+        let adjacency: usize = self
+            .nodes
+            .iter()
+            .map(|n| {
+                n.neighbors.capacity() * size_of::<NodeIdx>()
+                    + n.neighbor_weights.capacity() * size_of::<Weight>()
+                    + n.neighbor_observables.capacity() * size_of::<ObsMask>()
+            })
+            .sum();
+        self.nodes.capacity() * size_of::<DetectorNode>()
+            + adjacency
+            + (self.negative_weight_detection_events_set.capacity()
+                + self.negative_weight_observables_set.capacity())
+                * size_of::<usize>()
+            + self.is_user_graph_boundary_node.capacity()
+            + self.boundaryless_component.capacity() * size_of::<u32>()
+            + self.component_parity.capacity()
+    }
 }
@@ -1,7 +1,7 @@
 use std::num::Wrapping;
 
 use crate::interop::*;
-use crate::matcher::alt_tree::AltTreeNode;
+use crate::matcher::alt_tree::{AltTreeEdge, AltTreeNode};
 use crate::types::*;
 use crate::util::arena::Arena;
 use crate::util::radix_heap::{HasTime, RadixHeapQueue};
@@ -10,15 +10,30 @@ use crate::util::varying::VaryingCT;
 use super::fill_region::GraphFillRegion;
 use super::graph::{MatchingGraph, BOUNDARY_NODE};
 
+/// Arena size above which `GraphFlooder::reset` may release arena storage.
+const ARENA_COMPACT_THRESHOLD: usize = 1 << 16;
+
+/// Release arena storage only when it exceeds the recent high-water mark of
+/// slots used per decode by this factor, so a run of large decodes keeps
+/// its storage.
+const ARENA_COMPACT_SLACK: usize = 2;
+
+#[derive(Debug, Clone)]
 pub struct GraphFlooder {
     pub graph: MatchingGraph,
     pub region_arena: Arena<GraphFillRegion>,
     pub node_arena: Arena<AltTreeNode>,
     pub queue: RadixHeapQueue<FloodCheckEvent>,
-    pub match_edges: Vec<CompressedEdge>,
     pub node_cleanup_buffer: Vec<NodeIdx>,
     touched_nodes: Vec<NodeIdx>,
     node_was_touched: Vec<bool>,
+    /// When set, simultaneous events at a node are resolved in a
+    /// pseudo-random neighbor order derived from this seed rather than in
+    /// adjacency order.
+    pub tie_break_seed: Option<u64>,
+    /// Decaying maxima of the slots each arena used per decode.
+    region_slots_high_water: usize,
+    node_slots_high_water: usize,
 }
 
 impl GraphFlooder {
@@ -29,9 +44,11 @@ impl GraphFlooder {
             region_arena: Arena::new(),
             node_arena: Arena::new(),
             queue: RadixHeapQueue::new(),
-            match_edges: Vec::new(),
             node_cleanup_buffer: Vec::new(),
             touched_nodes: Vec::new(),
+            tie_break_seed: None,
+            region_slots_high_water: 0,
+            node_slots_high_water: 0,
         }
     }
 
@@ -291,11 +308,27 @@ impl GraphFlooder {
         let regions = self.region_arena.items();
         let node = &self.graph.nodes[node_idx.0 as usize];
         let (rad1_y, rad1_growing, _rad1_shrinking) = Self::node_local_radius_parts(node, regions);
+        let offset = self.neighbor_scan_offset(node_idx, node.neighbors.len());
 
         if rad1_growing {
-            self.find_next_event_growing(node, regions, rad1_y)
+            self.find_next_event_growing(node, regions, rad1_y, offset)
         } else {
-            self.find_next_event_not_growing(node, regions, rad1_y)
+            self.find_next_event_not_growing(node, regions, rad1_y, offset)
+        }
+    }
+
+    /// Where to start scanning a node's neighbors. Ties go to the first
+    /// neighbor scanned, so rotating the start per node and time shuffles
+    /// tie-breaking while keeping it reproducible for a given seed.
+    fn neighbor_scan_offset(&self, node_idx: NodeIdx, num_neighbors: usize) -> usize {
+        match self.tie_break_seed {
+            Some(seed) if num_neighbors > 1 => {
+                let key = seed
+                    ^ (node_idx.0 as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+                    ^ (self.queue.cur_time as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+                (splitmix64(key) % num_neighbors as u64) as usize
+            }
+            _ => 0,
         }
     }
 
@@ -305,11 +338,14 @@ impl GraphFlooder {
         node: &super::detector_node::DetectorNode,
         regions: &[GraphFillRegion],
         rad1_y: CumulativeTime,
+        offset: usize,
     ) -> (usize, CumulativeTime) {
         let mut best_time = i64::MAX;
         let mut best_neighbor = NO_NEIGHBOR;
+        let num_neighbors = node.neighbors.len();
 
-        for i in 0..node.neighbors.len() {
+        for k in 0..num_neighbors {
+            let i = if k + offset < num_neighbors { k + offset } else { k + offset - num_neighbors };
             let neighbor_idx = node.neighbors[i];
             let weight = node.neighbor_weights[i] as CumulativeTime;
 
@@ -338,6 +374,9 @@ impl GraphFlooder {
 
             let (rad2_y, rad2_growing, rad2_shrinking) =
                 Self::node_local_radius_parts(neighbor, regions);
+            // Growing into a shrinking region keeps the gap constant, so
+            // they never collide; as in PyMatching, the nodes are looked at
+            // again once the shrinking region's state changes.
             if rad2_shrinking {
                 continue;
             }
@@ -362,18 +401,15 @@ impl GraphFlooder {
         node: &super::detector_node::DetectorNode,
         regions: &[GraphFillRegion],
         rad1_y: CumulativeTime,
+        offset: usize,
     ) -> (usize, CumulativeTime) {
         let mut best_time = i64::MAX;
         let mut best_neighbor = NO_NEIGHBOR;
+        let num_neighbors = node.neighbors.len();
 
-        // Skip boundary neighbors (index 0 if it's boundary) since we're not growing
-        let start = if !node.neighbors.is_empty() && node.neighbors[0] == BOUNDARY_NODE {
-            1
-        } else {
-            0
-        };
-
-        for i in start..node.neighbors.len() {
+        // Boundary neighbors are skipped below since we're not growing
+        for k in 0..num_neighbors {
+            let i = if k + offset < num_neighbors { k + offset } else { k + offset - num_neighbors };
             let neighbor_idx = node.neighbors[i];
             if neighbor_idx == BOUNDARY_NODE {
                 continue;
@@ -544,17 +580,21 @@ impl GraphFlooder {
     }
 
     fn do_blossom_shattering(&self, region_idx: RegionIdx) -> MwpmEvent {
-        let region = &self.region_arena[region_idx.0];
-
-        let in_parent = region.blossom_in_parent_loc.and_then(|node_idx| {
-            self.graph.nodes[node_idx.0 as usize]
-                .heir_region_on_shatter(self.region_arena.items())
-        });
-
-        let in_child = region.blossom_in_child_loc.and_then(|node_idx| {
-            self.graph.nodes[node_idx.0 as usize]
-                .heir_region_on_shatter(self.region_arena.items())
-        });
+        // A shrinking blossom is an inner region: the edges to its parent
+        // and to its outer partner both start at a node inside it, and the
+        // blossom children containing those nodes take over its tree links.
+        let alt_node = &self.node_arena[self.region_arena[region_idx.0]
+            .alt_tree_node
+            .expect("shrinking blossom must be in an alternating tree")
+            .0];
+        let heir = |loc: Option<NodeIdx>| {
+            loc.and_then(|node_idx| {
+                self.graph.nodes[node_idx.0 as usize]
+                    .heir_region_on_shatter(self.region_arena.items())
+            })
+        };
+        let in_parent = heir(alt_node.parent.as_ref().and_then(|p| p.edge.loc_from));
+        let in_child = heir(alt_node.inner_to_outer_edge.loc_from);
 
         match (in_parent, in_child) {
             (Some(ip), Some(ic)) => MwpmEvent::BlossomShatter {
@@ -597,20 +637,60 @@ impl GraphFlooder {
     // Reset
     // ---------------------------------------------------------------
 
+    /// Return to the empty state. Safe to call at any point of a decode,
+    /// including with blossoms and alternating trees still live: every node
+    /// a region reached is in `touched_nodes`, so its region pointers and
+    /// cached wrapped radius are cleared, and every region and tree node
+    /// allocated since the last reset is torn down, children, parents and
+    /// blossom links included, before its slot is freed.
     pub fn reset(&mut self) {
         for node_idx in self.touched_nodes.drain(..) {
             self.graph.nodes[node_idx.0 as usize].reset();
             self.node_was_touched[node_idx.0 as usize] = false;
         }
+        let regions_used = self.region_arena.num_touched();
+        let nodes_used = self.node_arena.num_touched();
         self.region_arena
             .recycle_touched(GraphFillRegion::reset_for_reuse);
         self.node_arena
             .recycle_touched(AltTreeNode::reset_for_reuse);
+        // Everything is free now; give back storage left by an unusually
+        // large decode instead of holding it for the decoder's lifetime.
+        compact_if_oversized(&mut self.region_arena, regions_used, &mut self.region_slots_high_water);
+        compact_if_oversized(&mut self.node_arena, nodes_used, &mut self.node_slots_high_water);
         self.queue.reset();
-        self.match_edges.clear();
         self.node_cleanup_buffer.clear();
     }
 
+    /// Approximate bytes held by the graph, arenas, queue and scratch
+    /// buffers, including the `Vec`s owned by regions and tree nodes.
+    pub fn memory_footprint(&self) -> usize {
+        let region_heap: usize = self
+            .region_arena
+            .items()
+            .iter()
+            .map(|r| {
+                r.blossom_children.capacity() * size_of::<RegionEdge>()
+                    + r.shell_area.capacity() * size_of::<NodeIdx>()
+            })
+            .sum();
+        let tree_heap: usize = self
+            .node_arena
+            .items()
+            .iter()
+            .map(|n| n.children.capacity() * size_of::<AltTreeEdge>())
+            .sum();
+        self.graph.memory_footprint()
+            + self.region_arena.memory_footprint()
+            + region_heap
+            + self.node_arena.memory_footprint()
+            + tree_heap
+            + self.queue.memory_footprint()
+            + (self.node_cleanup_buffer.capacity() + self.touched_nodes.capacity())
+                * size_of::<NodeIdx>()
+            + self.node_was_touched.capacity()
+    }
+
     // ---------------------------------------------------------------
     // Helpers
     // ---------------------------------------------------------------
@@ -632,6 +712,25 @@ impl GraphFlooder {
     }
 }
 
+/// SplitMix64 finalizer: a cheap, well-mixed hash of a 64-bit key.
+fn splitmix64(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Fold `used` into the decaying `high_water` mark (it loses an eighth per
+/// decode) and compact `arena` once it is both large and well above it.
+fn compact_if_oversized<T: Default>(arena: &mut Arena<T>, used: usize, high_water: &mut usize) {
+    *high_water = used.max(*high_water - *high_water / 8);
+    if arena.num_slots() > ARENA_COMPACT_THRESHOLD
+        && arena.num_slots() > ARENA_COMPACT_SLACK * *high_water
+    {
+        arena.compact();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
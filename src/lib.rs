@@ -1,4 +1,5 @@
 pub mod types;
+pub mod error;
 pub mod util;
 pub mod interop;
 pub mod flooder;
@@ -7,6 +8,7 @@ pub mod search;
 pub mod driver;
 
 pub use driver::decoding::Matching;
+pub use error::MatchingError;
 
 #[cfg(feature = "rsinter")]
 pub mod decoder;
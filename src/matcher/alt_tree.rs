@@ -50,6 +50,7 @@ pub struct AltTreePruneResult {
 /// Each AltTreeNode represents a *pair* of alternating-tree nodes:
 /// an inner (shrinking) region and an outer (growing) region.
 /// The root node has `inner_region = None`.
+#[derive(Debug, Clone)]
 pub struct AltTreeNode {
     pub inner_region: Option<RegionIdx>,
     pub outer_region: Option<RegionIdx>,
@@ -57,6 +58,8 @@ pub struct AltTreeNode {
     pub parent: Option<AltTreeEdge>,
     pub children: Vec<AltTreeEdge>,
     pub visited: bool,
+    /// Distance from the root of the tree, in alternating-tree nodes.
+    pub depth: usize,
 }
 
 impl Default for AltTreeNode {
@@ -68,6 +71,7 @@ impl Default for AltTreeNode {
             parent: None,
             children: Vec::new(),
             visited: false,
+            depth: 0,
         }
     }
 }
@@ -80,6 +84,7 @@ impl AltTreeNode {
         self.parent = None;
         self.children.clear();
         self.visited = false;
+        self.depth = 0;
     }
 
     /// Root-only constructor: outer region only, no inner.
@@ -91,6 +96,7 @@ impl AltTreeNode {
             parent: None,
             children: Vec::new(),
             visited: false,
+            depth: 0,
         }
     }
 
@@ -107,6 +113,7 @@ impl AltTreeNode {
             parent: None,
             children: Vec::new(),
             visited: false,
+            depth: 0,
         }
     }
 
@@ -121,11 +128,63 @@ impl AltTreeNode {
         let reversed_edge = child.edge.reversed();
         self.children.push(child);
         arena[child_idx.0].parent = Some(AltTreeEdge::new(self_idx, reversed_edge));
+        AltTreeNode::set_subtree_depth(child_idx, self.depth + 1, arena);
+    }
+
+    /// Set the depth of `root_idx` and re-derive the depths of its descendants.
+    /// Returns the largest depth in the subtree.
+    ///
+    /// Walks the subtree depth-first through the parent links rather than
+    /// an explicit stack, so it never allocates.
+    pub fn set_subtree_depth(
+        root_idx: AltTreeIdx,
+        depth: usize,
+        arena: &mut Arena<AltTreeNode>,
+    ) -> usize {
+        arena[root_idx.0].depth = depth;
+        let mut max_depth = depth;
+        let mut idx = root_idx;
+        loop {
+            // Descend to the first child, or climb to the next unvisited sibling.
+            let next = match arena[idx.0].children.first() {
+                Some(child) => Some((child.alt_tree_node, idx)),
+                None => loop {
+                    if idx == root_idx {
+                        break None;
+                    }
+                    let parent = arena[idx.0].parent.as_ref().unwrap().alt_tree_node;
+                    let siblings = &arena[parent.0].children;
+                    let pos = siblings.iter().position(|c| c.alt_tree_node == idx).unwrap();
+                    if let Some(sibling) = siblings.get(pos + 1) {
+                        break Some((sibling.alt_tree_node, parent));
+                    }
+                    idx = parent;
+                },
+            };
+            let Some((next, parent)) = next else {
+                return max_depth;
+            };
+            let depth = arena[parent.0].depth + 1;
+            arena[next.0].depth = depth;
+            max_depth = max_depth.max(depth);
+            idx = next;
+        }
     }
 
     /// Tree rotation: make this node the root.
     /// Recursively rotates parent first, then re-parents.
-    pub fn become_root(self_idx: AltTreeIdx, arena: &mut Arena<AltTreeNode>) {
+    /// Returns the depth of the re-rooted tree.
+    pub fn become_root(self_idx: AltTreeIdx, arena: &mut Arena<AltTreeNode>) -> usize {
+        if arena[self_idx.0].parent.is_none() {
+            return 0; // already root
+        }
+        AltTreeNode::rotate_to_root(self_idx, arena);
+        AltTreeNode::set_subtree_depth(self_idx, 0, arena)
+    }
+
+    /// Like [`AltTreeNode::become_root`], but leaves the depths stale. For
+    /// trees about to be shattered, whose depths are never read again.
+    pub(crate) fn rotate_to_root(self_idx: AltTreeIdx, arena: &mut Arena<AltTreeNode>) {
         let parent_edge = arena[self_idx.0].parent.clone();
         if parent_edge.is_none() {
             return; // already root
@@ -134,7 +193,7 @@ impl AltTreeNode {
         let old_parent_idx = parent_edge.alt_tree_node;
 
         // Recurse: make old parent the root first
-        AltTreeNode::become_root(old_parent_idx, arena);
+        AltTreeNode::rotate_to_root(old_parent_idx, arena);
 
         // Now old_parent is root. Rotate.
         // old_parent.inner_region = self.inner_region
@@ -23,19 +23,69 @@ impl MatchingResult {
     }
 }
 
+impl Default for MatchingResult {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Weights saturate at the bounds of `TotalWeight` instead of wrapping;
+/// debug builds assert that this never happens.
 impl std::ops::AddAssign for MatchingResult {
     fn add_assign(&mut self, rhs: Self) {
         self.obs_mask ^= rhs.obs_mask;
-        self.weight += rhs.weight;
+        debug_assert!(
+            self.weight.checked_add(rhs.weight).is_some(),
+            "matching weight overflow: {} + {}",
+            self.weight,
+            rhs.weight
+        );
+        self.weight = self.weight.saturating_add(rhs.weight);
     }
 }
 
+// ---------------------------------------------------------------------------
+// MwpmStats
+// ---------------------------------------------------------------------------
+
+/// Shape of the search seen during one decode, for spotting graphs whose
+/// blossoms or alternating trees grow unusually large.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MwpmStats {
+    /// Number of regions in the largest odd cycle turned into a blossom.
+    /// Sub-blossoms count as one region each.
+    pub max_blossom_size: usize,
+    /// Largest number of alternating-tree edges between a root and any node
+    /// in its tree.
+    pub max_tree_depth: usize,
+    /// Detection events skipped because their detector has no edges, so
+    /// nothing in the graph can explain them.
+    pub isolated_detection_events: usize,
+    /// Flooder events processed by the matcher, a rough measure of how
+    /// hard the decode was.
+    pub num_events: usize,
+}
+
 // ---------------------------------------------------------------------------
 // Mwpm
 // ---------------------------------------------------------------------------
 
 pub struct Mwpm {
     pub flooder: GraphFlooder,
+    /// Statistics of the current decode; cleared when a new one starts.
+    pub stats: MwpmStats,
+    /// Reject a detection event at a detector with no edges instead of
+    /// skipping it and counting it in `stats`. The driver checks this
+    /// before creating any detection event.
+    pub strict_isolated_detectors: bool,
+    /// Start every decode at time zero (the default). When off, the queue
+    /// time carries over from one decode to the next, giving a single
+    /// monotonic timeline across shots. The queue orders events by their
+    /// time modulo 2^32 relative to the current time, so the absolute time
+    /// may grow past 2^32; only events within one decode must stay less
+    /// than 2^31 ahead, as [`crate::driver::user_graph::UserGraph::check_weight_horizon`]
+    /// ensures.
+    pub reset_time_per_decode: bool,
     // SearchFlooder will be added in Task 7.
 }
 
@@ -43,6 +93,9 @@ impl Mwpm {
     pub fn new(flooder: GraphFlooder) -> Self {
         Mwpm {
             flooder,
+            stats: MwpmStats::default(),
+            strict_isolated_detectors: false,
+            reset_time_per_decode: true,
         }
     }
 
@@ -57,7 +110,9 @@ impl Mwpm {
                 .node_arena
                 .alloc_with_reset(AltTreeNode::reset_for_reuse),
         );
-        self.flooder.node_arena[alt_idx.0] = AltTreeNode::new_root(region_idx);
+        // Set the fields of the reset slot rather than replacing it, so a
+        // reused node keeps its `children` buffer.
+        self.flooder.node_arena[alt_idx.0].outer_region = Some(region_idx);
         self.flooder.region_arena[region_idx.0].alt_tree_node = Some(alt_idx);
         self.flooder.set_region_growing(region_idx);
     }
@@ -87,6 +142,17 @@ impl Mwpm {
         }
     }
 
+    /// Run the flooder to its next notification and process it, returning
+    /// the event, or `None` once there is nothing left to do.
+    pub fn step(&mut self) -> Option<MwpmEvent> {
+        let event = self.flooder.run_until_next_mwpm_notification();
+        if event.is_no_event() {
+            return None;
+        }
+        self.process_event(event.clone());
+        Some(event)
+    }
+
     // -------------------------------------------------------------------
     // Region hit region dispatch
     // -------------------------------------------------------------------
@@ -149,7 +215,7 @@ impl Mwpm {
         let alt_node = self.flooder.region_arena[region.0]
             .alt_tree_node
             .unwrap();
-        AltTreeNode::become_root(alt_node, &mut self.flooder.node_arena);
+        self.become_root(alt_node);
         self.shatter_descendants_into_matches_and_freeze(alt_node);
 
         // Match region to boundary and freeze
@@ -185,7 +251,7 @@ impl Mwpm {
         });
         self.flooder.set_region_frozen(unmatched_region);
 
-        AltTreeNode::become_root(alt_node, &mut self.flooder.node_arena);
+        self.become_root(alt_node);
         self.shatter_descendants_into_matches_and_freeze(alt_node);
     }
 
@@ -206,8 +272,8 @@ impl Mwpm {
             .alt_tree_node
             .unwrap();
 
-        AltTreeNode::become_root(alt_node_1, &mut self.flooder.node_arena);
-        AltTreeNode::become_root(alt_node_2, &mut self.flooder.node_arena);
+        self.become_root(alt_node_1);
+        self.become_root(alt_node_2);
 
         self.shatter_descendants_into_matches_and_freeze(alt_node_1);
         self.shatter_descendants_into_matches_and_freeze(alt_node_2);
@@ -317,8 +383,12 @@ impl Mwpm {
             edge,
         });
 
-        // Detach old outer_region from tree
-        let old_outer = self.flooder.node_arena[common_ancestor.0].outer_region.unwrap();
+        // Detach old outer_region from tree. Every node in a live tree has an
+        // outer region, including a bare root that is itself one of the two
+        // colliding regions; only recycled arena slots lack one.
+        let old_outer = self.flooder.node_arena[common_ancestor.0]
+            .outer_region
+            .expect("blossom common ancestor has no outer region: it is not a live alt-tree node");
         self.flooder.region_arena[old_outer.0].alt_tree_node = None;
 
         // Create blossom region in flooder
@@ -328,15 +398,8 @@ impl Mwpm {
         self.flooder.node_arena[common_ancestor.0].outer_region = Some(blossom_region);
         self.flooder.region_arena[blossom_region.0].alt_tree_node = Some(common_ancestor);
 
-        // Store anchor nodes for blossom shattering
-        let inner_to_outer_loc = self.flooder.node_arena[common_ancestor.0].inner_to_outer_edge.loc_from;
-        let parent_loc = self.flooder.node_arena[common_ancestor.0].parent
-            .as_ref()
-            .and_then(|p| p.edge.loc_from);
-        self.flooder.region_arena[blossom_region.0].blossom_in_parent_loc = parent_loc;
-        self.flooder.region_arena[blossom_region.0].blossom_in_child_loc = inner_to_outer_loc;
-
         // Re-parent orphans
+        let ancestor_depth = self.flooder.node_arena[common_ancestor.0].depth;
         for c in prune_result_1.orphan_edges {
             let child_idx = c.alt_tree_node;
             let edge = c.edge;
@@ -345,6 +408,8 @@ impl Mwpm {
                 .push(AltTreeEdge::new(child_idx, edge));
             self.flooder.node_arena[child_idx.0].parent =
                 Some(AltTreeEdge::new(common_ancestor, edge.reversed()));
+            let arena = &mut self.flooder.node_arena;
+            AltTreeNode::set_subtree_depth(child_idx, ancestor_depth + 1, arena);
         }
         for c in prune_result_2.orphan_edges {
             let child_idx = c.alt_tree_node;
@@ -354,6 +419,8 @@ impl Mwpm {
                 .push(AltTreeEdge::new(child_idx, edge));
             self.flooder.node_arena[child_idx.0].parent =
                 Some(AltTreeEdge::new(common_ancestor, edge.reversed()));
+            let arena = &mut self.flooder.node_arena;
+            AltTreeNode::set_subtree_depth(child_idx, ancestor_depth + 1, arena);
         }
     }
 
@@ -382,11 +449,11 @@ impl Mwpm {
         // Find indices of in_parent and in_child
         let mut parent_idx = 0;
         let mut child_idx = 0;
-        for i in 0..bsize {
-            if blossom_children[i].region == in_parent_region {
+        for (i, child) in blossom_children.iter().enumerate() {
+            if child.region == in_parent_region {
                 parent_idx = i;
             }
-            if blossom_children[i].region == in_child_region {
+            if child.region == in_child_region {
                 child_idx = i;
             }
         }
@@ -416,7 +483,7 @@ impl Mwpm {
         let evens_start;
         let evens_end;
 
-        if gap % 2 == 0 {
+        if gap.is_multiple_of(2) {
             evens_start = child_idx + 1;
             evens_end = child_idx + bsize - gap;
 
@@ -500,6 +567,10 @@ impl Mwpm {
             .push(blossom_child_edge);
         self.flooder.node_arena[blossom_alt_node.0].parent =
             Some(AltTreeEdge::new(current_alt_node, rev));
+        let depth = self.flooder.node_arena[current_alt_node.0].depth + 1;
+        let max_depth =
+            AltTreeNode::set_subtree_depth(blossom_alt_node, depth, &mut self.flooder.node_arena);
+        self.stats.max_tree_depth = self.stats.max_tree_depth.max(max_depth);
 
         // Free the blossom region
         self.flooder.region_arena.free(blossom_region.0);
@@ -510,12 +581,13 @@ impl Mwpm {
     // -------------------------------------------------------------------
 
     fn shatter_descendants_into_matches_and_freeze(&mut self, alt_node: AltTreeIdx) {
-        // Recursively process children first
-        let children: Vec<AltTreeEdge> =
-            std::mem::take(&mut self.flooder.node_arena[alt_node.0].children);
-        for child_edge in &children {
-            self.shatter_descendants_into_matches_and_freeze(child_edge.alt_tree_node);
+        // Recursively process children first. The list is cleared rather
+        // than taken so the freed slot keeps its buffer.
+        for i in 0..self.flooder.node_arena[alt_node.0].children.len() {
+            let child = self.flooder.node_arena[alt_node.0].children[i].alt_tree_node;
+            self.shatter_descendants_into_matches_and_freeze(child);
         }
+        self.flooder.node_arena[alt_node.0].children.clear();
 
         if let Some(inner) = self.flooder.node_arena[alt_node.0].inner_region {
             let outer = self.flooder.node_arena[alt_node.0].outer_region.unwrap();
@@ -543,6 +615,16 @@ impl Mwpm {
         self.flooder.node_arena.free(alt_node.0);
     }
 
+    // -------------------------------------------------------------------
+    // Tree rotation helper
+    // -------------------------------------------------------------------
+
+    /// Re-root the tree at `alt_node` just before it is shattered. Its
+    /// depths are never read again, so they are not re-derived.
+    fn become_root(&mut self, alt_node: AltTreeIdx) {
+        AltTreeNode::rotate_to_root(alt_node, &mut self.flooder.node_arena);
+    }
+
     // -------------------------------------------------------------------
     // Make child helper
     // -------------------------------------------------------------------
@@ -560,8 +642,10 @@ impl Mwpm {
                 .node_arena
                 .alloc_with_reset(AltTreeNode::reset_for_reuse),
         );
-        self.flooder.node_arena[child_idx.0] =
-            AltTreeNode::new_pair(child_inner, child_outer, child_inner_to_outer_edge);
+        let node = &mut self.flooder.node_arena[child_idx.0];
+        node.inner_region = Some(child_inner);
+        node.outer_region = Some(child_outer);
+        node.inner_to_outer_edge = child_inner_to_outer_edge;
         self.flooder.region_arena[child_inner.0].alt_tree_node = Some(child_idx);
         self.flooder.region_arena[child_outer.0].alt_tree_node = Some(child_idx);
 
@@ -570,6 +654,10 @@ impl Mwpm {
         self.flooder.node_arena[parent.0].children.push(edge);
         self.flooder.node_arena[child_idx.0].parent = Some(AltTreeEdge::new(parent, rev));
 
+        let depth = self.flooder.node_arena[parent.0].depth + 1;
+        self.flooder.node_arena[child_idx.0].depth = depth;
+        self.stats.max_tree_depth = self.stats.max_tree_depth.max(depth);
+
         child_idx
     }
 
@@ -656,6 +744,7 @@ impl Mwpm {
 
         // Set blossom children
         self.flooder.region_arena[blossom_idx.0].blossom_children = cycle.to_vec();
+        self.stats.max_blossom_size = self.stats.max_blossom_size.max(cycle.len());
 
         // Freeze each child region, set blossom parent, clear shrink events
         // (mirrors C++ create_blossom: freeze + wrap_into_blossom + clear shrink_event_tracker)
@@ -678,6 +767,14 @@ impl Mwpm {
         blossom_idx
     }
 
+    /// Point every node of the new blossom at it and reschedule its events.
+    ///
+    /// Nodes outside the blossom need no rescheduling: an edge between a
+    /// blossom node and an outside node is re-examined from the blossom
+    /// side, which is now growing and so finds any collision with a growing
+    /// or frozen neighbour. Only a shrinking neighbour is skipped, and that
+    /// gap stays constant. Stale events at outside nodes are harmless, since
+    /// each look-at-node event recomputes its collision when it fires.
     fn update_blossom_area_and_reschedule(
         &mut self,
         region: RegionIdx,
@@ -705,10 +802,91 @@ impl Mwpm {
     // Shatter blossom and extract matches
     // -------------------------------------------------------------------
 
+    /// Check that matches are mutual: a live region matched to `b` has `b`
+    /// matched back to it, and a boundary match (`region: None`) uses an
+    /// edge ending on the boundary. A one-sided match means one of the
+    /// `handle_tree_hitting_*` handlers left the state inconsistent.
+    pub fn verify_matching_symmetry(&self) -> Result<(), String> {
+        let arena = &self.flooder.region_arena;
+        for (a, region) in arena.items().iter().enumerate() {
+            if !arena.is_live(a as u32) {
+                continue;
+            }
+            let Some(m) = &region.match_ else {
+                continue;
+            };
+            match m.region {
+                Some(b) => {
+                    if !arena.is_live(b.0) {
+                        return Err(format!("region {a} is matched to freed region {}", b.0));
+                    }
+                    let back = arena[b.0].match_.as_ref().and_then(|mb| mb.region);
+                    if back != Some(RegionIdx(a as u32)) {
+                        return Err(format!(
+                            "region {a} is matched to region {} but it is matched to {back:?}",
+                            b.0
+                        ));
+                    }
+                }
+                None => {
+                    if m.edge.loc_to.is_some() {
+                        return Err(format!(
+                            "region {a} has a boundary match along a non-boundary edge"
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn shatter_blossom_and_extract_matches(
         &mut self,
         region: RegionIdx,
     ) -> MatchingResult {
+        let mut res = MatchingResult::new();
+        self.shatter_blossom_and_stream_matches(region, &mut |edge, weight| {
+            res += MatchingResult {
+                obs_mask: edge.obs_mask,
+                weight,
+            };
+        });
+        res
+    }
+
+    /// Shatter `region` (and its match partner), collecting the compressed
+    /// edge of every non-empty match (for decode_to_edges).
+    pub fn shatter_blossom_and_extract_match_edges(
+        &mut self,
+        region: RegionIdx,
+        match_edges: &mut Vec<CompressedEdge>,
+    ) {
+        self.shatter_blossom_and_stream_matches(region, &mut |edge, _| {
+            if edge.loc_from.is_some() || edge.loc_to.is_some() || edge.obs_mask != 0 {
+                match_edges.push(edge);
+            }
+        });
+    }
+
+    /// Shatter `region` (and its match partner), handing each resolved match
+    /// to `emit` as soon as it is known instead of accumulating them.
+    ///
+    /// `emit` receives the match's compressed edge (`loc_to = None` for a
+    /// boundary match) and its weight in discretized units. The radius of a
+    /// shattered blossom is charged to the match of the sub-blossom that
+    /// inherits the blossom's match, so the weights sum to the same total as
+    /// [`Mwpm::shatter_blossom_and_extract_matches`].
+    pub fn shatter_blossom_and_stream_matches<F>(&mut self, region: RegionIdx, emit: &mut F)
+    where
+        F: FnMut(CompressedEdge, TotalWeight),
+    {
+        self.stream_matches(region, 0, emit);
+    }
+
+    fn stream_matches<F>(&mut self, region: RegionIdx, extra_weight: TotalWeight, emit: &mut F)
+    where
+        F: FnMut(CompressedEdge, TotalWeight),
+    {
         let boundary_edge = self.flooder.region_arena[region.0]
             .match_
             .as_ref()
@@ -745,49 +923,53 @@ impl Mwpm {
                     .y_intercept();
                 self.flooder.region_arena.free(match_region.0);
                 self.flooder.region_arena.free(region.0);
-                return MatchingResult {
-                    obs_mask: edge.obs_mask,
-                    weight: w1 + w2,
-                };
+                emit(edge, w1 + w2 + extra_weight);
+                return;
             }
         } else if !has_blossom_children {
             // PyMatching keeps a default-initialized match object even when the
             // region is only carrying an implicit boundary/empty match state.
             let w = self.flooder.region_arena[region.0].radius.y_intercept();
             self.flooder.region_arena.free(region.0);
-            return MatchingResult {
-                obs_mask: boundary_edge.obs_mask,
-                weight: w,
-            };
+            emit(boundary_edge, w + extra_weight);
+            return;
         }
 
         // Complex case: shatter sub-blossoms
-        let mut res = MatchingResult::new();
         let mut region = region;
+        let mut extra_weight = extra_weight;
 
         if !self.flooder.region_arena[region.0].blossom_children.is_empty() {
-            region = self.pair_and_shatter_subblossoms(region, &mut res);
+            let (subblossom, blossom_weight) = self.pair_and_shatter_subblossoms(region, emit);
+            region = subblossom;
+            extra_weight += blossom_weight;
         }
 
         let match_region = self.flooder.region_arena[region.0]
             .match_
             .as_ref()
             .and_then(|m| m.region);
-        if let Some(mr) = match_region {
-            if !self.flooder.region_arena[mr.0].blossom_children.is_empty() {
-                self.pair_and_shatter_subblossoms(mr, &mut res);
-            }
+        if let Some(mr) = match_region
+            && !self.flooder.region_arena[mr.0].blossom_children.is_empty()
+        {
+            let (_, blossom_weight) = self.pair_and_shatter_subblossoms(mr, emit);
+            extra_weight += blossom_weight;
         }
 
-        res += self.shatter_blossom_and_extract_matches(region);
-        res
+        self.stream_matches(region, extra_weight, emit);
     }
 
-    fn pair_and_shatter_subblossoms(
+    /// Dissolve one blossom layer: hand its match to the sub-blossom that
+    /// owns the match edge and pair the remaining children around the cycle.
+    /// Returns that sub-blossom and the dissolved blossom's radius.
+    fn pair_and_shatter_subblossoms<F>(
         &mut self,
         region: RegionIdx,
-        res: &mut MatchingResult,
-    ) -> RegionIdx {
+        emit: &mut F,
+    ) -> (RegionIdx, TotalWeight)
+    where
+        F: FnMut(CompressedEdge, TotalWeight),
+    {
         let children: Vec<RegionEdge> =
             std::mem::take(&mut self.flooder.region_arena[region.0].blossom_children);
 
@@ -797,8 +979,15 @@ impl Mwpm {
             self.clear_region_blossom_parent(child.region, false);
         }
 
-        // 2. Find which child owns the match edge's loc_from node.
-        let match_edge = self.flooder.region_arena[region.0].match_.as_ref().unwrap().edge;
+        // 2. Find which child owns the match edge's loc_from node. A blossom
+        //    can only be left unmatched when its component has an odd number
+        //    of detection events and no boundary to absorb the extra one,
+        //    which the driver rejects before creating any detection event.
+        let match_edge = self.flooder.region_arena[region.0]
+            .match_
+            .as_ref()
+            .expect("no perfect matching: odd number of detection events in a component without a boundary")
+            .edge;
         let subblossom = match_edge
             .loc_from
             .and_then(|node_idx| self.flooder.graph.nodes[node_idx.0 as usize].region_that_arrived_top)
@@ -817,8 +1006,8 @@ impl Mwpm {
             });
         }
 
-        // 4. Accumulate blossom radius weight
-        res.weight += self.flooder.region_arena[region.0].radius.y_intercept();
+        // 4. Blossom radius weight, charged to the subblossom's match
+        let blossom_weight = self.flooder.region_arena[region.0].radius.y_intercept();
 
         // 5. Find subblossom index in children
         let index = children.iter().position(|c| c.region == subblossom)
@@ -835,136 +1024,13 @@ impl Mwpm {
             let e = re1.edge;
             self.flooder.region_arena[r1.0].match_ = Some(Match { region: Some(r2), edge: e });
             self.flooder.region_arena[r2.0].match_ = Some(Match { region: Some(r1), edge: e.reversed() });
-            let sub_res = self.shatter_blossom_and_extract_matches(r1);
-            *res += sub_res;
+            self.stream_matches(r1, 0, emit);
             i += 2;
         }
 
         // 7. Free blossom region and return subblossom
         self.flooder.region_arena.free(region.0);
-        subblossom
-    }
-
-    // -------------------------------------------------------------------
-    // Shatter blossom and extract match edges (for decode_to_edges)
-    // -------------------------------------------------------------------
-
-    pub fn shatter_blossom_and_extract_match_edges(
-        &mut self,
-        region: RegionIdx,
-        match_edges: &mut Vec<CompressedEdge>,
-    ) {
-        let boundary_edge = self.flooder.region_arena[region.0]
-            .match_
-            .as_ref()
-            .map(|m| m.edge)
-            .unwrap_or_else(CompressedEdge::empty);
-        let has_match_region = self.flooder.region_arena[region.0]
-            .match_
-            .as_ref()
-            .and_then(|m| m.region)
-            .is_some();
-        let has_blossom_children =
-            !self.flooder.region_arena[region.0].blossom_children.is_empty();
-
-        if has_match_region {
-            let match_region = self.flooder.region_arena[region.0]
-                .match_
-                .as_ref()
-                .unwrap()
-                .region
-                .unwrap();
-            let match_region_has_blossom =
-                !self.flooder.region_arena[match_region.0].blossom_children.is_empty();
-
-            if !has_blossom_children && !match_region_has_blossom {
-                let edge = self.flooder.region_arena[region.0]
-                    .match_
-                    .as_ref()
-                    .unwrap()
-                    .edge;
-                match_edges.push(edge);
-                self.flooder.region_arena.free(match_region.0);
-                self.flooder.region_arena.free(region.0);
-                return;
-            }
-        } else if !has_blossom_children {
-            if boundary_edge.loc_from.is_some() || boundary_edge.loc_to.is_some() || boundary_edge.obs_mask != 0 {
-                match_edges.push(boundary_edge);
-            }
-            self.flooder.region_arena.free(region.0);
-            return;
-        }
-
-        // Complex case: shatter sub-blossoms
-        let mut region = region;
-
-        if !self.flooder.region_arena[region.0].blossom_children.is_empty() {
-            region = self.pair_and_shatter_subblossoms_edges(region, match_edges);
-        }
-
-        let match_region = self.flooder.region_arena[region.0]
-            .match_
-            .as_ref()
-            .and_then(|m| m.region);
-        if let Some(mr) = match_region {
-            if !self.flooder.region_arena[mr.0].blossom_children.is_empty() {
-                self.pair_and_shatter_subblossoms_edges(mr, match_edges);
-            }
-        }
-
-        self.shatter_blossom_and_extract_match_edges(region, match_edges);
-    }
-
-    fn pair_and_shatter_subblossoms_edges(
-        &mut self,
-        region: RegionIdx,
-        match_edges: &mut Vec<CompressedEdge>,
-    ) -> RegionIdx {
-        let children: Vec<RegionEdge> =
-            std::mem::take(&mut self.flooder.region_arena[region.0].blossom_children);
-
-        for child in &children {
-            self.clear_region_blossom_parent(child.region, false);
-        }
-
-        let match_edge = self.flooder.region_arena[region.0].match_.as_ref().unwrap().edge;
-        let subblossom = match_edge
-            .loc_from
-            .and_then(|node_idx| self.flooder.graph.nodes[node_idx.0 as usize].region_that_arrived_top)
-            .expect("match edge loc_from must have a region");
-
-        let blossom_match = self.flooder.region_arena[region.0].match_.clone().unwrap();
-        self.flooder.region_arena[subblossom.0].match_ = Some(Match {
-            region: blossom_match.region,
-            edge: blossom_match.edge,
-        });
-        if let Some(other) = blossom_match.region {
-            self.flooder.region_arena[other.0].match_ = Some(Match {
-                region: Some(subblossom),
-                edge: blossom_match.edge.reversed(),
-            });
-        }
-
-        let index = children.iter().position(|c| c.region == subblossom)
-            .expect("subblossom must be in blossom_children");
-        let num_children = children.len();
-
-        let mut i = 0;
-        while i < num_children - 1 {
-            let re1 = &children[(index + i + 1) % num_children];
-            let re2 = &children[(index + i + 2) % num_children];
-            let r1 = re1.region;
-            let r2 = re2.region;
-            let e = re1.edge;
-            self.flooder.region_arena[r1.0].match_ = Some(Match { region: Some(r2), edge: e });
-            self.flooder.region_arena[r2.0].match_ = Some(Match { region: Some(r1), edge: e.reversed() });
-            self.shatter_blossom_and_extract_match_edges(r1, match_edges);
-            i += 2;
-        }
-
-        self.flooder.region_arena.free(region.0);
-        subblossom
+        (subblossom, blossom_weight)
     }
 
     // -------------------------------------------------------------------
@@ -975,21 +1041,70 @@ impl Mwpm {
         self.flooder.reschedule_events_at_detector_node(node_idx);
     }
 
+    /// Reschedule every node in the total area of `region`, including the
+    /// nodes of its blossom descendants.
     fn reschedule_region_nodes(&mut self, region: RegionIdx) {
         let shell_len = self.flooder.region_arena[region.0].shell_area.len();
         for i in 0..shell_len {
             let node_idx = self.flooder.region_arena[region.0].shell_area[i];
             self.flooder.reschedule_events_at_detector_node(node_idx);
         }
+        let child_len = self.flooder.region_arena[region.0].blossom_children.len();
+        for i in 0..child_len {
+            let child = self.flooder.region_arena[region.0].blossom_children[i].region;
+            self.reschedule_region_nodes(child);
+        }
     }
 
     // -------------------------------------------------------------------
     // Reset
     // -------------------------------------------------------------------
 
+    /// Clear the matcher for the next decode. See [`GraphFlooder::reset`];
+    /// like it, this may abandon a decode partway through.
     pub fn reset(&mut self) {
+        let cur_time = self.flooder.queue.cur_time;
         self.flooder.reset();
+        if !self.reset_time_per_decode {
+            self.flooder.queue.cur_time = cur_time;
+        }
+    }
+
+    /// Approximate bytes held by the solver. See
+    /// [`GraphFlooder::memory_footprint`].
+    pub fn memory_footprint(&self) -> usize {
+        self.flooder.memory_footprint()
     }
+
+    // -------------------------------------------------------------------
+    // Snapshot / restore
+    // -------------------------------------------------------------------
+
+    /// Capture the full matcher state so it can be rolled back later.
+    ///
+    /// This is a deep copy of the flooder (the graph nodes, both arenas and
+    /// the event queue) plus the running [`MwpmStats`]. Its cost is proportional to the size of the graph,
+    /// not to the number of detection events, so it is far more expensive
+    /// than a `reset` on a sparse syndrome.
+    pub fn snapshot(&self) -> MwpmSnapshot {
+        MwpmSnapshot {
+            flooder: self.flooder.clone(),
+            stats: self.stats,
+        }
+    }
+
+    /// Return to a state previously captured with [`Mwpm::snapshot`].
+    pub fn restore(&mut self, snapshot: MwpmSnapshot) {
+        self.flooder = snapshot.flooder;
+        self.stats = snapshot.stats;
+    }
+}
+
+/// Saved matcher state produced by [`Mwpm::snapshot`].
+#[derive(Debug, Clone)]
+pub struct MwpmSnapshot {
+    flooder: GraphFlooder,
+    stats: MwpmStats,
 }
 
 #[cfg(test)]
@@ -1,5 +1,6 @@
 use std::num::Wrapping;
 
+use crate::error::MatchingError;
 use crate::interop::CompressedEdge;
 use crate::search::search_graph::SearchGraph;
 use crate::types::*;
@@ -65,6 +66,8 @@ pub struct SearchFlooder {
     pub queue: RadixHeapQueue<SearchEvent>,
     reached_nodes: Vec<SearchNodeIdx>,
     target_type: TargetType,
+    /// Number of shortest-path searches run so far.
+    pub(crate) num_searches: usize,
 }
 
 impl SearchFlooder {
@@ -74,6 +77,7 @@ impl SearchFlooder {
             queue: RadixHeapQueue::new(),
             reached_nodes: Vec::new(),
             target_type: TargetType::NoTarget,
+            num_searches: 0,
         }
     }
 
@@ -190,11 +194,28 @@ impl SearchFlooder {
         self.reschedule_events(empty_idx);
     }
 
+    /// Index of `from` in the neighbors of `node`, or an error naming the
+    /// one-way edge `from -> node` if the graph lacks the edge back.
+    fn reverse_neighbor_index(
+        &self,
+        node: SearchNodeIdx,
+        from: SearchNodeIdx,
+    ) -> Result<usize, MatchingError> {
+        self.graph.nodes[node.0 as usize]
+            .find_neighbor(Some(from))
+            .ok_or_else(|| {
+                MatchingError::InvalidGraph(format!(
+                    "search graph edge {} -> {} has no reverse edge",
+                    from.0, node.0
+                ))
+            })
+    }
+
     /// Process a "look at node" event. Returns a collision edge if found.
     fn do_look_at_node_event(
         &mut self,
         node_idx: SearchNodeIdx,
-    ) -> SearchGraphEdge {
+    ) -> Result<SearchGraphEdge, MatchingError> {
         let (next_nb, next_time) = self.find_next_event(node_idx);
 
         if let Some(nb_i) = next_nb {
@@ -204,19 +225,18 @@ impl SearchFlooder {
                 match dst_opt {
                     None => {
                         // Boundary collision.
-                        return SearchGraphEdge {
+                        return Ok(SearchGraphEdge {
                             node: Some(node_idx),
                             neighbor_index: nb_i,
-                        };
+                        });
                     }
                     Some(dst_idx) => {
                         let dst_reached = self.graph.nodes[dst_idx.0 as usize]
                             .reached_from_source;
                         if dst_reached.is_none() {
                             // Explore the empty neighbor.
-                            let reverse_idx = self.graph.nodes
-                                [dst_idx.0 as usize]
-                                .index_of_neighbor(Some(node_idx));
+                            let reverse_idx =
+                                self.reverse_neighbor_index(dst_idx, node_idx)?;
                             self.explore_empty_node(dst_idx, reverse_idx);
                             // Revisit this node immediately.
                             let tracker = &mut self.graph.nodes
@@ -230,16 +250,16 @@ impl SearchFlooder {
                                 event,
                                 &mut self.queue,
                             );
-                            return SearchGraphEdge {
+                            return Ok(SearchGraphEdge {
                                 node: None,
                                 neighbor_index: NO_NEIGHBOR,
-                            };
+                            });
                         } else {
                             // Two-front collision.
-                            return SearchGraphEdge {
+                            return Ok(SearchGraphEdge {
                                 node: Some(node_idx),
                                 neighbor_index: nb_i,
-                            };
+                            });
                         }
                     }
                 }
@@ -256,22 +276,23 @@ impl SearchFlooder {
             }
         }
 
-        SearchGraphEdge {
+        Ok(SearchGraphEdge {
             node: None,
             neighbor_index: NO_NEIGHBOR,
-        }
+        })
     }
 
     // -- public API ---------------------------------------------------------
 
     /// Run bidirectional Dijkstra from `src` to `dst`.
     /// `dst` is `None` for boundary search.
-    /// Returns the collision edge.
+    /// Returns the collision edge, or an error if the search crosses an
+    /// edge the graph only lists in one direction.
     pub fn run_until_collision(
         &mut self,
         src: SearchNodeIdx,
         dst: Option<SearchNodeIdx>,
-    ) -> SearchGraphEdge {
+    ) -> Result<SearchGraphEdge, MatchingError> {
         match dst {
             None => {
                 self.target_type = TargetType::Boundary;
@@ -293,18 +314,18 @@ impl SearchFlooder {
                     |t| SearchEvent::LookAtNode { node, time: t },
                 );
                 if should_process {
-                    let edge = self.do_look_at_node_event(node);
+                    let edge = self.do_look_at_node_event(node)?;
                     if edge.node.is_some() {
-                        return edge;
+                        return Ok(edge);
                     }
                 }
             }
         }
 
-        SearchGraphEdge {
+        Ok(SearchGraphEdge {
             node: None,
             neighbor_index: NO_NEIGHBOR,
-        }
+        })
     }
 
     /// Trace back from a node to its source, collecting edges.
@@ -333,20 +354,36 @@ impl SearchFlooder {
 
     /// Iterate edges on the shortest path from `src` to `dst` (in order),
     /// calling `callback` with `(from: Option<SearchNodeIdx>, to: Option<SearchNodeIdx>, obs_mask)`.
+    ///
+    /// Fails with [`MatchingError::InvalidGraph`] if the graph has an edge
+    /// listed in only one direction's neighbors where the search or the
+    /// path needs the reverse; the search state is reset either way, though
+    /// `callback` may already have seen part of the path.
     pub fn iter_edges_on_shortest_path(
         &mut self,
         src: usize,
         dst: Option<usize>,
         mut callback: impl FnMut(Option<SearchNodeIdx>, Option<SearchNodeIdx>, ObsMask),
-    ) {
+    ) -> Result<(), MatchingError> {
+        self.num_searches += 1;
+        let result = self.emit_shortest_path(src, dst, &mut callback);
+        self.reset();
+        result
+    }
+
+    fn emit_shortest_path(
+        &mut self,
+        src: usize,
+        dst: Option<usize>,
+        callback: &mut impl FnMut(Option<SearchNodeIdx>, Option<SearchNodeIdx>, ObsMask),
+    ) -> Result<(), MatchingError> {
         let src_idx = SearchNodeIdx(src as u32);
         let dst_idx = dst.map(|d| SearchNodeIdx(d as u32));
 
-        let collision_edge = self.run_until_collision(src_idx, dst_idx);
+        let collision_edge = self.run_until_collision(src_idx, dst_idx)?;
 
         if collision_edge.node.is_none() {
-            self.reset();
-            return;
+            return Ok(());
         }
 
         let collision_node = collision_edge.node.unwrap();
@@ -376,17 +413,16 @@ impl SearchFlooder {
 
         if leads_to_src {
             // Reverse path2 (it goes collision->src, we want src->collision).
-            self.emit_reversed(&path2, &mut callback);
+            self.emit_reversed(&path2, callback)?;
             // Path1 goes collision->dst, emit in order.
-            self.emit_forward(&path1, &mut callback);
+            self.emit_forward(&path1, callback);
         } else {
             // Reverse path1 (it goes collision->src, we want src->collision).
-            self.emit_reversed(&path1, &mut callback);
+            self.emit_reversed(&path1, callback)?;
             // Path2 goes collision->dst, emit in order.
-            self.emit_forward(&path2, &mut callback);
+            self.emit_forward(&path2, callback);
         }
-
-        self.reset();
+        Ok(())
     }
 
     /// Emit edges in forward order (node -> neighbor).
@@ -406,11 +442,13 @@ impl SearchFlooder {
     }
 
     /// Emit edges in reversed order (neighbor -> node, traversed backwards).
+    /// Fails, having emitted the edges before it, on an edge whose reverse
+    /// is missing.
     fn emit_reversed(
         &self,
         edges: &[SearchGraphEdge],
         callback: &mut impl FnMut(Option<SearchNodeIdx>, Option<SearchNodeIdx>, ObsMask),
-    ) {
+    ) -> Result<(), MatchingError> {
         for e in edges.iter().rev() {
             let node_i = e.node.unwrap().0 as usize;
             let nb_opt = self.graph.nodes[node_i].neighbors[e.neighbor_index];
@@ -419,8 +457,7 @@ impl SearchFlooder {
             let to = e.node;
             // Find the reverse edge's observable.
             let obs = if let Some(nb_idx) = nb_opt {
-                let reverse_i = self.graph.nodes[nb_idx.0 as usize]
-                    .index_of_neighbor(e.node);
+                let reverse_i = self.reverse_neighbor_index(nb_idx, e.node.unwrap())?;
                 self.graph.nodes[nb_idx.0 as usize].neighbor_observables
                     [reverse_i]
             } else {
@@ -430,23 +467,25 @@ impl SearchFlooder {
             };
             callback(from, to, obs);
         }
+        Ok(())
     }
 
     /// Build a `CompressedEdge` for the shortest path between two nodes.
+    /// Fails as [`SearchFlooder::iter_edges_on_shortest_path`] does.
     pub fn find_shortest_path(
         &mut self,
         src: usize,
         dst: Option<usize>,
-    ) -> CompressedEdge {
+    ) -> Result<CompressedEdge, MatchingError> {
         let mut obs_mask: ObsMask = 0;
         self.iter_edges_on_shortest_path(src, dst, |_, _, obs| {
             obs_mask ^= obs;
-        });
-        CompressedEdge {
+        })?;
+        Ok(CompressedEdge {
             loc_from: Some(NodeIdx(src as u32)),
             loc_to: dst.map(|d| NodeIdx(d as u32)),
             obs_mask,
-        }
+        })
     }
 
     /// Reset the graph and queue for the next search.
@@ -458,4 +497,11 @@ impl SearchFlooder {
         self.queue.reset();
         self.target_type = TargetType::NoTarget;
     }
+
+    /// Approximate bytes held by the graph, queue and scratch buffers.
+    pub fn memory_footprint(&self) -> usize {
+        self.graph.memory_footprint()
+            + self.queue.memory_footprint()
+            + self.reached_nodes.capacity() * size_of::<SearchNodeIdx>()
+    }
 }
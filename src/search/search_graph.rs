@@ -34,12 +34,13 @@ impl SearchDetectorNode {
 
     /// Find the index of a neighbor by its `Option<SearchNodeIdx>`.
     pub fn index_of_neighbor(&self, target: Option<SearchNodeIdx>) -> usize {
-        for (k, n) in self.neighbors.iter().enumerate() {
-            if *n == target {
-                return k;
-            }
-        }
-        panic!("Failed to find neighbor");
+        self.find_neighbor(target).expect("Failed to find neighbor")
+    }
+
+    /// Like [`SearchDetectorNode::index_of_neighbor`], but `None` when
+    /// `target` is not a neighbor.
+    pub fn find_neighbor(&self, target: Option<SearchNodeIdx>) -> Option<usize> {
+        self.neighbors.iter().position(|n| *n == target)
     }
 
     /// Reset ephemeral Dijkstra state.
@@ -105,4 +106,18 @@ impl SearchGraph {
         self.nodes[u].neighbor_weights.insert(0, weight);
         self.nodes[u].neighbor_observables.insert(0, obs_mask);
     }
+
+    /// Approximate bytes held by the graph, from its `Vec` capacities.
+    pub fn memory_footprint(&self) -> usize {
+        let adjacency: usize = self
+            .nodes
+            .iter()
+            .map(|n| {
+                n.neighbors.capacity() * size_of::<Option<SearchNodeIdx>>()
+                    + n.neighbor_weights.capacity() * size_of::<Weight>()
+                    + n.neighbor_observables.capacity() * size_of::<ObsMask>()
+            })
+            .sum();
+        self.nodes.capacity() * size_of::<SearchDetectorNode>() + adjacency
+    }
 }
@@ -1,6 +1,7 @@
 use std::ops::{Index, IndexMut};
 
 /// Simple index-based arena allocator backed by a `Vec` and a free list.
+#[derive(Debug, Clone)]
 pub struct Arena<T> {
     items: Vec<T>,
     free_list: Vec<u32>,
@@ -100,6 +101,52 @@ impl<T: Default> Arena<T> {
         self.active == 0
     }
 
+    /// Whether slot `idx` is currently allocated.
+    pub fn is_live(&self, idx: u32) -> bool {
+        self.is_active.get(idx as usize).copied().unwrap_or(false)
+    }
+
+    /// Number of slots backing the arena, live or free.
+    pub fn num_slots(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Number of slots allocated at least once since the last recycle.
+    pub fn num_touched(&self) -> usize {
+        self.touched.len()
+    }
+
+    /// Drop free slots at the end of the arena and release their storage.
+    ///
+    /// Live slots never move, so existing indices stay valid and no
+    /// remapping is needed; free slots below the last live one are kept.
+    pub fn compact(&mut self) {
+        let new_len = self.is_active.iter().rposition(|&a| a).map_or(0, |i| i + 1);
+        if new_len == self.items.len() {
+            return;
+        }
+        self.items.truncate(new_len);
+        self.was_touched.truncate(new_len);
+        self.is_active.truncate(new_len);
+        self.free_list.retain(|&idx| (idx as usize) < new_len);
+        self.touched.retain(|&idx| (idx as usize) < new_len);
+        self.items.shrink_to_fit();
+        self.was_touched.shrink_to_fit();
+        self.is_active.shrink_to_fit();
+        self.free_list.shrink_to_fit();
+        self.touched.shrink_to_fit();
+    }
+
+    /// Approximate bytes held by the arena's own buffers, counting every
+    /// slot at `size_of::<T>()`. Heap data owned by the items is not
+    /// included.
+    pub fn memory_footprint(&self) -> usize {
+        self.items.capacity() * size_of::<T>()
+            + (self.free_list.capacity() + self.touched.capacity()) * size_of::<u32>()
+            + self.was_touched.capacity()
+            + self.is_active.capacity()
+    }
+
     /// Borrow the underlying items slice (needed for read-only access while mutating other fields).
     pub fn items(&self) -> &[T] {
         &self.items
@@ -40,6 +40,7 @@ pub trait HasTime {
 /// holds the most distant events.
 ///
 /// Invariant: `cur_time` only moves forward (monotonically).
+#[derive(Debug, Clone)]
 pub struct RadixHeapQueue<E: HasTime> {
     buckets: [Vec<E>; 33],
     pub cur_time: i64,
@@ -147,6 +148,11 @@ impl<E: HasTime> RadixHeapQueue<E> {
         self.clear();
         self.cur_time = 0;
     }
+
+    /// Approximate bytes held by the bucket buffers.
+    pub fn memory_footprint(&self) -> usize {
+        self.buckets.iter().map(|b| b.capacity() * size_of::<E>()).sum()
+    }
 }
 
 impl<E: HasTime> Default for RadixHeapQueue<E> {
@@ -0,0 +1,35 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+/// Deterministic xorshift64 generator, so randomized tests are reproducible.
+pub struct Rng(u64);
+
+impl Rng {
+    /// `seed` must be non-zero.
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Integer in `0..bound`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    /// Float in `[0, 1)`.
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Dense syndrome in which each of `num_detectors` detectors fires with
+/// probability `1 / one_in`.
+pub fn random_syndrome(rng: &mut Rng, num_detectors: usize, one_in: u64) -> Vec<u8> {
+    (0..num_detectors).map(|_| (rng.below(one_in) == 0) as u8).collect()
+}
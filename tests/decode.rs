@@ -1,4 +1,8 @@
-use rmatching::Matching;
+use rmatching::matcher::mwpm::MwpmStats;
+use rmatching::{Matching, MatchingError};
+
+mod common;
+use common::{random_syndrome, Rng};
 
 /// 3-node chain: D0 -- D1 -- D2, with L0 on the D0-D1 edge.
 /// Fire D0 and D1 => should predict L0 flipped.
@@ -119,3 +123,1559 @@ error(0.05) D2
     assert_eq!(prediction.len(), 1);
     assert_eq!(prediction[0], 1, "Expected L0 flipped from DEM decode");
 }
+
+/// Two corrections with equal primary weight: D0 to the boundary directly
+/// (flipping L0), or D0 -- D1 -- boundary. The secondary weight decides.
+#[test]
+fn decode_secondary_weight_breaks_ties() {
+    let build = |direct_secondary: f64, detour_secondary: f64| {
+        let mut m = Matching::new();
+        m.add_boundary_edge_with_secondary_weight(0, 2.0, direct_secondary, &[0], 0.1);
+        m.add_edge_with_secondary_weight(0, 1, 1.0, detour_secondary, &[], 0.1);
+        m.add_boundary_edge_with_secondary_weight(1, 1.0, detour_secondary, &[], 0.1);
+        m
+    };
+
+    let mut prefer_detour = build(3.0, 1.0);
+    assert_eq!(prefer_detour.decode(&[1, 0]), vec![0]);
+
+    let mut prefer_direct = build(1.0, 3.0);
+    assert_eq!(prefer_direct.decode(&[1, 0]), vec![1]);
+}
+
+/// Secondary weights never override a strictly smaller primary weight.
+#[test]
+fn decode_secondary_weight_does_not_override_primary() {
+    let mut m = Matching::new();
+    m.add_boundary_edge_with_secondary_weight(0, 2.0, 200.0, &[0], 0.1);
+    m.add_edge_with_secondary_weight(0, 1, 2.0, 0.0, &[], 0.1);
+    m.add_boundary_edge_with_secondary_weight(1, 1.0, 0.0, &[], 0.1);
+    assert_eq!(m.decode(&[1, 0]), vec![1]);
+}
+
+/// Between two negative-weight edges of equal primary weight, the one
+/// with the smaller secondary weight is the cheaper, as for positive ones.
+#[test]
+fn decode_secondary_weight_adds_cost_to_negative_edges() {
+    let build = |secondary_0: f64, secondary_1: f64| {
+        let mut m = Matching::new();
+        m.add_boundary_edge_with_secondary_weight(0, -1.0, secondary_0, &[0], 0.9);
+        m.add_boundary_edge_with_secondary_weight(0, -1.0, secondary_1, &[1], 0.9);
+        m
+    };
+    // D0 firing is explained by the cheaper edge on its own.
+    assert_eq!(build(1.0, 3.0).decode(&[1]), vec![1, 0]);
+    assert_eq!(build(3.0, 1.0).decode(&[1]), vec![0, 1]);
+    let (_, weight_low) = build(1.0, 3.0).decode_with_int_weight(&[1]);
+    let (_, weight_high) = build(3.0, 3.0).decode_with_int_weight(&[1]);
+    assert!(weight_low < weight_high);
+}
+
+/// A boundary edge flipping two observables at once flips both in the
+/// prediction.
+#[test]
+fn decode_multi_observable_boundary_edge() {
+    let mut m = Matching::from_dem(
+        "error(0.1) D0 L0 L1\n\
+         error(0.1) D0 D1\n\
+         error(0.1) D1\n",
+    )
+    .unwrap();
+    assert_eq!(m.decode(&[1, 0]), vec![1, 1]);
+    assert_eq!(m.decode(&[0, 1]), vec![0, 0]);
+    assert_eq!(m.decode(&[1, 1]), vec![0, 0]);
+}
+
+/// Torus detectors are indexed `y * width + x`; L0 counts crossings of the
+/// vertical seam and L1 of the horizontal one.
+#[test]
+fn decode_periodic_grid() {
+    let mut m = Matching::from_grid_periodic(5, 5, 0.1).unwrap();
+
+    // Single error on the horizontal edge (4,2)-(0,2) crosses the seam.
+    let mut syndrome = vec![0u8; 25];
+    syndrome[2 * 5 + 4] = 1;
+    syndrome[2 * 5] = 1;
+    assert_eq!(m.decode(&syndrome), vec![1, 0]);
+
+    // Error chain (1,0)-(1,1)-(1,2)-(1,3) is three steps long; the shorter
+    // correction wraps around through y = 4 -> 0 and completes a logical L1.
+    let mut syndrome = vec![0u8; 25];
+    syndrome[1] = 1;
+    syndrome[3 * 5 + 1] = 1;
+    assert_eq!(m.decode(&syndrome), vec![0, 1]);
+
+    // Two separated pairs pair up internally without touching either seam.
+    let mut syndrome = vec![0u8; 25];
+    syndrome[6] = 1;
+    syndrome[7] = 1;
+    syndrome[18] = 1;
+    syndrome[23] = 1;
+    assert_eq!(m.decode(&syndrome), vec![0, 0]);
+}
+
+#[test]
+fn decode_periodic_grid_rejects_small_sizes() {
+    assert!(Matching::from_grid_periodic(2, 5, 0.1).is_err());
+    assert!(Matching::from_grid_periodic(5, 1, 0.1).is_err());
+}
+
+#[test]
+fn decode_streaming_matches_buffered_paths() {
+    let dem = concat!(
+        "error(0.1) D0 D1 L0\n",
+        "error(0.1) D1 D2\n",
+        "error(0.1) D2 D3 L1\n",
+        "error(0.1) D3 D4\n",
+        "error(0.1) D4 D0\n",
+        "error(0.1) D0 D5\n",
+        "error(0.05) D5\n",
+    );
+    let mut m = Matching::from_dem(dem).unwrap();
+    for syndrome in [
+        vec![1u8, 1, 1, 0, 0, 0],
+        vec![1, 1, 1, 1, 1, 1],
+        vec![0, 0, 1, 1, 0, 0],
+        vec![0, 0, 0, 0, 0, 0],
+    ] {
+        let mut obs_mask = 0u64;
+        let mut pairs = Vec::new();
+        m.decode_streaming(&syndrome, |a, b, mask, _| {
+            obs_mask ^= mask;
+            pairs.push(if b == -1 || (a != -1 && a <= b) { (a, b) } else { (b, a) });
+        });
+        pairs.sort();
+
+        let prediction = m.decode(&syndrome);
+        let expected_mask = prediction
+            .iter()
+            .enumerate()
+            .fold(0u64, |acc, (i, &bit)| acc | ((bit as u64) << i));
+        assert_eq!(obs_mask, expected_mask);
+        assert_eq!(pairs, m.decode_to_edges(&syndrome));
+    }
+}
+
+#[test]
+fn decode_to_compressed_edges_obs_masks_xor_to_prediction() {
+    let mut m = Matching::from_grid_periodic(5, 5, 0.1).unwrap();
+    let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+    for _ in 0..50 {
+        let mut syndrome = vec![0u8; 25];
+        for _ in 0..4 {
+            syndrome[rng.below(25) as usize] ^= 1;
+        }
+        let edges = m.decode_to_compressed_edges(&syndrome);
+        let obs_mask = edges.iter().fold(0u64, |acc, e| acc ^ e.obs_mask);
+        let expected_mask = m
+            .decode(&syndrome)
+            .iter()
+            .enumerate()
+            .fold(0u64, |acc, (i, &bit)| acc | ((bit as u64) << i));
+        assert_eq!(obs_mask, expected_mask);
+
+        let mut endpoints: Vec<usize> = edges
+            .iter()
+            .flat_map(|e| [e.loc_from, e.loc_to])
+            .flatten()
+            .map(|n| n.0 as usize)
+            .collect();
+        endpoints.sort();
+        let fired: Vec<usize> = (0..25).filter(|&i| syndrome[i] == 1).collect();
+        assert_eq!(endpoints, fired);
+    }
+}
+
+#[test]
+fn weight_resolution_warnings_flags_edges_rounding_to_zero() {
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 1.0e8, &[], 0.1);
+    m.add_edge(1, 2, 1.0e-3, &[0], 0.1);
+    m.add_edge(2, 3, 0.0, &[], 0.1);
+    m.add_boundary_edge(3, 2.5e8, &[], 0.1);
+    assert_eq!(m.weight_resolution_warnings(), vec![1]);
+
+    let mut fine = Matching::new();
+    fine.add_edge(0, 1, 1.5, &[], 0.1);
+    fine.add_boundary_edge(1, 0.25, &[0], 0.1);
+    assert!(fine.weight_resolution_warnings().is_empty());
+}
+
+/// Distance-5 repetition code with explicit boundary nodes 4 (left) and 5
+/// (right). Defining L0 as the parity of the left boundary node must agree
+/// with labelling the left boundary edge; the parity of {D0, D1} flips on
+/// the edges leaving that set, 4-0 and 1-2. Edge labels for detector-defined
+/// observables are ignored.
+#[test]
+fn decode_detector_defined_observable() {
+    let build = |left_obs: &[usize], middle_obs: &[usize]| {
+        let mut m = Matching::new();
+        m.add_edge(4, 0, 1.0, left_obs, 0.1);
+        m.add_edge(0, 1, 1.0, &[], 0.1);
+        m.add_edge(1, 2, 1.0, middle_obs, 0.1);
+        m.add_edge(2, 3, 1.0, &[], 0.1);
+        m.add_edge(3, 5, 1.0, &[], 0.1);
+        m.set_boundary(&[4, 5]);
+        m
+    };
+    let mut edge_based = build(&[0, 1], &[1]);
+    let mut detector_based = build(&[1], &[0]);
+    detector_based.set_detector_observable(0, &[4]);
+    detector_based.set_detector_observable(1, &[0, 1]);
+
+    for bits in 0..16u8 {
+        let syndrome: Vec<u8> = (0..6).map(|i| if i < 4 { (bits >> i) & 1 } else { 0 }).collect();
+        assert_eq!(
+            detector_based.decode(&syndrome),
+            edge_based.decode(&syndrome),
+            "syndrome {syndrome:?}"
+        );
+    }
+}
+
+#[test]
+fn decode_observable_order() {
+    use rmatching::driver::decoding::ObservableOrder;
+
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 1.0, &[0], 0.1);
+    m.add_edge(1, 2, 1.0, &[2], 0.1);
+    m.add_boundary_edge(0, 5.0, &[], 0.1);
+    m.add_boundary_edge(2, 1.0, &[1], 0.1);
+
+    // D0 -- D1 flips L0; D2 -- boundary flips L1.
+    let syndrome = [1u8, 1, 1];
+    assert_eq!(m.decode(&syndrome), vec![1, 1, 0]);
+
+    m.set_observable_order(ObservableOrder::MsbFirst);
+    assert_eq!(m.decode(&syndrome), vec![0, 1, 1]);
+    assert_eq!(m.decode_batch(&[syndrome.to_vec()]), vec![vec![0, 1, 1]]);
+
+    m.set_observable_order(ObservableOrder::Custom(vec![1, 2, 7]));
+    assert_eq!(m.decode(&syndrome), vec![1, 0, 0]);
+
+    m.set_observable_order(ObservableOrder::LsbFirst);
+    assert_eq!(m.decode(&syndrome), vec![1, 1, 0]);
+}
+
+#[test]
+fn decode_to_fault_ids_follows_shortest_paths() {
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 1.0, &[0], 0.1); // fault 0
+    m.add_edge(0, 1, 1.0, &[], 0.1); // fault 1
+    m.add_edge(1, 2, 1.0, &[], 0.1); // fault 2
+    m.add_edge(2, 3, 1.0, &[], 0.1); // fault 3
+    m.add_boundary_edge(3, 1.0, &[], 0.1); // fault 4
+
+    assert_eq!(m.decode_to_fault_ids(&[1, 0, 0, 1]), vec![0, 4]);
+    assert_eq!(m.decode_to_fault_ids(&[0, 1, 0, 1]), vec![2, 3]);
+    assert_eq!(m.decode_to_fault_ids(&[0, 0, 0, 0]), Vec::<usize>::new());
+}
+
+#[test]
+fn decode_to_fault_id_groups_reports_parallel_edges() {
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 2.0, &[0], 0.1); // fault 0
+    m.add_edge(0, 1, 2.0, &[], 0.1); // fault 1
+    m.add_edge(0, 1, 1.0, &[], 0.1); // fault 2, lighter than fault 1
+    m.add_boundary_edge(1, 1.0, &[], 0.1); // fault 3
+    m.add_boundary_edge(0, 1.0, &[0], 0.1); // fault 4, lighter than fault 0
+    m.add_edge(1, 0, 1.0, &[], 0.1); // fault 5, ties fault 2
+
+    assert_eq!(m.decode_to_fault_ids(&[1, 1]), vec![2]);
+    assert_eq!(m.decode_to_fault_id_groups(&[1, 1]), vec![vec![2, 1, 5]]);
+    assert_eq!(m.decode_to_fault_ids(&[1, 0]), vec![4]);
+    assert_eq!(m.decode_to_fault_id_groups(&[1, 0]), vec![vec![4, 0]]);
+    assert_eq!(m.decode_to_fault_id_groups(&[0, 1]), vec![vec![3]]);
+}
+
+/// Repetition code B - D0 - D1 - D2 - D3 - B where the middle edge is far
+/// more likely than the rest. Under noise sampled with those probabilities
+/// the decoder should blame the middle edge more often than any other.
+#[test]
+fn edge_match_frequency_favours_biased_edge() {
+    let probabilities = [0.05, 0.05, 0.4, 0.05, 0.05];
+    let mut dem = String::new();
+    for (i, p) in probabilities.iter().enumerate() {
+        let detectors = match i {
+            0 => "D0 L0".to_string(),
+            4 => "D3".to_string(),
+            _ => format!("D{} D{}", i - 1, i),
+        };
+        dem.push_str(&format!("error({p}) {detectors}\n"));
+    }
+    let mut m = Matching::from_dem(&dem).unwrap();
+
+    let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+    let mut sample = || rng.uniform();
+    let syndromes: Vec<Vec<u8>> = (0..500)
+        .map(|_| {
+            let mut syndrome = vec![0u8; 4];
+            for (i, &p) in probabilities.iter().enumerate() {
+                if sample() < p {
+                    if i > 0 {
+                        syndrome[i - 1] ^= 1;
+                    }
+                    if i < 4 {
+                        syndrome[i] ^= 1;
+                    }
+                }
+            }
+            syndrome
+        })
+        .collect();
+
+    let counts = m.edge_match_frequency(&syndromes);
+    assert_eq!(counts.len(), 5);
+    for (i, &count) in counts.iter().enumerate() {
+        if i != 2 {
+            assert!(counts[2] > 2 * count, "counts = {counts:?}");
+        }
+    }
+}
+
+/// A triangle of zero-weight edges must not stall the flooder.
+#[test]
+fn decode_zero_weight_triangle() {
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 0.0, &[0], 0.5);
+    m.add_edge(1, 2, 0.0, &[], 0.5);
+    m.add_edge(2, 0, 0.0, &[], 0.5);
+    m.add_boundary_edge(0, 3.0, &[], 0.1);
+
+    assert_eq!(m.decode(&[1, 1, 0]), vec![1]);
+    assert_eq!(m.decode(&[0, 1, 1]), vec![0]);
+    assert_eq!(m.decode(&[1, 0, 1]), vec![0]);
+    assert_eq!(m.decode(&[1, 1, 1]).len(), 1);
+}
+
+#[test]
+fn decode_detection_events_matches_dense_decode() {
+    let dem = concat!(
+        "error(0.1) D0 D1 L0\n",
+        "error(0.1) D1 D2\n",
+        "error(0.1) D2 D3 L1\n",
+        "error(0.1) D3 D4\n",
+        "error(0.1) D4 D0\n",
+        "error(0.1) D0 D5\n",
+        "error(0.05) D5\n",
+    );
+    let mut m = Matching::from_dem(dem).unwrap();
+    for bits in 0..64u8 {
+        let syndrome: Vec<u8> = (0..6).map(|i| (bits >> i) & 1).collect();
+        let mut events: Vec<usize> = (0..6).filter(|&i| syndrome[i] != 0).rev().collect();
+        assert_eq!(m.decode_detection_events(&events), m.decode(&syndrome));
+        // Repeats and out-of-range indices are ignored.
+        events.extend_from_slice(&events.clone());
+        events.push(100);
+        assert_eq!(m.decode_detection_events(&events), m.decode(&syndrome));
+    }
+}
+
+#[test]
+fn decode_detection_events_repeated_indices() {
+    use rmatching::driver::decoding::RepeatedEvents;
+
+    // B -L0- D0 -- D1 -- D2 -L1- B
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 1.0, &[0], 0.1);
+    m.add_edge(0, 1, 3.0, &[], 0.1);
+    m.add_edge(1, 2, 3.0, &[], 0.1);
+    m.add_boundary_edge(2, 1.0, &[1], 0.1);
+
+    // By default a repeat fires the detector once.
+    assert_eq!(m.decode_detection_events(&[0, 2, 0]), vec![1, 1]);
+    assert_eq!(m.decode_detection_events(&[2, 0, 0, 0, 0]), vec![1, 1]);
+
+    m.set_repeated_events(RepeatedEvents::Parity);
+    // An even number of listings cancels, an odd number fires once.
+    assert_eq!(m.decode_detection_events(&[0, 2, 0]), vec![0, 1]);
+    assert_eq!(m.decode_detection_events(&[2, 0, 0, 0, 0]), vec![0, 1]);
+    assert_eq!(m.decode_detection_events(&[0, 2, 0, 2, 0]), vec![1, 0]);
+    assert_eq!(m.decode_detection_events(&[1, 1, 0, 0]), vec![0, 0]);
+    // decode_ordered follows the same setting.
+    assert_eq!(m.decode_ordered(&[0, 2, 0]), vec![0, 1]);
+    assert_eq!(m.decode_ordered(&[0, 2, 0, 2, 0]), vec![1, 0]);
+    assert_eq!(m.decode_ordered(&[1, 1, 0, 0]), vec![0, 0]);
+
+    m.set_repeated_events(RepeatedEvents::Dedup);
+    assert_eq!(m.decode_ordered(&[0, 2, 0]), vec![1, 1]);
+}
+
+/// On a uniform 4-cycle with all detectors firing, matching {0-1, 2-3}
+/// flips L0 and matching {1-2, 3-0} does not; both have the same weight.
+#[test]
+fn decode_tie_break_seed() {
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 1.0, &[0], 0.1);
+    m.add_edge(1, 2, 1.0, &[], 0.1);
+    m.add_edge(2, 3, 1.0, &[], 0.1);
+    m.add_edge(3, 0, 1.0, &[], 0.1);
+    let syndrome = [1u8, 1, 1, 1];
+
+    let mut outcomes = Vec::new();
+    for seed in 0..16u64 {
+        m.set_tie_break_seed(Some(seed));
+        let first = m.decode(&syndrome);
+        assert_eq!(m.decode(&syndrome), first, "seed {seed} not reproducible");
+        outcomes.push(first[0]);
+    }
+    assert!(outcomes.contains(&0) && outcomes.contains(&1), "{outcomes:?}");
+
+    m.set_tie_break_seed(None);
+    let default = m.decode(&syndrome);
+    assert_eq!(m.decode(&syndrome), default);
+}
+
+#[test]
+fn boundary_to_boundary_edge_keeps_its_observable() {
+    // D0 - D1 chain, boundaries B2 and B3 at either end, plus a direct
+    // B2 - B3 edge flipping L1 that no detector can see.
+    let build = |bb_weight: f64| {
+        let mut m = Matching::new();
+        m.add_edge(2, 0, 1.0, &[0], 0.1);
+        m.add_edge(0, 1, 1.0, &[], 0.1);
+        m.add_edge(1, 3, 1.0, &[], 0.1);
+        m.add_edge(2, 3, bb_weight, &[1], 0.1);
+        m.add_boundary_edge(3, 1.0, &[], 0.1);
+        m.set_boundary(&[2, 3]);
+        m
+    };
+
+    let mut positive = build(2.0);
+    assert_eq!(positive.boundary_to_boundary_observable_edges(), vec![3]);
+    assert_eq!(positive.decode(&[0, 0, 0, 0]), vec![0, 0]);
+    assert_eq!(positive.decode(&[1, 0, 0, 0]), vec![1, 0]);
+
+    // A negative-weight one is always part of the correction.
+    let mut negative = build(-2.0);
+    assert_eq!(negative.boundary_to_boundary_observable_edges(), vec![3]);
+    assert_eq!(negative.decode(&[0, 0, 0, 0]), vec![0, 1]);
+    assert_eq!(negative.decode(&[1, 0, 0, 0]), vec![1, 1]);
+    assert_eq!(negative.decode_to_fault_ids(&[0, 0, 0, 0]), vec![3]);
+}
+
+#[test]
+fn decode_to_edges_sorted_is_independent_of_insertion_order() {
+    // Chain D0 - ... - D5 with boundary edges at both ends.
+    let mut edges: Vec<(usize, Option<usize>)> = (0..5).map(|i| (i, Some(i + 1))).collect();
+    edges.push((0, None));
+    edges.push((5, None));
+    let build = |edges: &[(usize, Option<usize>)]| {
+        let mut m = Matching::new();
+        for &(a, b) in edges {
+            match b {
+                Some(b) => m.add_edge(a, b, 1.0, &[], 0.1),
+                None => m.add_boundary_edge(a, 1.0, &[], 0.1),
+            }
+        }
+        m
+    };
+
+    let syndrome = [1u8, 0, 1, 1, 0, 1];
+    let forward = build(&edges).decode_to_edges_sorted(&syndrome);
+    edges.reverse();
+    let reversed = build(&edges).decode_to_edges_sorted(&syndrome);
+    assert_eq!(forward, vec![(2, 3), (0, -1), (5, -1)]);
+    assert_eq!(forward, reversed);
+}
+
+/// A matched pair freed by a blossom shatter can already touch a growing
+/// region that was skipped while the pair shrank inside the blossom; the
+/// shatter must reschedule every node of the pair so that collision fires.
+#[test]
+fn decode_collides_with_region_freed_by_blossom_shatter() {
+    let mut m = Matching::new();
+    for (a, b, w) in [
+        (0, 1, 1), (1, 2, 6), (2, 3, 1), (3, 4, 6), (4, 5, 2), (5, 6, 1), (6, 7, 6),
+        (7, 8, 1), (8, 9, 5), (9, 10, 4), (10, 11, 5), (11, 12, 2), (12, 13, 5),
+        (12, 3, 1), (6, 13, 1), (0, 11, 6), (3, 11, 3), (6, 0, 5), (12, 13, 2),
+    ] {
+        m.add_edge(a, b, w as f64, &[], 0.1);
+    }
+    m.add_boundary_edge(6, 4.0, &[], 0.1);
+    m.add_boundary_edge(7, 9.0, &[], 0.1);
+
+    let syndrome = [0u8, 1, 0, 0, 0, 1, 1, 1, 1, 1, 0, 1, 1, 1];
+    let mut total = 0;
+    m.decode_streaming(&syndrome, |_, _, _, w| total += w);
+    // Optimum: 1-B (via 0-6), 5-6, 7-8, 9-11 and 12-13, weight 23,
+    // doubled by the discretization.
+    assert_eq!(total, 2 * 23);
+}
+
+#[test]
+fn decode_to_matched_with_paths_connects_each_pair() {
+    let mut m = Matching::new();
+    let mut endpoints = Vec::new();
+    // 4x4 grid of detectors with boundary edges along the left column.
+    for r in 0..4 {
+        for c in 0..4 {
+            let n = r * 4 + c;
+            if c + 1 < 4 {
+                m.add_edge(n, n + 1, 1.0, &[], 0.1);
+                endpoints.push((n, Some(n + 1)));
+            }
+            if r + 1 < 4 {
+                m.add_edge(n, n + 4, 1.0, &[], 0.1);
+                endpoints.push((n, Some(n + 4)));
+            }
+        }
+        m.add_boundary_edge(r * 4, 1.0, &[0], 0.1);
+        endpoints.push((r * 4, None));
+    }
+
+    let mut syndrome = vec![0u8; 16];
+    for d in [1, 3, 6, 14, 11] {
+        syndrome[d] = 1;
+    }
+    let matches = m.decode_to_matched_with_paths(&syndrome);
+    assert_eq!(
+        matches.iter().map(|(a, b, _)| (*a, *b)).collect::<Vec<_>>(),
+        m.decode_to_edges(&syndrome)
+    );
+    for (a, b, path) in matches {
+        // Walk the path from `a`; it must end at `b` (or the boundary).
+        let mut at = Some(a as usize);
+        for &id in &path {
+            let (n1, n2) = endpoints[id];
+            at = if at == Some(n1) {
+                n2
+            } else {
+                assert_eq!(at, n2, "fault {id} does not continue the path");
+                Some(n1)
+            };
+        }
+        let end = if b < 0 { None } else { Some(b as usize) };
+        assert_eq!(at, end);
+    }
+}
+
+#[test]
+fn decode_shared_across_threads_matches_decode() {
+    let mut m = Matching::from_grid_periodic(6, 6, 0.05).unwrap();
+    let mut rng = Rng::new(0x5851_f42d_4c95_7f2d);
+    let syndromes: Vec<Vec<u8>> = (0..64)
+        .map(|_| {
+            let mut syndrome = vec![0u8; 36];
+            for _ in 0..3 {
+                // Flip both ends of a random horizontal edge.
+                let n = rng.below(36) as usize;
+                syndrome[n] ^= 1;
+                syndrome[n / 6 * 6 + (n + 1) % 6] ^= 1;
+            }
+            syndrome
+        })
+        .collect();
+    let expected = m.decode_batch(&syndromes);
+
+    let shared = &m;
+    std::thread::scope(|scope| {
+        for t in 0..4 {
+            let (syndromes, expected) = (&syndromes, &expected);
+            scope.spawn(move || {
+                for i in (t..syndromes.len()).step_by(4) {
+                    assert_eq!(shared.decode_shared(&syndromes[i]), expected[i]);
+                }
+            });
+        }
+    });
+
+    // Changing the graph rebuilds this thread's cached solver.
+    assert_eq!(m.decode_shared(&syndromes[0]), expected[0]);
+    m.add_edge(0, 1, 0.1, &[0, 1], 0.1);
+    let mut syndrome = vec![0u8; 36];
+    syndrome[0] = 1;
+    syndrome[1] = 1;
+    assert_eq!(m.decode_shared(&syndrome), vec![1, 1]);
+    assert_eq!(m.decode(&syndrome), vec![1, 1]);
+}
+
+#[test]
+fn invalid_error_probability_is_ignored_by_default() {
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 1.0, &[0], 2.0);
+    assert_eq!(m.decode(&[1]), vec![1]);
+}
+
+#[test]
+#[should_panic(expected = "edge 1 has error probability 2 outside [0, 1]")]
+fn invalid_error_probability_panics_when_required() {
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 1.0, &[], 0.1);
+    m.add_boundary_edge(0, 1.0, &[0], 2.0);
+    assert_eq!(m.decode(&[1, 0]), vec![1]);
+    m.set_require_valid_probabilities(true);
+    m.decode(&[1, 0]);
+}
+
+#[test]
+fn invalid_error_probability_is_reported_when_required() {
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 1.0, &[], 0.1);
+    m.add_boundary_edge(0, 1.0, &[0], 2.0);
+    m.set_require_valid_probabilities(true);
+    let err = MatchingError::InvalidProbability { edge: 1, probability: 2.0 };
+    assert_eq!(m.try_commit(), Err(err.clone()));
+    assert_eq!(m.try_decode(&[1, 0]), Err(err.clone()));
+    assert!(matches!(m.try_context(), Err(e) if e == err));
+
+    m.set_require_valid_probabilities(false);
+    assert_eq!(m.try_decode(&[1, 0]), Ok(vec![1]));
+}
+
+#[test]
+fn weight_horizon_is_reported_before_decoding() {
+    // Every edge discretizes to the maximum weight, so the far end of the
+    // chain is too far from the boundary for the event queue.
+    let chain = |len: usize| {
+        let mut m = Matching::new();
+        m.add_boundary_edge(0, 1.5, &[0], 0.1);
+        for i in 0..len - 1 {
+            m.add_edge(i, i + 1, 1.5, &[], 0.1);
+        }
+        m
+    };
+    let mut m = chain(100);
+    assert!(matches!(m.try_commit(), Err(MatchingError::WeightHorizonExceeded { .. })));
+    assert!(matches!(
+        m.try_decode(&[1; 100]),
+        Err(MatchingError::WeightHorizonExceeded { .. })
+    ));
+    assert!(m.try_context().is_err());
+
+    let mut m = chain(30);
+    assert_eq!(m.try_commit(), Ok(true));
+    assert_eq!(m.try_commit(), Ok(false));
+    let mut syndrome = vec![0u8; 30];
+    syndrome[29] = 1;
+    assert_eq!(m.try_decode(&syndrome), Ok(vec![1]));
+}
+
+#[test]
+fn fewer_weight_levels_decode_a_long_repetition_code() {
+    let n = 200;
+    let mut dem = String::from("error(0.1) D0 L0\n");
+    for i in 0..n - 1 {
+        dem.push_str(&format!("error(0.1) D{i} D{}\n", i + 1));
+    }
+    dem.push_str(&format!("error(0.1) D{}\n", n - 1));
+
+    // Parsing succeeds; the horizon is only checked once a solver is built.
+    let mut m = Matching::from_dem(&dem).unwrap();
+    let mut syndrome = vec![0u8; n];
+    syndrome[50] = 1;
+    let err = m.try_decode(&syndrome).unwrap_err();
+    assert!(matches!(err, MatchingError::WeightHorizonExceeded { .. }), "{err:?}");
+    assert!(err.to_string().contains("set_num_distinct_weights"), "{err}");
+
+    m.set_num_distinct_weights(1 << 16);
+    assert_eq!(m.try_decode(&syndrome), Ok(vec![1]));
+    syndrome[50] = 0;
+    syndrome[150] = 1;
+    assert_eq!(m.try_decode(&syndrome), Ok(vec![0]));
+}
+
+#[test]
+fn last_decode_stats_reports_blossom_size_and_tree_depth() {
+    // Firing every detector of an odd ring closes the whole ring into one
+    // blossom, after the tree has grown halfway round it.
+    let n = 9;
+    let mut m = Matching::new();
+    for i in 0..n {
+        m.add_edge(i, (i + 1) % n, 1.0, &[], 0.1);
+    }
+    m.add_boundary_edge(0, 100.0, &[], 0.1);
+
+    m.decode(&vec![1; n]);
+    let stats = m.last_decode_stats();
+    assert_eq!(stats.max_blossom_size, n);
+    assert_eq!(stats.max_tree_depth, (n - 1) / 2);
+
+    // Statistics cover only the latest decode.
+    m.decode(&vec![0; n]);
+    assert_eq!(m.last_decode_stats(), MwpmStats::default());
+}
+
+#[test]
+fn decode_with_event_count_grows_with_syndrome_difficulty() {
+    let n = 9;
+    let mut m = Matching::new();
+    for i in 0..n {
+        m.add_edge(i, (i + 1) % n, 1.0, &[0], 0.1);
+    }
+    m.add_boundary_edge(0, 100.0, &[], 0.1);
+
+    let (prediction, events) = m.decode_with_event_count(&vec![0; n]);
+    assert_eq!((prediction, events), (vec![0], 0));
+
+    let mut pair = vec![0; n];
+    pair[3] = 1;
+    pair[4] = 1;
+    let (prediction, pair_events) = m.decode_with_event_count(&pair);
+    assert_eq!(prediction, m.decode(&pair));
+    assert!(pair_events > 0);
+
+    // The whole ring forms a blossom that must then reach the boundary.
+    let (prediction, ring_events) = m.decode_with_event_count(&vec![1; n]);
+    assert_eq!(prediction, m.decode(&vec![1; n]));
+    assert!(ring_events > pair_events, "{ring_events} <= {pair_events}");
+    assert_eq!(m.last_decode_stats().num_events, ring_events);
+}
+
+#[test]
+fn decode_with_match_count_counts_pairs_and_boundary_matches() {
+    // B -L0- D0 - D1 - D2 - D3 - D4 - D5 - B
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 1.0, &[0], 0.1);
+    for i in 0..5 {
+        m.add_edge(i, i + 1, 1.0, &[], 0.1);
+    }
+    m.add_boundary_edge(5, 1.0, &[], 0.1);
+
+    assert_eq!(m.decode_with_match_count(&[0; 6]), (vec![0], 0));
+    // Two separate pairs.
+    let syndrome = [1, 1, 0, 0, 1, 1];
+    assert_eq!(m.decode_with_match_count(&syndrome), (m.decode(&syndrome), 2));
+    // One pair and one boundary match.
+    let syndrome = [1, 0, 1, 1, 0, 0];
+    assert_eq!(m.decode_with_match_count(&syndrome), (vec![1], 2));
+    assert_eq!(m.decode_to_edges(&syndrome).len(), 2);
+}
+
+#[test]
+fn decode_with_match_count_counts_every_edge_of_a_long_match() {
+    // B -L0- D0 - D1 - D2 - D3 - D4 - D5 - B
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 10.0, &[0], 0.1);
+    for i in 0..5 {
+        m.add_edge(i, i + 1, 1.0, &[], 0.1);
+    }
+    m.add_boundary_edge(5, 10.0, &[], 0.1);
+
+    // One match spanning three edges.
+    let syndrome = [0, 1, 0, 0, 1, 0];
+    assert_eq!(m.decode_to_edges(&syndrome), vec![(1, 4)]);
+    assert_eq!(m.decode_with_match_count(&syndrome), (vec![0], 3));
+    // A one-edge pair plus a three-edge pair.
+    let syndrome = [1, 1, 1, 0, 0, 1];
+    assert_eq!(m.decode_with_match_count(&syndrome), (vec![0], 4));
+}
+
+#[test]
+fn decode_with_match_count_includes_negative_edges() {
+    // B -3- D0 -(-2)- D1 -3- B
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 3.0, &[], 0.1);
+    m.add_edge(0, 1, -2.0, &[0], 0.9);
+    m.add_boundary_edge(1, 3.0, &[], 0.1);
+
+    for syndrome in [[0, 0], [1, 1], [1, 0], [0, 1]] {
+        let (prediction, count) = m.decode_with_match_count(&syndrome);
+        assert_eq!(prediction, m.decode(&syndrome), "{syndrome:?}");
+        assert_eq!(count, m.decode_to_fault_ids(&syndrome).len(), "{syndrome:?}");
+    }
+    assert_eq!(m.decode_with_match_count(&[0, 0]), (vec![0], 0));
+    assert_eq!(m.decode_with_match_count(&[1, 1]), (vec![1], 1));
+}
+
+#[test]
+fn decode_packed_batch_matches_decode_batch() {
+    // 5x3 grid of detectors (not a multiple of 8) with boundary edges on
+    // the left and right, L0 on the left column and L1 on a middle edge,
+    // plus one negative-weight edge.
+    let (width, height) = (5, 3);
+    let num_detectors = width * height;
+    let mut m = Matching::new();
+    for y in 0..height {
+        for x in 0..width {
+            let d = y * width + x;
+            if x + 1 < width {
+                let obs: &[usize] = if x == 2 && y == 1 { &[1] } else { &[] };
+                m.add_edge(d, d + 1, 1.0 + (d % 3) as f64, obs, 0.1);
+            }
+            if y + 1 < height {
+                m.add_edge(d, d + width, 1.5, &[], 0.1);
+            }
+        }
+        m.add_boundary_edge(y * width, 1.0, &[0], 0.1);
+        m.add_boundary_edge(y * width + width - 1, 1.0, &[], 0.1);
+    }
+    m.add_edge(0, 1, -0.5, &[1], 0.6);
+
+    let mut rng = Rng::new(0x9e37_79b9_7f4a_7c15);
+    let num_shots = 200;
+    let syndromes: Vec<Vec<u8>> = (0..num_shots)
+        .map(|_| random_syndrome(&mut rng, num_detectors, 5))
+        .collect();
+    let shot_bytes = num_detectors.div_ceil(8);
+    let mut packed = vec![0u8; shot_bytes * num_shots];
+    for (shot, syndrome) in syndromes.iter().enumerate() {
+        for (d, &bit) in syndrome.iter().enumerate() {
+            packed[shot * shot_bytes + d / 8] |= bit << (d % 8);
+        }
+    }
+
+    let expected: Vec<u64> = m
+        .decode_batch(&syndromes)
+        .iter()
+        .map(|p| p.iter().enumerate().map(|(i, &b)| (b as u64) << i).sum())
+        .collect();
+    assert_eq!(
+        m.decode_packed_batch(&packed, num_shots, num_detectors).unwrap(),
+        expected
+    );
+
+    let err = m
+        .decode_packed_batch(&packed[1..], num_shots, num_detectors)
+        .unwrap_err();
+    assert!(matches!(err, MatchingError::InvalidSyndrome(_)), "{err:?}");
+    assert!(err.to_string().contains("bytes"), "{err}");
+}
+
+#[test]
+fn boundary_weight_multiplier_discourages_boundary_matches() {
+    // D0 -3- D1 -3- D2, each with a boundary edge of weight 1; L0 is on
+    // D1's boundary edge.
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 3.0, &[], 0.1);
+    m.add_edge(1, 2, 3.0, &[], 0.1);
+    m.add_boundary_edge(0, 1.0, &[], 0.1);
+    m.add_boundary_edge(1, 1.0, &[0], 0.1);
+    m.add_boundary_edge(2, 1.0, &[], 0.1);
+
+    // Every event goes to the boundary.
+    assert_eq!(
+        m.decode_to_edges_sorted(&[1, 1, 1]),
+        vec![(0, -1), (1, -1), (2, -1)]
+    );
+    assert_eq!(m.decode(&[1, 1, 1]), vec![1]);
+
+    // With boundary edges at weight 5 one internal pair is cheaper, leaving
+    // only the boundary match the odd parity forces.
+    m.set_boundary_weight_multiplier(5.0);
+    let edges = m.decode_to_edges_sorted(&[1, 1, 1]);
+    assert_eq!(edges.len(), 2);
+    assert_eq!(edges.iter().filter(|&&(_, b)| b < 0).count(), 1);
+    assert_eq!(m.decode(&[1, 1, 1]), vec![0]);
+
+    m.set_boundary_weight_multiplier(1.0);
+    assert_eq!(m.decode(&[1, 1, 1]), vec![1]);
+}
+
+#[test]
+fn memory_footprint_grows_with_edges_and_cache() {
+    let mut m = Matching::new();
+    let mut previous = m.memory_footprint();
+    for i in 0..50 {
+        m.add_edge(i, i + 1, 1.0, &[0], 0.1);
+        let footprint = m.memory_footprint();
+        assert!(footprint >= previous, "{footprint} < {previous} after edge {i}");
+        previous = footprint;
+    }
+    assert!(previous > 0);
+
+    let mut syndrome = vec![0u8; 51];
+    syndrome[10] = 1;
+    syndrome[20] = 1;
+    m.decode_to_fault_ids(&syndrome);
+    assert!(m.memory_footprint() > previous);
+}
+
+/// R -10- B -8- C with R -16- C closes a blossom at t = 9, after B has
+/// spent three time units shrinking as an inner region. D -14- B has been
+/// growing towards B all that time at a constant gap, so no collision was
+/// scheduled; once B is part of the growing blossom they meet at t = 11.
+/// This only happens if B's node is rescheduled when the blossom forms.
+#[test]
+fn blossom_reschedules_nodes_of_formerly_shrinking_children() {
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 8.0, &[], 0.1); // B - C
+    m.add_edge(2, 0, 10.0, &[], 0.1); // R - B
+    m.add_edge(2, 1, 16.0, &[], 0.1); // R - C
+    m.add_edge(3, 0, 14.0, &[0], 0.1); // D - B
+    m.add_boundary_edge(3, 100.0, &[], 0.1);
+
+    let mut total = 0;
+    let mut edges = Vec::new();
+    m.decode_streaming(&[1, 1, 1, 1], |a, b, _, w| {
+        edges.push((a.min(b), a.max(b)));
+        total += w;
+    });
+    edges.sort_unstable();
+    assert_eq!(m.last_decode_stats().max_blossom_size, 3);
+    assert_eq!(edges, vec![(0, 3), (1, 2)]);
+    assert_eq!(total, 2 * (14 + 16));
+    assert_eq!(m.decode(&[1, 1, 1, 1]), vec![1]);
+}
+
+#[test]
+fn decode_with_int_weight_sums_discretized_edge_weights() {
+    // Integral weights are kept as-is and doubled.
+    // B -3- D0 -5- D1 -3- D2 -4- B
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 3.0, &[0], 0.1);
+    m.add_edge(0, 1, 5.0, &[], 0.1);
+    m.add_edge(1, 2, 3.0, &[], 0.1);
+    m.add_boundary_edge(2, 4.0, &[], 0.1);
+
+    assert_eq!(m.decode_with_int_weight(&[1, 1, 0]), (vec![0], 2 * 5));
+    assert_eq!(m.decode_with_int_weight(&[1, 0, 1]), (vec![1], 2 * (3 + 4)));
+    assert_eq!(m.decode_with_int_weight(&[0, 0, 0]), (vec![0], 0));
+
+    // Fractional weights: the total is the sum of the per-match weights.
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 0.3, &[0], 0.1);
+    m.add_edge(0, 1, 1.7, &[], 0.1);
+    m.add_edge(1, 2, 0.9, &[], 0.1);
+    m.add_boundary_edge(2, 0.4, &[], 0.1);
+    let syndrome = [1, 1, 1];
+    let mut streamed = 0;
+    m.decode_streaming(&syndrome, |_, _, _, w| streamed += w);
+    let (prediction, weight) = m.decode_with_int_weight(&syndrome);
+    assert_eq!(prediction, m.decode(&syndrome));
+    assert_eq!(weight, streamed);
+
+    // A negative-weight edge in the correction counts negatively.
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 3.0, &[], 0.1);
+    m.add_edge(0, 1, -2.0, &[0], 0.9);
+    m.add_boundary_edge(1, 3.0, &[], 0.1);
+    assert_eq!(m.decode_with_int_weight(&[1, 1]), (vec![1], -2 * 2));
+    assert_eq!(m.decode_with_int_weight(&[0, 0]), (vec![0], 0));
+}
+
+#[test]
+fn decode_batch_with_weights_matches_single_decodes() {
+    // B -0.3- D0 -1.7- D1 -0.9- D2 -0.4- B, plus a negative-weight D1-D3
+    // edge and D3 -0.5- B.
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 0.3, &[0], 0.1);
+    m.add_edge(0, 1, 1.7, &[], 0.1);
+    m.add_edge(1, 2, 0.9, &[], 0.1);
+    m.add_boundary_edge(2, 0.4, &[1], 0.1);
+    m.add_edge(1, 3, -0.2, &[], 0.6);
+    m.add_boundary_edge(3, 0.5, &[], 0.1);
+
+    let syndromes: Vec<Vec<u8>> = (0..16u8)
+        .map(|bits| (0..4).map(|i| (bits >> i) & 1).collect())
+        .collect();
+    let batch = m.decode_batch_with_weights(&syndromes);
+    assert_eq!(batch.len(), syndromes.len());
+    for (syndrome, (prediction, weight)) in syndromes.iter().zip(&batch) {
+        assert_eq!(&m.decode_with_weight(syndrome), &(prediction.clone(), *weight));
+        assert_eq!(prediction, &m.decode(syndrome));
+    }
+
+    // Weights come back in the units the edges were added with.
+    let weight_of = |s: &[u8]| batch[s.iter().rev().fold(0, |acc, &b| 2 * acc + b as usize)].1;
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-6;
+    // Matching D1-D3 undoes the negative edge: weights 0.2 - 0.2.
+    assert!(close(weight_of(&[0, 0, 0, 0]), 0.0));
+    assert!(close(weight_of(&[1, 0, 0, 0]), 0.3));
+    assert!(close(weight_of(&[0, 0, 1, 0]), 0.4));
+    // Exactly the negative edge's detectors fired.
+    assert!(close(weight_of(&[0, 1, 0, 1]), -0.2));
+}
+
+#[test]
+fn isolated_detection_events_are_counted() {
+    // Detector 4 exists (5 and 6 have an edge) but has no edges of its own.
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 1.0, &[], 0.1);
+    m.add_boundary_edge(0, 1.0, &[0], 0.1);
+    m.add_edge(5, 6, 1.0, &[], 0.1);
+
+    assert_eq!(m.decode(&[1, 0, 0, 0, 1, 0, 0]), vec![1]);
+    assert_eq!(m.last_decode_stats().isolated_detection_events, 1);
+    assert_eq!(m.decode_to_edges(&[1, 0, 0, 0, 1, 0, 0]), vec![(0, -1)]);
+    m.decode(&[1, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(m.last_decode_stats().isolated_detection_events, 0);
+}
+
+#[test]
+#[should_panic(expected = "detector 4 fired but has no incident edges")]
+fn isolated_detection_event_panics_when_strict() {
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 1.0, &[], 0.1);
+    m.add_boundary_edge(0, 1.0, &[0], 0.1);
+    m.add_edge(5, 6, 1.0, &[], 0.1);
+    m.set_strict_isolated_detectors(true);
+    m.decode(&[1, 0, 0, 0, 1, 0, 0]);
+}
+
+#[test]
+fn isolated_detection_event_is_an_error_when_strict() {
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 1.0, &[], 0.1);
+    m.add_boundary_edge(0, 1.0, &[0], 0.1);
+    m.add_edge(5, 6, 1.0, &[], 0.1);
+    m.set_strict_isolated_detectors(true);
+    // The isolated detector is rejected even after D0 in event order, and
+    // before D0's region is created, so the next decode starts clean.
+    let err = m.try_decode(&[1, 0, 0, 0, 1, 0, 0]).unwrap_err();
+    assert_eq!(
+        err,
+        MatchingError::InvalidSyndrome("detector 4 fired but has no incident edges".into())
+    );
+    assert_eq!(m.try_decode(&[1, 0, 0, 0, 0, 0, 0]), Ok(vec![1]));
+    assert_eq!(m.try_decode(&[0, 0, 0, 0, 0, 1, 1]), Ok(vec![0]));
+    assert_eq!(m.last_decode_stats().isolated_detection_events, 0);
+}
+
+#[test]
+fn decode_ordered_is_independent_of_event_order() {
+    let mut rng = Rng::new(0xd1b5_4a32_d192_ed03);
+
+    // 6x6 grid with distinct random weights, so the minimum-weight
+    // matching is unique. L0 on the left boundary, L1 on the top one.
+    let n = 6;
+    let mut m = Matching::new();
+    let mut weight = || 1.0 + rng.below(10_000) as f64 / 1000.0;
+    for r in 0..n {
+        for c in 0..n {
+            let d = r * n + c;
+            if c + 1 < n {
+                m.add_edge(d, d + 1, weight(), &[], 0.1);
+            }
+            if r + 1 < n {
+                m.add_edge(d, d + n, weight(), &[], 0.1);
+            }
+        }
+        m.add_boundary_edge(r * n, weight(), &[0], 0.1);
+        m.add_boundary_edge(r * n + n - 1, weight(), &[], 0.1);
+        m.add_boundary_edge(r, weight(), &[1], 0.1);
+    }
+
+    for _ in 0..50 {
+        let syndrome: Vec<u8> = random_syndrome(&mut rng, n * n, 4);
+        let mut events: Vec<usize> = (0..n * n).filter(|&d| syndrome[d] != 0).collect();
+        let expected = m.decode(&syndrome);
+
+        assert_eq!(m.decode_ordered(&events), expected);
+        events.reverse();
+        assert_eq!(m.decode_ordered(&events), expected);
+        for i in (1..events.len()).rev() {
+            events.swap(i, rng.below(i as u64 + 1) as usize);
+        }
+        assert_eq!(m.decode_ordered(&events), expected);
+
+        // Repeats are ignored.
+        let mut repeated = events.clone();
+        repeated.extend_from_slice(&events);
+        assert_eq!(m.decode_ordered(&repeated), expected);
+    }
+}
+
+#[test]
+fn decode_to_matching_graph_dense_view() {
+    // B -1- D0 -5- D1 -1- D2 -1- D3 -5- D4 -1- B
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 1.0, &[0], 0.1);
+    m.add_edge(0, 1, 5.0, &[], 0.1);
+    m.add_edge(1, 2, 1.0, &[], 0.1);
+    m.add_edge(2, 3, 1.0, &[], 0.1);
+    m.add_edge(3, 4, 5.0, &[], 0.1);
+    m.add_boundary_edge(4, 1.0, &[], 0.1);
+
+    assert_eq!(
+        m.decode_to_matching_graph(&[1, 0, 1, 1, 0]),
+        vec![Some(-1), None, Some(3), Some(2), None]
+    );
+    assert_eq!(m.decode_to_matching_graph(&[0; 5]), vec![None; 5]);
+
+    // D0, D1, D2 in a triangle form a blossom that later matches D3; the
+    // dense view names the detector actually matched to D3.
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 2.0, &[], 0.1);
+    m.add_edge(1, 2, 2.0, &[], 0.1);
+    m.add_edge(0, 2, 2.0, &[], 0.1);
+    m.add_edge(1, 3, 8.0, &[], 0.1);
+    m.add_boundary_edge(3, 100.0, &[], 0.1);
+    assert_eq!(m.decode_to_matching_graph(&[1, 1, 1, 1]), vec![Some(2), Some(3), Some(0), Some(1)]);
+    assert_eq!(m.last_decode_stats().max_blossom_size, 3);
+}
+
+#[test]
+fn normalize_boundary_encoding_preserves_decoding() {
+    use rmatching::driver::user_graph::BoundaryEncoding;
+
+    // A 3x3 grid whose left column uses virtual boundary edges and whose
+    // right column uses edges to boundary node 9; L1 is the parity of the
+    // top row, optionally with node 9.
+    let build_with = |l1_has_boundary: bool| {
+        let mut m = Matching::new();
+        for y in 0..3 {
+            for x in 0..3 {
+                let i = 3 * y + x;
+                if x < 2 {
+                    m.add_edge(i, i + 1, 1.0 + (i % 4) as f64, &[0], 0.1);
+                }
+                if y < 2 {
+                    m.add_edge(i, i + 3, 2.0 + (i % 3) as f64, &[], 0.1);
+                }
+            }
+            m.add_boundary_edge(3 * y, 1.5 + y as f64, &[], 0.1);
+            m.add_edge(3 * y + 2, 9, 2.5 - y as f64 / 2.0, &[0], 0.1);
+        }
+        m.add_edge(0, 4, -1.0, &[], 0.6);
+        m.set_boundary(&[9]);
+        let l1: &[usize] = if l1_has_boundary { &[0, 1, 2, 9] } else { &[0, 1, 2] };
+        m.set_detector_observable(1, l1);
+        m
+    };
+    let build = || build_with(false);
+    let syndromes: Vec<Vec<u8>> = (0..1u32 << 10)
+        .map(|bits| (0..10).map(|i| ((bits >> i) & 1) as u8).collect())
+        .collect();
+    let mut original = build();
+    let expected: Vec<_> = syndromes
+        .iter()
+        .map(|s| (original.decode(s), original.decode_to_fault_ids(s)))
+        .collect();
+    let check = |m: &mut Matching| {
+        for (s, want) in syndromes.iter().zip(&expected) {
+            assert_eq!(&(m.decode(s), m.decode_to_fault_ids(s)), want, "syndrome {s:?}");
+        }
+    };
+
+    let mut virtual_boundary = build();
+    virtual_boundary.normalize_boundary_encoding(BoundaryEncoding::Virtual).unwrap();
+    assert_eq!(virtual_boundary.boundary_detectors(), vec![0, 2, 3, 5, 6, 8]);
+    check(&mut virtual_boundary);
+    // And back again, onto a new boundary node.
+    virtual_boundary.normalize_boundary_encoding(BoundaryEncoding::Nodes).unwrap();
+    check(&mut virtual_boundary);
+
+    let mut node_boundary = build();
+    node_boundary.normalize_boundary_encoding(BoundaryEncoding::Nodes).unwrap();
+    check(&mut node_boundary);
+    node_boundary.normalize_boundary_encoding(BoundaryEncoding::Virtual).unwrap();
+    check(&mut node_boundary);
+
+    // Node 9 is in L1 but the virtual boundary is not, so they cannot merge.
+    let mut mixed = build_with(true);
+    assert!(mixed.normalize_boundary_encoding(BoundaryEncoding::Virtual).is_err());
+}
+
+#[test]
+fn dem_probabilities_outside_unit_interval_are_certainties() {
+    use rmatching::driver::dem_parse::parse_dem;
+
+    // B -L0- D0 -- D1 -- D2 -- B, with the D2 boundary edge the lightest.
+    let chain = "error(0.1) D0 D1\n\
+                 error(0.1) D1 D2\n\
+                 error(0.02) D0 L0\n\
+                 error(0.2) D2\n";
+
+    // Errors that never occur are dropped.
+    let never = format!("{chain}error(0) D0 D2 L1\nerror(-0.5) D1 L1\n");
+    assert_eq!(parse_dem(&never).unwrap().get_num_edges(), 4);
+    let mut m = Matching::from_dem(&never).unwrap();
+    assert_eq!(m.decode(&[1, 0, 1]), vec![0, 0]);
+    assert_eq!(m.decode(&[0, 1, 0]), vec![0, 0]);
+
+    // An error that always occurs flips D1 and L1 in every shot.
+    let always = format!("{chain}error(1) D1 L1\n");
+    assert_eq!(parse_dem(&always).unwrap().get_num_edges(), 4);
+    let mut m = Matching::from_dem(&always).unwrap();
+    assert_eq!(m.decode(&[0, 1, 0]), vec![0, 1]);
+    // D1 now needs explaining, via D2 and the boundary.
+    assert_eq!(m.decode(&[0, 0, 0]), vec![0, 1]);
+    assert_eq!(m.decode_to_edges(&[0, 0, 0]), vec![(1, -1)]);
+    // A certain hyperedge is applied too.
+    let mut m = Matching::from_dem(&format!("{chain}error(1.5) D0 D1 D2\n")).unwrap();
+    assert_eq!(m.decode_to_edges(&[1, 1, 1]), vec![]);
+    assert_eq!(m.decode_to_edges(&[1, 0, 1]), vec![(1, -1)]);
+    assert_eq!(m.decode_to_edges(&[0, 1, 0]), vec![(0, 2)]);
+}
+
+#[test]
+fn negative_weight_preprocessing_keeps_syndromes_solvable() {
+    // 5x5 grid with boundary edges on the left and right columns and
+    // every fourth edge negative.
+    let mut rng = Rng::new(0x853c_49e6_748f_ea9b);
+    let mut m = Matching::new();
+    let mut edges: Vec<(usize, Option<usize>, usize)> = Vec::new();
+    let mut add = |m: &mut Matching, a: usize, b: Option<usize>, obs: &[usize], r: u64| {
+        // Irregular weights, so that shortest paths are unique and the
+        // fault ids follow the same paths as the matching.
+        let mut weight = 1.0 + (r % 10007) as f64 / 997.0;
+        if edges.len() % 4 == 3 {
+            weight = -weight / 2.0;
+        }
+        match b {
+            Some(b) => m.add_edge(a, b, weight, obs, 0.1),
+            None => m.add_boundary_edge(a, weight, obs, 0.1),
+        }
+        edges.push((a, b, obs.first().map_or(0, |&o| 1 << o)));
+    };
+    for y in 0..5 {
+        for x in 0..5 {
+            let i = 5 * y + x;
+            if x < 4 {
+                add(&mut m, i, Some(i + 1), &[], rng.next_u64());
+            }
+            if y < 4 {
+                add(&mut m, i, Some(i + 5), &[1], rng.next_u64());
+            }
+        }
+        add(&mut m, 5 * y, None, &[0], rng.next_u64());
+        add(&mut m, 5 * y + 4, None, &[], rng.next_u64());
+    }
+
+    for _ in 0..300 {
+        let syndrome: Vec<u8> = random_syndrome(&mut rng, 25, 3);
+        let prediction = m.decode(&syndrome);
+        // The correction explains exactly the syndrome and the prediction.
+        let mut explained = vec![0u8; 25];
+        let mut obs = 0usize;
+        for id in m.decode_to_fault_ids(&syndrome) {
+            let (a, b, mask) = edges[id];
+            explained[a] ^= 1;
+            if let Some(b) = b {
+                explained[b] ^= 1;
+            }
+            obs ^= mask;
+        }
+        assert_eq!(explained, syndrome);
+        assert_eq!(prediction, vec![(obs & 1) as u8, (obs >> 1 & 1) as u8]);
+    }
+}
+
+#[test]
+fn batch_changes_take_effect_on_commit() {
+    let chain = |m: &mut Matching| {
+        m.add_boundary_edge(0, 1.0, &[0], 0.1);
+        m.add_edge(0, 1, 3.0, &[], 0.1);
+        m.add_edge(1, 2, 3.0, &[], 0.1);
+        m.add_boundary_edge(2, 1.0, &[1], 0.1);
+    };
+    let mut m = Matching::new();
+    chain(&mut m);
+    assert!(m.commit());
+    assert!(!m.commit());
+    let before = m.decode(&[1, 1, 0]);
+
+    // Inside a batch, decoding still uses the graph from before it.
+    m.begin_batch();
+    m.add_edge(0, 1, 0.5, &[1], 0.1);
+    m.add_boundary_edge(3, 1.0, &[], 0.1);
+    assert_eq!(m.decode(&[1, 1, 0]), before);
+    assert!(m.commit());
+    assert!(!m.commit());
+
+    let mut fresh = Matching::new();
+    chain(&mut fresh);
+    fresh.add_edge(0, 1, 0.5, &[1], 0.1);
+    fresh.add_boundary_edge(3, 1.0, &[], 0.1);
+    for bits in 0..16u8 {
+        let syndrome: Vec<u8> = (0..4).map(|i| (bits >> i) & 1).collect();
+        assert_eq!(m.decode(&syndrome), fresh.decode(&syndrome));
+    }
+    assert_ne!(m.decode(&[1, 1, 0, 0]), before);
+
+    // Ending a batch without changes keeps the solver.
+    m.begin_batch();
+    m.end_batch();
+    assert!(!m.commit());
+}
+
+#[test]
+fn cached_decodes_keep_the_old_graph_inside_a_batch() {
+    // B -L0- D0 - D1 - B, with the left boundary edge forbidden in a batch.
+    let build = || {
+        let mut m = Matching::new();
+        m.add_boundary_edge(0, 1.0, &[0], 0.1);
+        m.add_edge(0, 1, 1.0, &[], 0.1);
+        m.add_boundary_edge(1, 3.0, &[], 0.1);
+        m
+    };
+    let mut m = build();
+    let old = m.decode(&[1, 0]);
+    assert_eq!(old, vec![1]);
+
+    // Only the matcher is cached when the batch begins; the fault ids must
+    // still come from the same graph as the matching.
+    m.begin_batch();
+    m.forbid_edge(0, None);
+    assert_eq!(m.decode(&[1, 0]), old);
+    assert_eq!(m.decode_batch(&[vec![1, 0]]), vec![old.clone()]);
+    assert_eq!(m.decode_to_edges(&[1, 0]), vec![(0, -1)]);
+    assert_eq!(m.decode_to_fault_ids(&[1, 0]), vec![0]);
+    assert_eq!(m.decode_refined(&[1, 0]), old);
+    // Decodes that build their own solver see the change straight away.
+    assert_eq!(m.decode_shared(&[1, 0]), vec![0]);
+    assert_eq!(m.context().decode(&[1, 0]), vec![0]);
+
+    m.commit();
+    assert_eq!(m.decode(&[1, 0]), vec![0]);
+    assert_eq!(m.decode_to_fault_ids(&[1, 0]), vec![1, 2]);
+    assert_eq!(m.decode_shared(&[1, 0]), vec![0]);
+
+    // Nothing cached when the batch begins: whichever is built first, the
+    // matcher and the fault ids come from the same graph.
+    let mut m = build();
+    m.begin_batch();
+    assert_eq!(m.decode_to_fault_ids(&[1, 0]), vec![0]);
+    m.forbid_edge(0, None);
+    assert_eq!(m.decode(&[1, 0]), old);
+    m.end_batch();
+    assert_eq!(m.decode(&[1, 0]), vec![0]);
+    assert_eq!(m.decode_to_fault_ids(&[1, 0]), vec![1, 2]);
+}
+
+#[test]
+fn decode_to_fault_ids_verifies_against_its_syndrome() {
+    // 4x4 grid with an explicit boundary node 16 on the left and the
+    // virtual boundary on the right.
+    let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+    let mut m = Matching::new();
+    for y in 0..4 {
+        for x in 0..4 {
+            let i = 4 * y + x;
+            let weight = 1.0 + rng.below(1009) as f64 / 101.0;
+            if x < 3 {
+                m.add_edge(i, i + 1, weight, &[], 0.1);
+            }
+            if y < 3 {
+                m.add_edge(i, i + 4, weight + 0.5, &[1], 0.1);
+            }
+        }
+        m.add_edge(4 * y, 16, 2.0, &[0], 0.1);
+        m.add_boundary_edge(4 * y + 3, 2.5, &[], 0.1);
+    }
+    m.set_boundary(&[16]);
+    for _ in 0..200 {
+        let syndrome: Vec<u8> = random_syndrome(&mut rng, 16, 3);
+        let faults = m.decode_to_fault_ids(&syndrome);
+        assert!(m.verify_correction(&syndrome, &faults), "syndrome {syndrome:?}");
+        if let Some((_, rest)) = faults.split_first() {
+            assert!(!m.verify_correction(&syndrome, rest));
+        }
+    }
+    assert!(!m.verify_correction(&[0; 16], &[usize::MAX]));
+
+    // A certain fault is part of every correction without a fault id.
+    let dem = "error(0.1) D0\nerror(0.1) D0 D1\nerror(0.1) D1 D2\nerror(1) D1 D2\n";
+    let mut m = Matching::from_dem(dem).unwrap();
+    assert!(!m.verify_correction(&[0, 0, 0], &[]));
+    assert!(m.verify_correction(&[0, 1, 1], &[]));
+    for syndrome in [[0, 0, 0], [1, 1, 0], [1, 0, 1], [0, 1, 1]] {
+        let faults = m.decode_to_fault_ids(&syndrome);
+        assert!(m.verify_correction(&syndrome, &faults), "syndrome {syndrome:?}");
+    }
+}
+
+#[test]
+fn fallible_apis_report_typed_errors() {
+    let err = Matching::from_dem("error(0.1) Dx\n").err().unwrap();
+    assert!(matches!(err, MatchingError::ParseError(_)), "{err:?}");
+    assert_eq!(err.to_string(), "bad detector index: invalid digit found in string");
+
+    let err = Matching::from_grid_periodic(2, 5, 0.1).err().unwrap();
+    assert!(matches!(err, MatchingError::InvalidGraph(_)), "{err:?}");
+    assert_eq!(err.to_string(), "periodic grid must be at least 3x3, got 2x5");
+
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 1.0, &[0], 0.1);
+    m.add_boundary_edge(1, -1.0, &[], 0.1);
+    let err = m.logical_class_weights(&[1, 1]).unwrap_err();
+    assert_eq!(err, MatchingError::InvalidWeight { edge: 1, weight: -1.0 });
+    assert_eq!(err.to_string(), "edge 1 has negative weight");
+
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 1.0, &[0, 1, 2, 3], 0.1);
+    let err = m.logical_class_weights(&[1, 1]).unwrap_err();
+    assert!(
+        matches!(err, MatchingError::ObservableOverflow { num_observables: 4, .. }),
+        "{err:?}"
+    );
+
+    m.add_edge(0, 1, 1.0, &[64], 0.1);
+    let err = m.decode_packed_batch(&[0b11], 1, 2).unwrap_err();
+    assert_eq!(
+        err,
+        MatchingError::ObservableOverflow {
+            operation: "packed decoding",
+            max: 64,
+            num_observables: 65,
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "packed decoding supports at most 64 observables, graph has 65"
+    );
+}
+
+#[test]
+fn logical_error_rate_ci_contains_the_exact_rate() {
+    // Three-qubit repetition code: decoding fails exactly when two or
+    // three of the qubits flip, with probability 3p^2 - 2p^3.
+    let p: f64 = 0.2;
+    let w = ((1.0 - p) / p).ln();
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, w, &[0], p);
+    m.add_edge(0, 1, w, &[], p);
+    m.add_boundary_edge(1, w, &[], p);
+    let exact = 3.0 * p * p - 2.0 * p * p * p;
+
+    let mut rng = Rng::new(0x0ddb_1a5e_5bad_5eed);
+    let mut uniform = move || rng.uniform();
+    let mut previous_width = f64::INFINITY;
+    for shots in [100, 1_000, 10_000, 100_000] {
+        let (rate, lower, upper) = m.estimate_logical_error_rate_ci(shots, &mut uniform).unwrap();
+        assert!(upper - lower < previous_width, "{shots} shots: [{lower}, {upper}]");
+        assert!(lower <= rate && rate <= upper, "{shots} shots: {rate} in [{lower}, {upper}]");
+        assert!(lower <= exact && exact <= upper, "{shots} shots: [{lower}, {upper}]");
+        previous_width = upper - lower;
+    }
+    assert!(previous_width < 0.004);
+
+    // The estimate does not depend on the observable order.
+    m.set_observable_order(rmatching::driver::decoding::ObservableOrder::MsbFirst);
+    let (_, lower, upper) = m.estimate_logical_error_rate_ci(10_000, &mut uniform).unwrap();
+    assert!(lower <= exact && exact <= upper, "[{lower}, {upper}]");
+
+    // With no failures the rate is exactly zero, but the interval is not.
+    let (rate, lower, upper) = m.estimate_logical_error_rate_ci(10, &mut || 0.99).unwrap();
+    assert_eq!((rate, lower), (0.0, 0.0));
+    assert!(upper > 0.2, "{upper}");
+
+    m.add_edge(0, 1, 1.0, &[], -1.0);
+    assert!(matches!(
+        m.estimate_logical_error_rate_ci(10, &mut uniform),
+        Err(MatchingError::InvalidProbability { edge: 3, .. })
+    ));
+}
+
+#[test]
+fn decode_refined_recovers_float_optimal_matching() {
+    // A heavy edge elsewhere makes the discretization step exactly one
+    // unit, so every weight below is rounded to the nearest integer.
+    let mut m = Matching::new();
+    m.add_edge(10, 11, 16_777_215.0, &[], -1.0);
+    // D0 and D1 can pair through D2 and D3 (float 31.47, rounded 30) or
+    // both match to the boundary (float 31.02, rounded 32).
+    m.add_edge(0, 2, 10.49, &[], -1.0);
+    m.add_edge(2, 3, 10.49, &[], -1.0);
+    m.add_edge(3, 1, 10.49, &[], -1.0);
+    m.add_boundary_edge(0, 15.51, &[0], -1.0);
+    m.add_boundary_edge(1, 15.51, &[1], -1.0);
+
+    let mut syndrome = vec![0u8; 12];
+    syndrome[0] = 1;
+    syndrome[1] = 1;
+    assert_eq!(m.decode(&syndrome), vec![0, 0]);
+    assert_eq!(m.decode_refined(&syndrome), vec![1, 1]);
+
+    // Where the discretized matching is already optimal it is kept.
+    syndrome[1] = 0;
+    assert_eq!(m.decode_refined(&syndrome), m.decode(&syndrome));
+    syndrome[2] = 1;
+    assert_eq!(m.decode_refined(&syndrome), vec![0, 0]);
+}
+
+#[test]
+fn forbidden_edges_force_an_alternative_matching() {
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 1.0, &[], -1.0);
+    m.add_boundary_edge(0, 2.0, &[0], -1.0);
+    m.add_boundary_edge(1, 2.0, &[1], -1.0);
+
+    assert_eq!(m.decode(&[1, 1]), vec![0, 0]);
+    m.forbid_edge(1, Some(0));
+    assert_eq!(m.decode(&[1, 1]), vec![1, 1]);
+    assert_eq!(m.decode_refined(&[1, 1]), vec![1, 1]);
+    let mut fault_ids = m.decode_to_fault_ids(&[1, 1]);
+    fault_ids.sort_unstable();
+    assert_eq!(fault_ids, vec![1, 2]);
+
+    // Without its own boundary edge, D1 reaches the boundary through D0.
+    m.clear_forbidden_edges();
+    assert_eq!(m.decode(&[0, 1]), vec![0, 1]);
+    m.forbid_edge(1, None);
+    assert_eq!(m.decode(&[0, 1]), vec![1, 0]);
+    m.clear_forbidden_edges();
+    assert_eq!(m.decode(&[0, 1]), vec![0, 1]);
+    assert_eq!(m.decode(&[1, 1]), vec![0, 0]);
+}
+
+#[test]
+fn decode_contexts_nest_on_one_matching() {
+    let mut m = Matching::from_grid_periodic(5, 5, 0.05).unwrap();
+    let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+    let syndromes: Vec<Vec<u8>> = (0..32)
+        .map(|_| {
+            let mut syndrome = vec![0u8; 25];
+            for _ in 0..3 {
+                let n = rng.below(25) as usize;
+                syndrome[n] ^= 1;
+                syndrome[(n + 5) % 25] ^= 1;
+            }
+            syndrome
+        })
+        .collect();
+    let expected = m.decode_batch(&syndromes);
+
+    // The inner context decodes while the outer one is mid-way through the
+    // batch; neither disturbs the other's solver.
+    let mut outer = m.context();
+    let mut inner = m.context();
+    let predictions: Vec<(Vec<u8>, Vec<u8>)> = syndromes
+        .iter()
+        .rev()
+        .map(|syndrome| {
+            let first = outer.decode(syndrome);
+            let nested = inner.decode(&syndromes[0]);
+            (first, nested)
+        })
+        .collect();
+    for (i, (first, nested)) in predictions.into_iter().rev().enumerate() {
+        assert_eq!(first, expected[i]);
+        assert_eq!(nested, expected[0]);
+    }
+    assert_eq!(outer.matching().num_observables(), m.num_observables());
+}
+
+#[test]
+fn odd_syndrome_on_a_torus_is_an_error() {
+    let mut m = Matching::from_grid_periodic(4, 4, 0.05).unwrap();
+    let mut syndrome = vec![0u8; 16];
+    for d in [0, 5, 10] {
+        syndrome[d] = 1;
+    }
+    let err = m.try_decode(&syndrome).unwrap_err();
+    assert!(matches!(err, MatchingError::InvalidSyndrome(_)), "{err:?}");
+    assert!(err.to_string().contains("odd number of detection events"), "{err}");
+    assert!(m.context().try_decode(&syndrome).is_err());
+
+    // The rejected syndrome leaves nothing behind.
+    syndrome[10] = 0;
+    let expected = Matching::from_grid_periodic(4, 4, 0.05).unwrap().decode(&syndrome);
+    assert_eq!(m.try_decode(&syndrome), Ok(expected));
+
+    // Only components without a boundary need an even count.
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 1.0, &[0], 0.1);
+    m.add_edge(2, 3, 1.0, &[], 0.1);
+    m.add_boundary_edge(3, 1.0, &[1], 0.1);
+    assert!(m.try_decode(&[1, 0, 0, 0]).is_err());
+    assert_eq!(m.try_decode(&[1, 1, 0, 1]), Ok(vec![1, 1]));
+}
+
+#[test]
+fn certain_fault_observables_agree_between_sampling_and_decoding() {
+    // The certain fault on D1 and D2 crosses both detector-defined
+    // observables, although D2 is a boundary node.
+    let mut m = Matching::from_dem("error(0.1) D0 D1\nerror(0.1) D1 D2\nerror(1) D1 D2\n").unwrap();
+    m.set_boundary(&[2]);
+    m.set_detector_observable(0, &[1]);
+    m.set_detector_observable(1, &[2]);
+    assert_eq!(m.flipped_observables(), vec![0, 1]);
+
+    let (syndrome, observables) = m.add_noise(&mut || 0.99).unwrap();
+    assert_eq!(syndrome, vec![0, 1, 0]);
+    assert_eq!(observables, vec![1, 1]);
+    assert_eq!(m.decode(&syndrome), observables);
+}
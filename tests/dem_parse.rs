@@ -1,4 +1,5 @@
-use rmatching::driver::dem_parse::parse_dem;
+use rmatching::driver::dem_parse::{parse_dem, parse_dem_strict, validate_dem};
+use rmatching::MatchingError;
 
 #[test]
 fn parse_simple_dem() {
@@ -94,3 +95,81 @@ repeat 2 {
     assert_eq!((g.edges[0].node1, g.edges[0].node2), (0, 1));
     assert_eq!((g.edges[1].node1, g.edges[1].node2), (2, 3));
 }
+
+#[test]
+fn parse_dem_strict_rejects_unknown_instructions() {
+    let dem = "\
+# a comment
+error(0.1) D0 D1 L0
+wobble D0
+tick
+logical_observable L0
+repeat 2 {
+    wobble D1
+    frobnicate(0.5) D0
+    error(0.1) D1
+    shift_detectors 1
+}";
+    let g = parse_dem(dem).unwrap();
+    assert_eq!(g.edges.len(), 3);
+
+    let err = parse_dem_strict(dem).err().expect("strict parse should fail");
+    assert!(matches!(err, MatchingError::ParseError(_)), "{err:?}");
+    assert_eq!(err.to_string(), "unsupported DEM instructions: wobble, frobnicate");
+
+    let clean = "error(0.1) D0 D1 L0\ndetector(0, 0) D1\ntick\nlogical_observable L0\n";
+    assert_eq!(parse_dem_strict(clean).unwrap().edges.len(), 1);
+}
+
+#[test]
+fn parse_top_level_lines_after_repeat_use_shifted_detectors() {
+    let dem = "\
+error(0.1) D0 D1
+shift_detectors 1
+repeat 3 {
+    error(0.1) D0 D1
+    repeat 2 {
+        error(0.1) D1
+        shift_detectors 1
+    }
+    shift_detectors 2
+}
+error(0.1) D0 D2 L0
+detector D3
+";
+    let g = parse_dem(dem).unwrap();
+    // Offset 1 before the repeat, then 3 iterations of 2 + 2 * 1.
+    let last = g.edges.last().unwrap();
+    assert_eq!((last.node1, last.node2), (13, 15));
+    assert_eq!(g.edges.len(), 1 + 3 * 3 + 1);
+    assert_eq!(g.nodes.len(), 17);
+    // Second iteration of the outer body starts at offset 1 + 4.
+    assert_eq!((g.edges[4].node1, g.edges[4].node2), (5, 6));
+}
+
+#[test]
+fn validate_dem_reports_counts_and_warnings() {
+    let dem = "\
+error(0.1) D0 D1 L0
+error(0.5) D1 D2
+error(0.1) D2 L1
+error(0.1) D0 D1 D2
+detector D4
+";
+    let stats = validate_dem(dem).unwrap();
+    assert_eq!(stats.num_detectors, 5);
+    assert_eq!(stats.num_edges, 3);
+    assert_eq!(stats.num_observables, 2);
+    assert_eq!(
+        stats.warnings,
+        vec![
+            "edge 1 has zero weight (p = 0.5)".to_string(),
+            "detector D3 is not in any error".to_string(),
+            "detector D4 is not in any error".to_string(),
+            "hyperedge dropped, decompose it with '^': error(0.1) D0 D1 D2".to_string(),
+        ]
+    );
+
+    assert!(validate_dem("error(0.1) D0 D1\n").unwrap().warnings.is_empty());
+    assert!(validate_dem("error(x) D0\n").is_err());
+}
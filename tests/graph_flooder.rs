@@ -177,3 +177,30 @@ fn flood_check_event_has_time() {
     assert!(!search_ev.is_no_event());
     assert_eq!(search_ev.time(), Wrapping(7));
 }
+
+#[test]
+fn flooder_reset_keeps_arena_storage_across_large_decodes() {
+    let mut graph = MatchingGraph::new(1, 0);
+    graph.add_boundary_edge(0, 5, &[]);
+    let mut flooder = GraphFlooder::new(graph);
+    let large = 70_000;
+
+    let mut storage = None;
+    for _ in 0..5 {
+        for _ in 0..large {
+            flooder.region_arena.alloc();
+        }
+        flooder.reset();
+        assert_eq!(flooder.region_arena.num_slots(), large);
+        let ptr = flooder.region_arena.items().as_ptr();
+        assert_eq!(*storage.get_or_insert(ptr), ptr, "arena storage was reallocated");
+    }
+
+    // Once decodes stay small, the high-water mark decays and the storage
+    // is released.
+    for _ in 0..20 {
+        flooder.region_arena.alloc();
+        flooder.reset();
+    }
+    assert!(flooder.region_arena.num_slots() < large);
+}
@@ -1,5 +1,8 @@
 use rmatching::Matching;
 
+mod common;
+use common::{random_syndrome, Rng};
+
 // ---------------------------------------------------------------------------
 // 1. e2e_rep_code_d3
 // ---------------------------------------------------------------------------
@@ -156,6 +159,28 @@ error(0.05) D2
     assert_eq!(pred2, vec![0]);
 }
 
+/// `decode_to_edges_with_observables` carries the boundary edge's
+/// observables, agreeing with `decode` without a second call.
+#[test]
+fn e2e_decode_to_edges_boundary_observables() {
+    let dem = "\
+error(0.1) D0 D1
+error(0.1) D1 D2
+error(0.05) D0 L0
+error(0.05) D2 L1
+";
+    let mut m = Matching::from_dem(dem).unwrap();
+    for syndrome in [vec![1u8, 0, 0], vec![0, 0, 1], vec![1, 0, 1], vec![0, 1, 1]] {
+        let edges = m.decode_to_edges_with_observables(&syndrome);
+        let mask = edges.iter().fold(0u64, |acc, &(_, _, mask)| acc ^ mask);
+        let prediction = m.decode(&syndrome);
+        assert_eq!(prediction, vec![(mask & 1) as u8, (mask >> 1 & 1) as u8]);
+    }
+
+    let edges = m.decode_to_edges_with_observables(&[1, 0, 0]);
+    assert_eq!(edges, vec![(0, -1, 0b01)]);
+}
+
 // ---------------------------------------------------------------------------
 // 5. e2e_surface_code_d3
 // ---------------------------------------------------------------------------
@@ -218,3 +243,365 @@ error(0.05) D3
     // but it must be a valid 0 or 1.
     assert!(pred[0] == 0 || pred[0] == 1);
 }
+
+// ---------------------------------------------------------------------------
+// Sparse shots on large graphs
+// ---------------------------------------------------------------------------
+
+/// Repetition-code chain with `n` detectors and a boundary at both ends.
+fn rep_code_chain(n: usize) -> Matching {
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 1.0, &[0], 0.1);
+    for i in 0..n - 1 {
+        m.add_edge(i, i + 1, 1.0, &[], 0.1);
+    }
+    m.add_boundary_edge(n - 1, 1.0, &[], 0.1);
+    m
+}
+
+/// Deterministic sparse syndromes from `errors` random single-edge errors
+/// on the chain, as detection-event lists.
+fn sparse_shots(n: usize, shots: usize, errors: usize) -> Vec<Vec<usize>> {
+    let mut rng = Rng::new(0x9e37_79b9_7f4a_7c15);
+    (0..shots)
+        .map(|_| {
+            let mut events = Vec::new();
+            for _ in 0..errors {
+                let i = rng.below(n as u64 - 1) as usize;
+                for d in [i, i + 1] {
+                    match events.iter().position(|&e| e == d) {
+                        Some(k) => {
+                            events.swap_remove(k);
+                        }
+                        None => events.push(d),
+                    }
+                }
+            }
+            events
+        })
+        .collect()
+}
+
+/// Reset only cleans up the nodes a shot touched; a long-lived decoder must
+/// still agree with a fresh one on every shot.
+#[test]
+fn e2e_reused_decoder_matches_fresh_decoder_on_sparse_shots() {
+    let n = 2000;
+    let mut reused = rep_code_chain(n);
+    for events in sparse_shots(n, 100, 6) {
+        let mut fresh = rep_code_chain(n);
+        assert_eq!(
+            reused.decode_detection_events(&events),
+            fresh.decode_detection_events(&events),
+            "events {events:?}"
+        );
+    }
+}
+
+#[test]
+fn e2e_boundary_detectors() {
+    let m = rep_code_chain(5);
+    assert_eq!(m.boundary_detectors(), vec![0, 4]);
+
+    // The same chain with explicit boundary nodes 5 and 6.
+    let mut m = Matching::new();
+    m.add_edge(5, 0, 1.0, &[0], 0.1);
+    for i in 0..4 {
+        m.add_edge(i, i + 1, 1.0, &[], 0.1);
+    }
+    m.add_edge(4, 6, 1.0, &[], 0.1);
+    m.add_edge(5, 6, 1.0, &[], 0.1);
+    m.set_boundary(&[5, 6]);
+    assert_eq!(m.boundary_detectors(), vec![0, 4]);
+}
+
+/// Planar distance-5 surface code (one stabilizer type): detectors on a
+/// 5x4 grid, left and right rough boundaries as explicit boundary nodes,
+/// L0 on every left boundary edge. The lightest logical is a row of 5 edges.
+#[test]
+fn min_logical_weight_surface_code_equals_distance() {
+    use rmatching::driver::logical_weight::BoundaryPair;
+
+    let d = 5;
+    let cols = d - 1;
+    let det = |r: usize, c: usize| r * cols + c;
+    let (left, right) = (d * cols, d * cols + 1);
+
+    let mut m = Matching::new();
+    for r in 0..d {
+        m.add_edge(left, det(r, 0), 1.0, &[0], 0.01);
+        for c in 0..cols - 1 {
+            m.add_edge(det(r, c), det(r, c + 1), 1.0, &[], 0.01);
+        }
+        m.add_edge(det(r, cols - 1), right, 1.0, &[], 0.01);
+        if r + 1 < d {
+            for c in 0..cols {
+                m.add_edge(det(r, c), det(r + 1, c), 1.0, &[], 0.01);
+            }
+        }
+    }
+    m.set_boundary(&[left, right]);
+
+    assert_eq!(m.min_logical_weight(&[]), vec![d as f64]);
+
+    let across = BoundaryPair { observable: 0, from: vec![left], to: vec![right] };
+    assert_eq!(m.min_logical_weight(&[across]), vec![d as f64]);
+
+    // Returning to the same boundary crosses L0 an even number of times.
+    let same_side = BoundaryPair { observable: 0, from: vec![left], to: vec![left] };
+    assert_eq!(m.min_logical_weight(&[same_side]), vec![f64::INFINITY]);
+}
+
+/// Distance-5 repetition code B -L0- D0 - D1 - D2 - D3 - B with unit
+/// weights: firing D1 leaves a correction of 2 edges to the left (flipping
+/// L0) and one of 3 edges to the right.
+#[test]
+fn logical_class_weights_rep_code() {
+    let mut m = Matching::new();
+    m.add_boundary_edge(0, 1.0, &[0], 0.1);
+    for i in 0..3 {
+        m.add_edge(i, i + 1, 1.0, &[], 0.1);
+    }
+    m.add_boundary_edge(3, 1.0, &[], 0.1);
+
+    assert_eq!(m.logical_class_weights(&[0, 1, 0, 0]).unwrap(), vec![3.0, 2.0]);
+    assert_eq!(m.decode(&[0, 1, 0, 0]), vec![1]);
+    assert_eq!(m.logical_class_weights(&[0, 0, 1, 0]).unwrap(), vec![2.0, 3.0]);
+    assert_eq!(m.logical_class_weights(&[0, 0, 0, 0]).unwrap(), vec![0.0, 5.0]);
+    assert_eq!(m.logical_class_weights(&[1, 1, 0, 0]).unwrap(), vec![1.0, 4.0]);
+}
+
+/// Every class weight on a small planar patch with two observables, some
+/// on internal edges, against enumerating all edge subsets.
+#[test]
+fn logical_class_weights_match_exhaustive_search() {
+    // 3x2 detectors with boundaries on all four sides. L0 is on the edges
+    // crossing between columns 0 and 1, L1 on those between the two rows.
+    let (cols, rows) = (3, 2);
+    let det = |r: usize, c: usize| r * cols + c;
+    let mut edges: Vec<(usize, Option<usize>, Vec<usize>)> = Vec::new();
+    for r in 0..rows {
+        edges.push((det(r, 0), None, vec![]));
+        edges.push((det(r, cols - 1), None, vec![]));
+        for c in 0..cols - 1 {
+            let obs = if c == 0 { vec![0] } else { vec![] };
+            edges.push((det(r, c), Some(det(r, c + 1)), obs));
+        }
+    }
+    for c in 0..cols {
+        edges.push((det(0, c), None, vec![]));
+        edges.push((det(rows - 1, c), None, vec![]));
+        edges.push((det(0, c), Some(det(1, c)), vec![1]));
+    }
+
+    let mut rng = Rng::new(0x5851_f42d_4c95_7f2d);
+    for _ in 0..5 {
+        let weights: Vec<u64> = edges.iter().map(|_| 1 + rng.below(6)).collect();
+        let mut m = Matching::new();
+        for ((a, b, obs), &w) in edges.iter().zip(&weights) {
+            match b {
+                Some(b) => m.add_edge(*a, *b, w as f64, obs, 0.1),
+                None => m.add_boundary_edge(*a, w as f64, obs, 0.1),
+            }
+        }
+
+        // best[syndrome][class] over all subsets of edges.
+        let num_detectors = cols * rows;
+        let mut best = vec![[u64::MAX; 4]; 1 << num_detectors];
+        for subset in 0..1usize << edges.len() {
+            let (mut syndrome, mut class, mut weight) = (0usize, 0usize, 0);
+            for (i, (a, b, obs)) in edges.iter().enumerate() {
+                if subset & (1 << i) != 0 {
+                    syndrome ^= 1 << a;
+                    if let Some(b) = b {
+                        syndrome ^= 1 << b;
+                    }
+                    for o in obs {
+                        class ^= 1 << o;
+                    }
+                    weight += weights[i];
+                }
+            }
+            best[syndrome][class] = best[syndrome][class].min(weight);
+        }
+
+        for (syndrome, class_weights) in best.iter().enumerate() {
+            let bits: Vec<u8> = (0..num_detectors).map(|d| (syndrome >> d & 1) as u8).collect();
+            let expected: Vec<f64> = class_weights.iter().map(|&w| w as f64).collect();
+            assert_eq!(m.logical_class_weights(&bits).unwrap(), expected, "syndrome {bits:?}");
+        }
+    }
+}
+
+#[test]
+fn logical_class_weights_rejects_observable_on_internal_cycle() {
+    // A ring whose single L0 edge makes the ring itself a logical.
+    let mut m = Matching::new();
+    m.add_edge(0, 1, 1.0, &[0], 0.1);
+    m.add_edge(1, 2, 1.0, &[], 0.1);
+    m.add_edge(2, 0, 1.0, &[], 0.1);
+    assert!(m.logical_class_weights(&[1, 1, 0]).is_err());
+}
+
+/// Minimum-weight perfect matching of `events` by brute force, where each
+/// event pairs with another or with the boundary (index `dist.len() - 1`).
+fn brute_force_matching_weight(dist: &[Vec<u64>], events: &[usize]) -> u64 {
+    let boundary = dist.len() - 1;
+    let full = (1usize << events.len()) - 1;
+    let mut best = vec![u64::MAX; full + 1];
+    best[0] = 0;
+    for mask in 1..=full {
+        let i = mask.trailing_zeros() as usize;
+        let rest = mask & !(1 << i);
+        let mut b = best[rest].saturating_add(dist[events[i]][boundary]);
+        for j in i + 1..events.len() {
+            if rest & (1 << j) != 0 {
+                b = b.min(best[rest & !(1 << j)].saturating_add(dist[events[i]][events[j]]));
+            }
+        }
+        best[mask] = b;
+    }
+    best[full]
+}
+
+/// Random small graphs with many detection events build alternating trees
+/// in which growing regions sit next to shrinking ones; the matching must
+/// still be optimal, i.e. no collision is dropped while a neighbor shrinks.
+#[test]
+fn random_small_graphs_decode_to_minimum_weight_matching() {
+    let mut rng = Rng::new(0x0123_4567_89ab_cdef);
+    const INF: u64 = u64::MAX / 4;
+    for _ in 0..1000 {
+        let n = 12;
+        let mut m = Matching::new();
+        let mut dist = vec![vec![INF; n + 1]; n + 1];
+        for (i, row) in dist.iter_mut().enumerate() {
+            row[i] = 0;
+        }
+        let mut connect = |m: &mut Matching, a: usize, b: Option<usize>, w: u64| {
+            let bi = b.unwrap_or(n);
+            dist[a][bi] = dist[a][bi].min(w);
+            dist[bi][a] = dist[bi][a].min(w);
+            match b {
+                Some(b) => m.add_edge(a, b, w as f64, &[], 0.1),
+                None => m.add_boundary_edge(a, w as f64, &[], 0.1),
+            }
+        };
+        for i in 0..n - 1 {
+            let w = 1 + rng.below(6);
+            connect(&mut m, i, Some(i + 1), w);
+        }
+        for _ in 0..6 {
+            let (a, b) = (rng.below(n as u64) as usize, rng.below(n as u64) as usize);
+            if a != b {
+                let w = 1 + rng.below(6);
+                connect(&mut m, a, Some(b), w);
+            }
+        }
+        for _ in 0..2 {
+            let a = rng.below(n as u64) as usize;
+            let w = 2 + rng.below(8);
+            connect(&mut m, a, None, w);
+        }
+        for k in 0..=n {
+            for i in 0..=n {
+                for j in 0..=n {
+                    dist[i][j] = dist[i][j].min(dist[i][k] + dist[k][j]);
+                }
+            }
+        }
+
+        let syndrome: Vec<u8> = random_syndrome(&mut rng, n, 2);
+        let events: Vec<usize> = (0..n).filter(|&i| syndrome[i] == 1).collect();
+        let decoded: u64 = m
+            .decode_to_edges(&syndrome)
+            .iter()
+            .map(|&(a, b)| {
+                let b = if b < 0 { n } else { b as usize };
+                dist[a as usize][b]
+            })
+            .sum();
+        assert_eq!(
+            decoded,
+            brute_force_matching_weight(&dist, &events),
+            "syndrome {syndrome:?}"
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------
+// CSS code decoded one sector per graph
+// ---------------------------------------------------------------------------
+
+/// Rotated distance-3 surface code on data qubits `3 * row + col`. Builds
+/// the decoder for one sector from its checks: qubit `q` becomes an edge
+/// between the checks containing it, flipping `observable` if `q` is on
+/// the logical operator it anticommutes with.
+fn css_sector(checks: &[&[usize]], logical: &[usize], observable: usize) -> Matching {
+    let w = (0.9f64 / 0.1).ln();
+    let mut m = Matching::new();
+    for q in 0..9 {
+        let dets: Vec<usize> = (0..checks.len()).filter(|&c| checks[c].contains(&q)).collect();
+        let obs: &[usize] = if logical.contains(&q) { &[observable] } else { &[] };
+        match dets[..] {
+            [a] => m.add_boundary_edge(a, w, obs, 0.1),
+            [a, b] => m.add_edge(a, b, w, obs, 0.1),
+            _ => unreachable!("qubit {q} is in {} checks", dets.len()),
+        }
+    }
+    m
+}
+
+#[test]
+fn css_pair_decodes_both_sectors() {
+    let z_checks: &[&[usize]] = &[&[0, 1, 3, 4], &[4, 5, 7, 8], &[2, 5], &[3, 6]];
+    let x_checks: &[&[usize]] = &[&[1, 2, 4, 5], &[3, 4, 6, 7], &[0, 1], &[7, 8]];
+    // X errors are seen by Z checks and flip the Z logical (top row), as
+    // observable 0; Z errors are seen by X checks and flip the X logical
+    // (left column), as observable 1.
+    let x_decoder = css_sector(z_checks, &[0, 1, 2], 0);
+    let z_decoder = css_sector(x_checks, &[0, 3, 6], 1);
+    let mut pair = Matching::pair(x_decoder, z_decoder).unwrap();
+
+    let syndrome = |checks: &[&[usize]], error: Option<usize>| -> Vec<u8> {
+        checks.iter().map(|c| error.is_some_and(|q| c.contains(&q)) as u8).collect()
+    };
+    let errors: Vec<Option<usize>> = std::iter::once(None).chain((0..9).map(Some)).collect();
+    let mut x_syndromes = Vec::new();
+    let mut z_syndromes = Vec::new();
+    let mut expected = Vec::new();
+    for &x_error in &errors {
+        for &z_error in &errors {
+            let x_syndrome = syndrome(z_checks, x_error);
+            let z_syndrome = syndrome(x_checks, z_error);
+            let (x_pred, z_pred) = pair.decode(&x_syndrome, &z_syndrome);
+            // The X decoder only knows observable 0, the Z decoder both.
+            assert_eq!((x_pred.len(), z_pred.len()), (1, 2));
+            assert_eq!(
+                pair.decode_combined(&x_syndrome, &z_syndrome),
+                vec![x_pred[0] ^ z_pred[0], z_pred[1]],
+                "X error {x_error:?}, Z error {z_error:?}"
+            );
+            // Distance 3 corrects any single error in each sector, so the
+            // correction flips a logical exactly when the error did.
+            let flips = |e: Option<usize>, logical: &[usize]| e.is_some_and(|q| logical.contains(&q)) as u8;
+            assert_eq!(
+                pair.decode_combined(&x_syndrome, &z_syndrome),
+                vec![flips(x_error, &[0, 1, 2]), flips(z_error, &[0, 3, 6])]
+            );
+            x_syndromes.push(x_syndrome);
+            z_syndromes.push(z_syndrome);
+            expected.push((x_pred, z_pred));
+        }
+    }
+
+    let (x_batch, z_batch) = pair.decode_batch(&x_syndromes, &z_syndromes);
+    let batched: Vec<_> = x_batch.into_iter().zip(z_batch).collect();
+    assert_eq!(batched, expected);
+
+    // Both sectors reporting on observable 0 is rejected.
+    let err = Matching::pair(css_sector(z_checks, &[0, 1, 2], 0), css_sector(x_checks, &[0, 3, 6], 0))
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "observable 0 is flipped by both the X and the Z decoder");
+}
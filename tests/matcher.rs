@@ -115,6 +115,53 @@ fn mwpm_blossom_formation() {
     assert!(event_count >= 2, "Expected at least 2 events, got {}", event_count);
 }
 
+/// The root region itself collides with its own grandchild, so the common
+/// ancestor of the blossom is the root: a node with an outer region but no
+/// inner region or parent.
+#[test]
+fn mwpm_blossom_at_bare_root() {
+    //   0 --10-- 1
+    //    \       |
+    //     12     2
+    //      \     |
+    //        --- 2
+    // 1 and 2 match first; 0 then pulls them into its tree and hits 2.
+    let mut g = MatchingGraph::new(3, 2);
+    g.add_edge(0, 1, 10, &[]);
+    g.add_edge(1, 2, 2, &[1]);
+    g.add_edge(0, 2, 12, &[]);
+    g.add_boundary_edge(0, 30, &[0]);
+    let mut mwpm = Mwpm::new(GraphFlooder::new(g));
+    for i in 0..3 {
+        mwpm.create_detection_event(NodeIdx(i));
+    }
+
+    let mut blossom_at_root = false;
+    loop {
+        let event = mwpm.flooder.run_until_next_mwpm_notification();
+        if event.is_no_event() {
+            break;
+        }
+        mwpm.process_event(event);
+        for (i, region) in mwpm.flooder.region_arena.items().iter().enumerate() {
+            if !mwpm.flooder.region_arena.is_live(i as u32)
+                || region.blossom_children.is_empty() || region.blossom_parent.is_some() {
+                continue;
+            }
+            if let Some(node) = region.alt_tree_node {
+                let node = &mwpm.flooder.node_arena[node.0];
+                blossom_at_root |= node.parent.is_none() && node.inner_region.is_none();
+            }
+        }
+    }
+    assert!(blossom_at_root, "expected a blossom rooted at the tree root");
+
+    let top = mwpm.flooder.graph.nodes[0].region_that_arrived_top.unwrap();
+    let res = mwpm.shatter_blossom_and_extract_matches(top);
+    assert_eq!(res.obs_mask, 0b11);
+    assert_eq!(res.weight, 32);
+}
+
 #[test]
 fn mwpm_reset() {
     let mut mwpm = two_node_mwpm(10);
@@ -156,9 +203,13 @@ fn alt_tree_node_become_root() {
     let child_edge = AltTreeEdge::new(child_idx, edge);
     arena[root_idx.0].children.push(child_edge);
     arena[child_idx.0].parent = Some(AltTreeEdge::new(root_idx, edge.reversed()));
+    arena[child_idx.0].depth = 1;
 
     // Make child the root
-    AltTreeNode::become_root(child_idx, &mut arena);
+    let depth = AltTreeNode::become_root(child_idx, &mut arena);
+    assert_eq!(depth, 1);
+    assert_eq!(arena[child_idx.0].depth, 0);
+    assert_eq!(arena[root_idx.0].depth, 1);
 
     // Child should now be root (no parent)
     assert!(arena[child_idx.0].parent.is_none());
@@ -681,6 +732,37 @@ fn matching_result_add_assign() {
     assert_eq!(a.weight, 50);
 }
 
+#[test]
+fn matching_result_add_assign_large_weights_are_exact() {
+    use rmatching::matcher::mwpm::MatchingResult;
+    use rmatching::types::TotalWeight;
+
+    // Many maximal discretized edge weights stay exact right up to the limit.
+    let step = 2 * (1 << 24) as TotalWeight;
+    let count = TotalWeight::MAX / step;
+    let mut a = MatchingResult::new();
+    for _ in 0..1000 {
+        a += MatchingResult { obs_mask: 0, weight: step * (count / 1000) };
+    }
+    a += MatchingResult { obs_mask: 0, weight: step * (count % 1000) };
+    assert_eq!(a.weight, step * count);
+    assert!(TotalWeight::MAX - a.weight < step);
+}
+
+#[test]
+#[cfg_attr(debug_assertions, should_panic(expected = "matching weight overflow"))]
+fn matching_result_add_assign_does_not_wrap() {
+    use rmatching::matcher::mwpm::MatchingResult;
+    use rmatching::types::TotalWeight;
+
+    let mut a = MatchingResult { obs_mask: 0, weight: TotalWeight::MAX - 1 };
+    a += MatchingResult { obs_mask: 0, weight: 2 };
+    assert_eq!(a.weight, TotalWeight::MAX);
+    let mut b = MatchingResult { obs_mask: 0, weight: TotalWeight::MIN + 1 };
+    b += MatchingResult { obs_mask: 0, weight: -2 };
+    assert_eq!(b.weight, TotalWeight::MIN);
+}
+
 // ---------------------------------------------------------------------------
 // Coverage: repeated decode exercises reset + re-blossom paths
 // ---------------------------------------------------------------------------
@@ -708,3 +790,222 @@ fn mwpm_repeated_decode_with_blossoms() {
         assert_eq!(pred, vec![0]);
     }
 }
+
+// ---------------------------------------------------------------------------
+// Snapshot / restore
+// ---------------------------------------------------------------------------
+
+fn run_to_completion(mwpm: &mut Mwpm) -> Vec<MwpmEvent> {
+    let mut events = Vec::new();
+    loop {
+        let event = mwpm.flooder.run_until_next_mwpm_notification();
+        if event.is_no_event() {
+            break;
+        }
+        events.push(event.clone());
+        mwpm.process_event(event);
+    }
+    events
+}
+
+#[test]
+fn mwpm_snapshot_restore_round_trip() {
+    // Pentagon D0..D4 with a boundary edge on D0.
+    let mut g = MatchingGraph::new(5, 1);
+    for i in 0..5 {
+        g.add_edge(i, (i + 1) % 5, 10, &[]);
+    }
+    g.add_boundary_edge(0, 12, &[0]);
+    let mut mwpm = Mwpm::new(GraphFlooder::new(g));
+
+    mwpm.create_detection_event(NodeIdx(0));
+    mwpm.create_detection_event(NodeIdx(1));
+    mwpm.create_detection_event(NodeIdx(2));
+    let event = mwpm.flooder.run_until_next_mwpm_notification();
+    mwpm.process_event(event);
+
+    let snapshot = mwpm.snapshot();
+    let before = format!("{:?}", mwpm.flooder);
+    let stats_before = mwpm.stats;
+
+    // Diverge: add another event and finish the matching.
+    mwpm.create_detection_event(NodeIdx(3));
+    run_to_completion(&mut mwpm);
+    assert_ne!(format!("{:?}", mwpm.flooder), before);
+    assert_ne!(mwpm.stats, stats_before);
+
+    mwpm.restore(snapshot.clone());
+    assert_eq!(format!("{:?}", mwpm.flooder), before);
+    assert_eq!(mwpm.stats, stats_before);
+
+    // Finishing from the snapshot twice gives the same events and end state.
+    let events_a = format!("{:?}", run_to_completion(&mut mwpm));
+    let after_a = format!("{:?}", mwpm.flooder);
+    let stats_a = mwpm.stats;
+    mwpm.restore(snapshot);
+    let events_b = format!("{:?}", run_to_completion(&mut mwpm));
+    assert_eq!(events_a, events_b);
+    assert_eq!(format!("{:?}", mwpm.flooder), after_a);
+    assert_eq!(mwpm.stats, stats_a);
+}
+
+// ---------------------------------------------------------------------------
+// Streaming match extraction
+// ---------------------------------------------------------------------------
+
+#[test]
+fn mwpm_reset_mid_blossom_leaves_next_decode_clean() {
+    let graph = || {
+        let mut g = MatchingGraph::new(6, 2);
+        g.add_edge(0, 1, 10, &[0]);
+        g.add_edge(1, 2, 10, &[]);
+        g.add_edge(2, 3, 10, &[1]);
+        g.add_edge(3, 4, 10, &[]);
+        g.add_edge(4, 0, 10, &[]);
+        g.add_edge(4, 5, 12, &[]);
+        g.add_boundary_edge(0, 14, &[1]);
+        g
+    };
+    let mut mwpm = Mwpm::new(GraphFlooder::new(graph()));
+    for n in [0, 1, 2] {
+        mwpm.create_detection_event(NodeIdx(n));
+    }
+    // Stop as soon as a blossom is live in a tree.
+    loop {
+        let event = mwpm.flooder.run_until_next_mwpm_notification();
+        assert!(!event.is_no_event(), "decode finished without forming a blossom");
+        mwpm.process_event(event);
+        let regions = mwpm.flooder.region_arena.items();
+        let live_blossom = (0..regions.len()).any(|i| {
+            mwpm.flooder.region_arena.is_live(i as u32)
+                && !regions[i].blossom_children.is_empty()
+                && regions[i].alt_tree_node.is_some()
+        });
+        if live_blossom {
+            break;
+        }
+    }
+
+    mwpm.reset();
+    assert!(mwpm.flooder.region_arena.is_empty());
+    assert!(mwpm.flooder.node_arena.is_empty());
+    for node in &mwpm.flooder.graph.nodes {
+        assert_eq!(node.region_that_arrived, None);
+        assert_eq!(node.region_that_arrived_top, None);
+        assert_eq!(node.wrapped_radius_cached, 0);
+    }
+
+    // The next decode matches one on a fresh matcher, events and all.
+    let mut fresh = Mwpm::new(GraphFlooder::new(graph()));
+    for m in [&mut mwpm, &mut fresh] {
+        m.create_detection_event(NodeIdx(3));
+        m.create_detection_event(NodeIdx(5));
+    }
+    assert_eq!(
+        format!("{:?}", run_to_completion(&mut mwpm)),
+        format!("{:?}", run_to_completion(&mut fresh))
+    );
+    let top = mwpm.flooder.graph.nodes[3].region_that_arrived_top.unwrap();
+    let got = mwpm.shatter_blossom_and_extract_matches(top);
+    assert_eq!(got.obs_mask, 0);
+    assert_eq!(got.weight, 22);
+}
+
+#[test]
+fn mwpm_streamed_matches_agree_with_buffered() {
+    // Pentagon whose three firing detectors form a blossom that then
+    // matches to the boundary through D0.
+    let build = || {
+        let mut g = MatchingGraph::new(5, 2);
+        g.add_edge(0, 1, 10, &[0]);
+        g.add_edge(1, 2, 10, &[]);
+        g.add_edge(2, 3, 10, &[1]);
+        g.add_edge(3, 4, 10, &[]);
+        g.add_edge(4, 0, 10, &[]);
+        g.add_boundary_edge(0, 14, &[1]);
+        let mut mwpm = Mwpm::new(GraphFlooder::new(g));
+        for n in [0, 1, 2] {
+            mwpm.create_detection_event(NodeIdx(n));
+        }
+        run_to_completion(&mut mwpm);
+        mwpm
+    };
+
+    let mut buffered = build();
+    let top = buffered.flooder.graph.nodes[0].region_that_arrived_top.unwrap();
+    assert!(!buffered.flooder.region_arena[top.0].blossom_children.is_empty());
+    let expected = buffered.shatter_blossom_and_extract_matches(top);
+
+    let mut streamed = build();
+    let top = streamed.flooder.graph.nodes[0].region_that_arrived_top.unwrap();
+    let mut got = rmatching::matcher::mwpm::MatchingResult::new();
+    let mut num_matches = 0;
+    streamed.shatter_blossom_and_stream_matches(top, &mut |edge, weight| {
+        got.obs_mask ^= edge.obs_mask;
+        got.weight += weight;
+        num_matches += 1;
+    });
+
+    assert_eq!(got, expected);
+    assert_eq!(num_matches, 2);
+}
+
+// ---------------------------------------------------------------------------
+// Matching symmetry invariant
+// ---------------------------------------------------------------------------
+
+#[test]
+fn mwpm_verify_matching_symmetry_catches_one_sided_match() {
+    // Chain D0 - D1 - D2 - D3: the only perfect matching is D0-D1, D2-D3.
+    let mut g = MatchingGraph::new(4, 1);
+    g.add_edge(0, 1, 10, &[]);
+    g.add_edge(1, 2, 4, &[]);
+    g.add_edge(2, 3, 10, &[]);
+    let mut mwpm = Mwpm::new(GraphFlooder::new(g));
+    for n in 0..4 {
+        mwpm.create_detection_event(NodeIdx(n));
+    }
+    run_to_completion(&mut mwpm);
+    assert_eq!(mwpm.verify_matching_symmetry(), Ok(()));
+
+    let r2 = mwpm.flooder.graph.nodes[2].region_that_arrived_top.unwrap();
+    let r3 = mwpm.flooder.graph.nodes[3].region_that_arrived_top.unwrap();
+    assert_eq!(
+        mwpm.flooder.region_arena[r2.0].match_.as_ref().unwrap().region,
+        Some(r3)
+    );
+    mwpm.flooder.region_arena[r3.0].match_ = None;
+    assert!(mwpm.verify_matching_symmetry().is_err());
+}
+
+#[test]
+fn mwpm_step_matches_batch_loop() {
+    // Pentagon with a boundary edge: forms and later shatters a blossom.
+    let build = || {
+        let mut g = MatchingGraph::new(5, 2);
+        g.add_edge(0, 1, 10, &[0]);
+        g.add_edge(1, 2, 10, &[]);
+        g.add_edge(2, 3, 10, &[1]);
+        g.add_edge(3, 4, 10, &[]);
+        g.add_edge(4, 0, 10, &[]);
+        g.add_boundary_edge(0, 14, &[1]);
+        let mut mwpm = Mwpm::new(GraphFlooder::new(g));
+        for n in [0, 1, 2] {
+            mwpm.create_detection_event(NodeIdx(n));
+        }
+        mwpm
+    };
+
+    let mut batch = build();
+    let expected = run_to_completion(&mut batch);
+
+    let mut stepped = build();
+    let mut events = Vec::new();
+    while let Some(event) = stepped.step() {
+        events.push(event);
+    }
+    assert!(!events.is_empty());
+    assert_eq!(format!("{events:?}"), format!("{expected:?}"));
+    assert_eq!(format!("{:?}", stepped.flooder), format!("{:?}", batch.flooder));
+    assert!(stepped.step().is_none());
+}
@@ -1,3 +1,4 @@
+use rmatching::MatchingError;
 use rmatching::search::{SearchFlooder, SearchGraph};
 use rmatching::types::*;
 
@@ -14,7 +15,7 @@ fn search_shortest_path() {
     let g = make_chain_graph();
     let mut flooder = SearchFlooder::new(g);
 
-    let edge = flooder.find_shortest_path(0, Some(2));
+    let edge = flooder.find_shortest_path(0, Some(2)).unwrap();
     assert_eq!(edge.loc_from, Some(NodeIdx(0)));
     assert_eq!(edge.loc_to, Some(NodeIdx(2)));
     // XOR of observables along the path: 0b01 ^ 0b10 = 0b11
@@ -26,7 +27,7 @@ fn search_shortest_path_reversed() {
     let g = make_chain_graph();
     let mut flooder = SearchFlooder::new(g);
 
-    let edge = flooder.find_shortest_path(2, Some(0));
+    let edge = flooder.find_shortest_path(2, Some(0)).unwrap();
     assert_eq!(edge.loc_from, Some(NodeIdx(2)));
     assert_eq!(edge.loc_to, Some(NodeIdx(0)));
     assert_eq!(edge.obs_mask, 0b11);
@@ -37,7 +38,7 @@ fn search_adjacent_nodes() {
     let g = make_chain_graph();
     let mut flooder = SearchFlooder::new(g);
 
-    let edge = flooder.find_shortest_path(0, Some(1));
+    let edge = flooder.find_shortest_path(0, Some(1)).unwrap();
     assert_eq!(edge.loc_from, Some(NodeIdx(0)));
     assert_eq!(edge.loc_to, Some(NodeIdx(1)));
     assert_eq!(edge.obs_mask, 0b01);
@@ -51,7 +52,7 @@ fn search_boundary_path() {
     g.add_boundary_edge(1, 5, 0b10);
 
     let mut flooder = SearchFlooder::new(g);
-    let edge = flooder.find_shortest_path(0, None);
+    let edge = flooder.find_shortest_path(0, None).unwrap();
     assert_eq!(edge.loc_from, Some(NodeIdx(0)));
     assert_eq!(edge.loc_to, None);
     // Path: 0->1 (obs 0b01) then 1->boundary (obs 0b10) => 0b11
@@ -64,7 +65,7 @@ fn search_boundary_direct() {
     g.add_boundary_edge(0, 7, 0b01);
 
     let mut flooder = SearchFlooder::new(g);
-    let edge = flooder.find_shortest_path(0, None);
+    let edge = flooder.find_shortest_path(0, None).unwrap();
     assert_eq!(edge.loc_from, Some(NodeIdx(0)));
     assert_eq!(edge.loc_to, None);
     assert_eq!(edge.obs_mask, 0b01);
@@ -75,11 +76,11 @@ fn search_reuse_after_reset() {
     let g = make_chain_graph();
     let mut flooder = SearchFlooder::new(g);
 
-    let e1 = flooder.find_shortest_path(0, Some(2));
+    let e1 = flooder.find_shortest_path(0, Some(2)).unwrap();
     assert_eq!(e1.obs_mask, 0b11);
 
     // Second search on the same flooder should work after implicit reset.
-    let e2 = flooder.find_shortest_path(0, Some(1));
+    let e2 = flooder.find_shortest_path(0, Some(1)).unwrap();
     assert_eq!(e2.obs_mask, 0b01);
 }
 
@@ -94,7 +95,7 @@ fn search_diamond_picks_shorter_path() {
     g.add_edge(2, 3, 10, 0b1000);
 
     let mut flooder = SearchFlooder::new(g);
-    let edge = flooder.find_shortest_path(0, Some(3));
+    let edge = flooder.find_shortest_path(0, Some(3)).unwrap();
     assert_eq!(edge.loc_from, Some(NodeIdx(0)));
     assert_eq!(edge.loc_to, Some(NodeIdx(3)));
     // Should take the short path: obs = 0b01 ^ 0b10 = 0b11
@@ -143,7 +144,8 @@ fn search_boundary_two_hops() {
     let mut edges = Vec::new();
     flooder.iter_edges_on_shortest_path(0, None, |from, to, obs| {
         edges.push((from, to, obs));
-    });
+    })
+    .unwrap();
 
     // Path should be 0->1->boundary with correct obs masks
     assert!(!edges.is_empty());
@@ -160,7 +162,7 @@ fn search_no_collision_disconnected() {
     // No edges added - nodes are disconnected
     let mut flooder = SearchFlooder::new(g);
 
-    let edge = flooder.find_shortest_path(0, Some(1));
+    let edge = flooder.find_shortest_path(0, Some(1)).unwrap();
     // Should not find a path
     assert_eq!(edge.loc_from, Some(NodeIdx(0)));
     // obs_mask should be 0 (no path found, so default)
@@ -206,7 +208,7 @@ fn search_long_chain_path() {
     g.add_edge(3, 4, 10, 0b01);
 
     let mut flooder = SearchFlooder::new(g);
-    let edge = flooder.find_shortest_path(0, Some(4));
+    let edge = flooder.find_shortest_path(0, Some(4)).unwrap();
     assert_eq!(edge.loc_from, Some(NodeIdx(0)));
     assert_eq!(edge.loc_to, Some(NodeIdx(4)));
     // obs = 0b01 ^ 0 ^ 0 ^ 0b01 = 0
@@ -221,8 +223,26 @@ fn search_iter_edges_node_to_node() {
     let mut collected = Vec::new();
     flooder.iter_edges_on_shortest_path(0, Some(2), |from, to, obs| {
         collected.push((from, to, obs));
-    });
+    })
+    .unwrap();
 
     // Should have 2 edges: 0->1 and 1->2
     assert_eq!(collected.len(), 2);
 }
+
+#[test]
+fn search_one_way_edges_fail_without_panicking() {
+    // Exploring 0 -> 1 on a chain needs the edge back to record the path.
+    let mut g = make_chain_graph();
+    let back_to_0 = g.nodes[1].index_of_neighbor(Some(SearchNodeIdx(0)));
+    g.nodes[1].neighbors.remove(back_to_0);
+    g.nodes[1].neighbor_weights.remove(back_to_0);
+    g.nodes[1].neighbor_observables.remove(back_to_0);
+    let mut flooder = SearchFlooder::new(g);
+    assert_eq!(
+        flooder.find_shortest_path(0, Some(2)).unwrap_err(),
+        MatchingError::InvalidGraph("search graph edge 0 -> 1 has no reverse edge".into())
+    );
+    // The failed search left nothing behind.
+    assert_eq!(flooder.find_shortest_path(1, Some(2)).unwrap().obs_mask, 0b10);
+}
@@ -1,4 +1,5 @@
 use rmatching::driver::user_graph::{UserGraph, NUM_DISTINCT_WEIGHTS};
+use rmatching::MatchingError;
 
 #[test]
 fn user_graph_add_edge() {
@@ -131,3 +132,239 @@ fn user_graph_get_mwpm_invalidation() {
     // This should rebuild
     let _ = g.get_mwpm();
 }
+
+#[test]
+fn user_graph_canonicalize_edges_ignores_construction_order() {
+    use rmatching::driver::dem_parse::parse_dem;
+
+    let mut a = parse_dem(
+        "error(0.1) D0 D1 L0\n\
+         error(0.2) D2\n\
+         error(0.05) D1 D2\n\
+         error(0.1) D0\n",
+    )
+    .unwrap();
+    let mut b = parse_dem(
+        "error(0.1) D0\n\
+         error(0.05) D2 D1\n\
+         error(0.2) D2\n\
+         error(0.1) D1 D0 L0\n",
+    )
+    .unwrap();
+
+    assert_eq!(a.canonicalize_edges(), vec![0, 3, 2, 1]);
+    assert_eq!(b.canonicalize_edges(), vec![3, 0, 1, 2]);
+
+    let key = |g: &UserGraph| -> Vec<(usize, usize, Vec<usize>, u64)> {
+        g.edges
+            .iter()
+            .map(|e| {
+                (
+                    e.node1.min(e.node2),
+                    e.node1.max(e.node2),
+                    e.observable_indices.clone(),
+                    e.weight.to_bits(),
+                )
+            })
+            .collect()
+    };
+    assert_eq!(key(&a), key(&b));
+    assert_eq!(a.edges[1].node2, usize::MAX);
+}
+
+#[test]
+fn user_graph_canonicalize_edges_orders_parallel_edges_of_equal_weight() {
+    let parallel: [(Vec<usize>, f64, f64); 4] = [
+        (vec![1], 0.0, 0.1),
+        (vec![0], 0.5, 0.1),
+        (vec![0], 0.0, 0.2),
+        (vec![0], 0.0, 0.1),
+    ];
+    let build = |order: &[usize]| {
+        let mut g = UserGraph::new();
+        for &i in order {
+            let (obs, secondary, p) = &parallel[i];
+            g.add_edge_with_secondary_weight(0, 1, obs.clone(), 1.0, *secondary, *p);
+        }
+        g.canonicalize_edges();
+        g.edges
+            .iter()
+            .map(|e| {
+                (
+                    e.observable_indices.clone(),
+                    e.secondary_weight.to_bits(),
+                    e.error_probability.to_bits(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let expected = build(&[3, 2, 1, 0]);
+    assert_eq!(expected[0], (vec![0], 0.0f64.to_bits(), 0.1f64.to_bits()));
+    assert_eq!(expected[3].0, vec![1]);
+    for order in [[0, 1, 2, 3], [2, 0, 3, 1], [1, 3, 0, 2]] {
+        assert_eq!(build(&order), expected);
+    }
+}
+
+#[test]
+fn user_graph_zero_weights_raised_to_minimum() {
+    let mut g = UserGraph::new();
+    g.add_edge(0, 1, vec![0], 0.0, 0.5);
+    g.add_edge(1, 2, vec![], 0.0, 0.5);
+    g.add_edge(2, 0, vec![], 0.0, 0.5);
+    g.add_boundary_edge(0, vec![], 3.0, 0.05);
+
+    let mg = g.to_matching_graph(NUM_DISTINCT_WEIGHTS);
+    for node in &mg.nodes {
+        assert!(node.neighbor_weights.iter().all(|&w| w >= 2));
+    }
+    let sg = g.to_search_graph(NUM_DISTINCT_WEIGHTS);
+    for node in &sg.nodes {
+        assert!(node.neighbor_weights.iter().all(|&w| w >= 2));
+    }
+}
+
+#[test]
+fn user_graph_matching_and_search_graph_node_counts_agree() {
+    let assert_counts_agree = |g: &UserGraph| {
+        let mg = g.to_matching_graph(NUM_DISTINCT_WEIGHTS);
+        let sg = g.to_search_graph(NUM_DISTINCT_WEIGHTS);
+        assert_eq!(mg.nodes.len(), g.get_num_nodes());
+        assert_eq!(sg.nodes.len(), g.get_num_nodes());
+    };
+
+    let mut g = UserGraph::new();
+    g.add_edge(0, 1, vec![0], 1.0, 0.1);
+    g.add_boundary_edge(1, vec![], 1.0, 0.1);
+    assert_counts_agree(&g);
+
+    // A boundary node past the last detector grows the node list.
+    g.set_boundary([5].into_iter().collect());
+    assert_counts_agree(&g);
+    g.add_edge(1, 5, vec![], 1.0, 0.1);
+    assert_counts_agree(&g);
+
+    // So does a detector-defined observable.
+    g.set_detector_observable(1, &[8]);
+    assert_counts_agree(&g);
+
+    g.set_boundary(std::collections::HashSet::new());
+    assert_counts_agree(&g);
+}
+
+#[test]
+fn user_graph_weight_horizon() {
+    // Every edge discretizes to the maximum weight (~2^25), so a node more
+    // than 64 edges from the boundary can outrun the 2^31 queue horizon.
+    let chain = |len: usize, with_boundary: bool| {
+        let mut g = UserGraph::new();
+        if with_boundary {
+            g.add_boundary_edge(0, vec![], 1.5, 0.1);
+        }
+        for i in 0..len - 1 {
+            g.add_edge(i, i + 1, vec![], 1.5, 0.1);
+        }
+        g
+    };
+
+    assert!(chain(30, true).check_weight_horizon(NUM_DISTINCT_WEIGHTS).is_ok());
+    assert!(chain(30, true).try_to_mwpm().is_ok());
+
+    let err = chain(100, true).try_to_mwpm().err().expect("horizon exceeded");
+    assert!(matches!(err, MatchingError::WeightHorizonExceeded { .. }), "{err:?}");
+    let err = err.to_string();
+    assert!(err.contains("2^31"), "{err}");
+    assert!(err.contains("num_distinct_weights"), "{err}");
+
+    // Without a boundary a region may grow across the whole component.
+    assert!(chain(30, false).check_weight_horizon(NUM_DISTINCT_WEIGHTS).is_ok());
+    assert!(chain(40, false).check_weight_horizon(NUM_DISTINCT_WEIGHTS).is_err());
+
+    // Fewer distinct weights shrink every edge and bring it back in range.
+    assert!(chain(100, true).check_weight_horizon(1 << 16).is_ok());
+}
+
+#[test]
+fn user_graph_independent_merge_combines_probabilities() {
+    use rmatching::driver::dem_parse::{parse_dem, parse_dem_with_merge_strategy};
+    use rmatching::driver::user_graph::MergeStrategy;
+
+    let dem = "error(0.1) D0 D1\n\
+               error(0.1) D1 D0\n\
+               error(0.1) D0 D1 L0\n\
+               error(0.2) D1\n";
+    assert_eq!(parse_dem(dem).unwrap().get_num_edges(), 4);
+
+    let g = parse_dem_with_merge_strategy(dem, MergeStrategy::Independent).unwrap();
+    assert_eq!(g.get_num_edges(), 3);
+    let p = g.edges[0].error_probability;
+    assert!((p - 0.18).abs() < 1e-12, "merged probability {p}");
+    assert!((g.edges[0].weight - (0.82f64 / 0.18).ln()).abs() < 1e-12);
+    // Different observables stay a separate edge.
+    assert_eq!(g.edges[1].error_probability, 0.1);
+    assert_eq!(g.edges[1].observable_indices, vec![0]);
+
+    // Merging an edge with itself again keeps the probability in [0, 1].
+    let g = parse_dem_with_merge_strategy("repeat 5 {\n error(0.9) D0\n}\n", MergeStrategy::Independent)
+        .unwrap();
+    assert_eq!(g.get_num_edges(), 1);
+    assert!((0.0..=1.0).contains(&g.edges[0].error_probability));
+    g.check_error_probabilities().unwrap();
+
+    // Out-of-range probabilities follow the usual rules instead of merging:
+    // p <= 0 is dropped and p >= 1 becomes a certain fault.
+    let dem = "error(0.1) D0\nerror(1.5) D0 L0\nerror(-0.2) D0 L1\nerror(0) D0\n";
+    let g = parse_dem_with_merge_strategy(dem, MergeStrategy::Independent).unwrap();
+    assert_eq!(g.get_num_edges(), 1);
+    assert_eq!(g.edges[0].error_probability, 0.1);
+    assert_eq!(g.flipped_observables(), vec![0]);
+    g.check_error_probabilities().unwrap();
+
+    let mut g = UserGraph::new();
+    g.add_edge(0, 1, vec![], 1.0, 1.5);
+    assert_eq!(
+        g.check_error_probabilities(),
+        Err(MatchingError::InvalidProbability { edge: 0, probability: 1.5 })
+    );
+    g.set_require_valid_probabilities(true);
+    assert!(matches!(g.try_to_mwpm(), Err(MatchingError::InvalidProbability { .. })));
+}
+
+#[test]
+fn user_graph_dem_coordinates_and_weight_scaling() {
+    use rmatching::driver::dem_parse::parse_dem;
+
+    // Two rounds of a two-detector repetition code; the second round is
+    // reached through a coordinate shift along the time axis.
+    let dem = "detector(0, 0) D0\n\
+               detector(2, 0) D1\n\
+               error(0.1) D0 D1\n\
+               error(0.1) D0\n\
+               error(0.01) D0 D2\n\
+               shift_detectors(0, 1) 2\n\
+               detector(0, 0) D0\n\
+               detector(2, 0) D1\n\
+               error(0.1) D0 D1\n";
+    let mut g = parse_dem(dem).unwrap();
+    assert_eq!(g.nodes[1].coordinates, vec![2.0, 0.0]);
+    assert_eq!(g.nodes[3].coordinates, vec![2.0, 1.0]);
+
+    let before: Vec<f64> = g.edges.iter().map(|e| e.weight).collect();
+    assert_eq!(g.scale_weights_by_coordinate(1, 0.5), Ok(1));
+    // Space-like and boundary edges keep their weights; the time-like D0-D2
+    // edge is halved.
+    assert_eq!(g.edges[0].weight, before[0]);
+    assert_eq!(g.edges[1].weight, before[1]);
+    assert_eq!(g.edges[2].weight, before[2] * 0.5);
+    assert_eq!(g.edges[3].weight, before[3]);
+
+    // Missing coordinates fail without changing any weight.
+    let weights: Vec<f64> = g.edges.iter().map(|e| e.weight).collect();
+    assert!(g.scale_weights_by_coordinate(2, 3.0).is_err());
+    g.add_edge(1, 4, vec![], 1.0, 0.1);
+    let err = g.scale_weights_by_coordinate(0, 3.0).unwrap_err();
+    assert!(matches!(err, MatchingError::InvalidGraph(_)), "{err:?}");
+    assert!(err.to_string().contains("detector 4"));
+    assert_eq!(&g.edges.iter().map(|e| e.weight).collect::<Vec<_>>()[..4], &weights[..]);
+}
@@ -169,6 +169,42 @@ fn arena_clear() {
     assert!(arena.is_empty());
 }
 
+#[test]
+fn arena_compact_drops_trailing_free_slots() {
+    let mut arena: Arena<u64> = Arena::new();
+    let indices: Vec<u32> = (0..100).map(|_| arena.alloc()).collect();
+    for &i in &indices {
+        arena[i] = i as u64 * 10;
+    }
+    // Free everything except slots 3 and 40, in several cycles.
+    for _ in 0..5 {
+        for &i in &indices[50..] {
+            arena.free(i);
+        }
+        for _ in 50..100 {
+            arena.alloc();
+        }
+    }
+    for &i in &indices {
+        if i != 3 && i != 40 {
+            arena.free(i);
+        }
+    }
+    assert_eq!(arena.num_slots(), 100);
+
+    arena.compact();
+    assert_eq!(arena.num_slots(), 41);
+    assert_eq!(arena.len(), 2);
+    assert_eq!(arena[3], 30);
+    assert_eq!(arena[40], 400);
+
+    // Freed slots below the last live one are still reused; new slots
+    // extend past it.
+    let reused: Vec<u32> = (0..39).map(|_| arena.alloc()).collect();
+    assert!(reused.iter().all(|&i| i < 40 && i != 3));
+    assert_eq!(arena.alloc(), 41);
+}
+
 // ---- RadixHeapQueue tests ----
 
 /// Minimal event type for testing.